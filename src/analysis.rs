@@ -0,0 +1,426 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use crate::coverage::Coverage;
+use crate::cpu::Memory;
+use crate::decoder::{decode, DecodedInstruction, Operand};
+use crate::instruction::{AddressingMode, Instruction};
+use crate::symbols::SymbolTable;
+
+const NMI_VECTOR: u16 = 0xFFFA;
+const RESET_VECTOR: u16 = 0xFFFC;
+const IRQ_VECTOR: u16 = 0xFFFE;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ByteKind {
+    Unknown,
+    Opcode,
+    Operand,
+    /* Accessed by an instruction without being executed */
+    Data,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    Fallthrough,
+    Branch,
+    Jump,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    pub target: u16,
+    pub kind: EdgeKind,
+}
+
+/* A run of instructions only entered at the top and only left at the bottom, subroutine calls aside */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: u16,
+    /* The addresses of the instructions, the last one transfers control */
+    pub instructions: Vec<u16>,
+    pub successors: Vec<Edge>,
+    pub calls: Vec<u16>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subroutine {
+    pub entry: u16,
+    /* The starts of the blocks reachable from the entry without following calls */
+    pub blocks: Vec<u16>,
+    pub callers: Vec<u16>,
+}
+
+/*
+ * Separates code from data by following the control flow from the vectors and the given
+ * entry points, decoding only bytes that can actually be executed.
+ */
+#[derive(Clone, Debug)]
+pub struct Analyzer {
+    pub entry_points: Vec<u16>,
+    /* Starts from the NMI, reset and IRQ vectors as well */
+    pub use_vectors: bool,
+}
+
+impl Default for Analyzer {
+    fn default() -> Analyzer {
+        Analyzer::new()
+    }
+}
+
+impl Analyzer {
+    pub fn new() -> Analyzer {
+        Analyzer { entry_points: Vec::new(), use_vectors: true }
+    }
+
+    pub fn analyze(&self, memory: &dyn Memory) -> Analysis {
+        self.run(memory, None)
+    }
+
+    /*
+     * Analyzes with the help of a recorded run: the destinations of indirect jumps and
+     * interrupts are followed as well, code only reached in ways the analysis can't see is
+     * decoded where it was executed and everything read or written becomes data.
+     */
+    pub fn analyze_with_coverage(&self, memory: &dyn Memory, coverage: &Coverage) -> Analysis {
+        self.run(memory, Some(coverage))
+    }
+
+    fn run(&self, memory: &dyn Memory, coverage: Option<&Coverage>) -> Analysis {
+        let mut roots = self.entry_points.clone();
+        if self.use_vectors {
+            for vector in [NMI_VECTOR, RESET_VECTOR, IRQ_VECTOR] {
+                roots.push(u16::from_le_bytes([memory.get(vector), memory.get(vector.wrapping_add(1))]));
+            }
+        }
+        if let Some(coverage) = coverage {
+            roots.extend(&coverage.interrupts);
+        }
+
+        let mut analysis = Analysis {
+            kinds: vec![ByteKind::Unknown; 0x10000],
+            instructions: BTreeMap::new(),
+            blocks: BTreeMap::new(),
+            subroutines: BTreeMap::new(),
+            jumps: coverage.map(|coverage| coverage.jumps.clone()).unwrap_or_default(),
+        };
+        let mut leaders: BTreeSet<u16> = roots.iter().copied().collect();
+        let mut entries: BTreeMap<u16, BTreeSet<u16>> = roots.iter().map(|root| (*root, BTreeSet::new())).collect();
+        let mut pending = roots;
+        for targets in analysis.jumps.values() {
+            leaders.extend(targets);
+            pending.extend(targets);
+        }
+        analysis.follow(memory, pending, &mut leaders, &mut entries);
+
+        if let Some(coverage) = coverage {
+            /* Lowest first, so the first address of every missed run of code starts a walk */
+            let missed: Vec<u16> = coverage.executed()
+                .filter(|address| analysis.kind(*address) == ByteKind::Unknown)
+                .collect();
+            for address in missed {
+                if analysis.kind(address) == ByteKind::Unknown {
+                    leaders.insert(address);
+                    analysis.follow(memory, vec![address], &mut leaders, &mut entries);
+                }
+            }
+            for address in 0..=0xFFFF {
+                if analysis.kind(address) == ByteKind::Unknown && (coverage.is_read(address) || coverage.is_written(address)) {
+                    analysis.kinds[address as usize] = ByteKind::Data;
+                }
+            }
+        }
+
+        analysis.mark_data();
+        analysis.build_blocks(&leaders);
+        analysis.build_subroutines(entries);
+        analysis
+    }
+}
+
+/* The target of a branch, jump or call, None for indirect jumps and everything else */
+fn branch_target(instruction: &DecodedInstruction) -> Option<u16> {
+    match (instruction.mode(), instruction.operand) {
+        (AddressingMode::Relative, Operand::Byte(offset)) => {
+            Some(instruction.next_address().wrapping_add(offset as i8 as u16))
+        }
+        (AddressingMode::Absolute, Operand::Word(target)) => match instruction.instruction {
+            Instruction::Jump(_) | Instruction::JumpSubroutine(_) => Some(target),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn ends_block(instruction: &DecodedInstruction) -> bool {
+    matches!(instruction.instruction, Instruction::Jump(_) | Instruction::ReturnFromSubroutine(_)
+        | Instruction::ReturnFormInterrupt(_) | Instruction::Break(_))
+        || instruction.mode() == AddressingMode::Relative
+}
+
+/* The result of the analysis: what every byte is, and the basic blocks and subroutines of the code */
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    kinds: Vec<ByteKind>,
+    instructions: BTreeMap<u16, DecodedInstruction>,
+    pub blocks: BTreeMap<u16, BasicBlock>,
+    pub subroutines: BTreeMap<u16, Subroutine>,
+    /* The destinations seen at runtime for indirect jumps and RTS tricks */
+    jumps: BTreeMap<u16, BTreeSet<u16>>,
+}
+
+impl Analysis {
+    pub fn kind(&self, address: u16) -> ByteKind {
+        self.kinds[address as usize]
+    }
+
+    pub fn is_code(&self, address: u16) -> bool {
+        matches!(self.kind(address), ByteKind::Opcode | ByteKind::Operand)
+    }
+
+    /* The instruction starting at the address, if it was reached by the analysis */
+    pub fn instruction(&self, address: u16) -> Option<&DecodedInstruction> {
+        self.instructions.get(&address)
+    }
+
+    /* The block containing the instruction at the address */
+    pub fn block_of(&self, address: u16) -> Option<&BasicBlock> {
+        let (_, block) = self.blocks.range(..=address).next_back()?;
+        block.instructions.contains(&address).then_some(block)
+    }
+
+    /*
+     * Marks the bytes of the instruction at the address as code. Stops at illegal opcodes, at
+     * code already visited and at instructions overlapping other instructions.
+     */
+    fn claim(&mut self, memory: &dyn Memory, address: u16) -> Option<DecodedInstruction> {
+        if self.kind(address) != ByteKind::Unknown {
+            return None;
+        }
+        let instruction = decode(memory, address)?;
+        let operands = (1..instruction.length as u16).map(|offset| address.wrapping_add(offset));
+        if operands.clone().any(|operand| self.kind(operand) != ByteKind::Unknown) {
+            return None;
+        }
+
+        self.kinds[address as usize] = ByteKind::Opcode;
+        for operand in operands {
+            self.kinds[operand as usize] = ByteKind::Operand;
+        }
+        self.instructions.insert(address, instruction);
+        Some(instruction)
+    }
+
+    /* Decodes the code reachable from the pending addresses, collecting block starts and subroutine entries */
+    fn follow(&mut self, memory: &dyn Memory, mut pending: Vec<u16>, leaders: &mut BTreeSet<u16>,
+              entries: &mut BTreeMap<u16, BTreeSet<u16>>) {
+        while let Some(address) = pending.pop() {
+            let mut address = address;
+            loop {
+                let Some(instruction) = self.claim(memory, address) else {
+                    /* Flowing into code decoded before makes it the start of a block */
+                    if self.kind(address) == ByteKind::Opcode {
+                        leaders.insert(address);
+                    }
+                    break;
+                };
+                let target = branch_target(&instruction);
+                let next = instruction.next_address();
+                match instruction.instruction {
+                    Instruction::Jump(AddressingMode::Absolute) => {
+                        leaders.extend(target);
+                        pending.extend(target);
+                        break;
+                    }
+                    Instruction::JumpSubroutine(_) => {
+                        if let Some(target) = target {
+                            leaders.insert(target);
+                            entries.entry(target).or_default().insert(instruction.address);
+                            pending.push(target);
+                        }
+                    }
+                    Instruction::Jump(_) | Instruction::ReturnFromSubroutine(_)
+                    | Instruction::ReturnFormInterrupt(_) | Instruction::Break(_) => break,
+                    _ if instruction.mode() == AddressingMode::Relative => {
+                        leaders.extend(target);
+                        leaders.insert(next);
+                        pending.extend(target);
+                    }
+                    _ => {}
+                }
+                address = next;
+            }
+        }
+    }
+
+    /* Marks the bytes accessed through absolute and zeropage operands as data */
+    fn mark_data(&mut self) {
+        for instruction in self.instructions.values() {
+            if branch_target(instruction).is_some() {
+                continue;
+            }
+            let address = match (instruction.mode(), instruction.operand) {
+                (AddressingMode::Absolute | AddressingMode::AbsoluteXIndexed
+                 | AddressingMode::AbsoluteYIndexed, Operand::Word(address)) => address,
+                (AddressingMode::Zeropage | AddressingMode::ZeropageXIndexed | AddressingMode::ZeropageYIndexed
+                 | AddressingMode::XIndexedIndirect | AddressingMode::IndirectYIndexed, Operand::Byte(address)) => {
+                    address as u16
+                }
+                _ => continue,
+            };
+            if self.kinds[address as usize] == ByteKind::Unknown {
+                self.kinds[address as usize] = ByteKind::Data;
+            }
+        }
+    }
+
+    fn build_blocks(&mut self, leaders: &BTreeSet<u16>) {
+        for leader in leaders {
+            if !self.instructions.contains_key(leader) {
+                continue;
+            }
+            let mut block = BasicBlock { start: *leader, instructions: Vec::new(), successors: Vec::new(), calls: Vec::new() };
+            let mut address = *leader;
+            while let Some(instruction) = self.instructions.get(&address) {
+                block.instructions.push(address);
+                let target = branch_target(instruction);
+                let next = instruction.next_address();
+
+                match instruction.instruction {
+                    Instruction::JumpSubroutine(_) => block.calls.extend(target),
+                    Instruction::Jump(_) | Instruction::ReturnFromSubroutine(_) => {
+                        let dynamic = self.jumps.get(&address).into_iter().flatten().copied();
+                        block.successors.extend(target.into_iter().chain(dynamic)
+                            .map(|target| Edge { target, kind: EdgeKind::Jump }));
+                    }
+                    _ if instruction.mode() == AddressingMode::Relative => {
+                        block.successors.extend(target.map(|target| Edge { target, kind: EdgeKind::Branch }));
+                        block.successors.push(Edge { target: next, kind: EdgeKind::Fallthrough });
+                    }
+                    _ => {}
+                }
+                if ends_block(instruction) {
+                    break;
+                }
+                if leaders.contains(&next) || !self.instructions.contains_key(&next) {
+                    if self.instructions.contains_key(&next) {
+                        block.successors.push(Edge { target: next, kind: EdgeKind::Fallthrough });
+                    }
+                    break;
+                }
+                address = next;
+            }
+            block.successors.retain(|edge| self.instructions.contains_key(&edge.target));
+            self.blocks.insert(*leader, block);
+        }
+    }
+
+    fn build_subroutines(&mut self, entries: BTreeMap<u16, BTreeSet<u16>>) {
+        for (entry, callers) in entries {
+            if !self.blocks.contains_key(&entry) {
+                continue;
+            }
+            let mut reached = BTreeSet::from([entry]);
+            let mut pending = vec![entry];
+            while let Some(start) = pending.pop() {
+                for edge in &self.blocks[&start].successors {
+                    if reached.insert(edge.target) {
+                        pending.push(edge.target);
+                    }
+                }
+            }
+            let subroutine = Subroutine {
+                entry,
+                blocks: reached.into_iter().collect(),
+                callers: callers.into_iter().collect(),
+            };
+            self.subroutines.insert(entry, subroutine);
+        }
+    }
+
+    /* The name of a code address: its symbol, `sub_XXXX` for subroutines and `L_XXXX` for other blocks */
+    pub fn label(&self, address: u16, symbols: Option<&SymbolTable>) -> Option<String> {
+        if let Some(name) = symbols.and_then(|symbols| symbols.name_of(address)) {
+            return Some(name.to_string());
+        }
+        if self.subroutines.contains_key(&address) {
+            Some(format!("sub_{address:04X}"))
+        } else if self.blocks.contains_key(&address) {
+            Some(format!("L_{address:04X}"))
+        } else {
+            None
+        }
+    }
+
+    /* The control-flow graph in Graphviz DOT, one node per basic block with calls drawn dashed */
+    pub fn to_dot(&self, symbols: Option<&SymbolTable>) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let mut label = self.label(block.start, symbols).map(|name| format!("{name}:\\l")).unwrap_or_default();
+            for address in &block.instructions {
+                let instruction = &self.instructions[address];
+                label.push_str(&format!("{address:04X}  {}\\l", instruction.to_string().replace('"', "\\\"")));
+            }
+            writeln!(dot, "    b{:04X} [label=\"{label}\"];", block.start).unwrap();
+        }
+        for block in self.blocks.values() {
+            for edge in &block.successors {
+                let attributes = match edge.kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Branch => " [label=\"taken\"]",
+                    EdgeKind::Jump => " [style=bold]",
+                };
+                writeln!(dot, "    b{:04X} -> b{:04X}{attributes};", block.start, edge.target).unwrap();
+            }
+            for call in block.calls.iter().filter(|call| self.blocks.contains_key(call)) {
+                writeln!(dot, "    b{:04X} -> b{call:04X} [style=dashed, label=\"call\"];", block.start).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /*
+     * Disassembles the range with labels on blocks and subroutines and everything the control
+     * flow never reached written as `.byte` lines, bytes accessed as data starting a new line.
+     */
+    pub fn listing(&self, memory: &dyn Memory, start: u16, end: u16, symbols: Option<&SymbolTable>) -> String {
+        let mut lines = Vec::new();
+        let mut address = start as u32;
+        while address <= end as u32 {
+            let current = address as u16;
+            if let Some(subroutine) = self.subroutines.get(&current) {
+                let callers: Vec<String> = subroutine.callers.iter().map(|caller| format!("${caller:04X}")).collect();
+                match callers.is_empty() {
+                    true => lines.push("; entry point".to_string()),
+                    false => lines.push(format!("; called from {}", callers.join(", "))),
+                }
+            }
+            if let Some(label) = self.label(current, symbols) {
+                lines.push(format!("{label}:"));
+            }
+
+            if let Some(instruction) = self.instructions.get(&current) {
+                let bytes: Vec<String> = instruction.bytes().iter().map(|byte| format!("{byte:02X}")).collect();
+                lines.push(format!(".{current:04X}  {:<8}  {instruction}", bytes.join(" ")));
+                address += instruction.length as u32;
+                continue;
+            }
+
+            /* Up to three bytes per line so the hex column lines up with the instructions */
+            let mut bytes = vec![memory.get(current)];
+            while bytes.len() < 3 {
+                let next = address + bytes.len() as u32;
+                if next > end as u32 || self.kinds[next as usize] != ByteKind::Unknown
+                    || self.label(next as u16, symbols).is_some() {
+                    break;
+                }
+                bytes.push(memory.get(next as u16));
+            }
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            let values: Vec<String> = bytes.iter().map(|byte| format!("${byte:02X}")).collect();
+            lines.push(format!(".{current:04X}  {:<8}  .byte {}", hex.join(" "), values.join(",")));
+            address += bytes.len() as u32;
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,115 @@
+use crate::instruction::{AddressingMode, OPCODES};
+use crate::symbols::SymbolTable;
+
+/*
+ * Assembles a single instruction like `LDA ($FB),Y` located at the address. Values are
+ * decimal, `$` prefixed hex, `%` prefixed binary or symbols. Zero page addressing is
+ * picked whenever the value fits and the instruction supports it.
+ */
+pub fn assemble(line: &str, address: u16, symbols: Option<&SymbolTable>) -> Result<Vec<u8>, String> {
+    let line = line.trim();
+    let (mnemonic, operand) = match line.split_once(char::is_whitespace) {
+        Some((mnemonic, operand)) => (mnemonic, operand.trim()),
+        None => (line, ""),
+    };
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    if !OPCODES.iter().flatten().any(|instruction| instruction.mnemonic() == mnemonic) {
+        return Err(format!("Unknown instruction '{mnemonic}'"));
+    }
+
+    let operand = operand.replace(' ', "").to_ascii_uppercase();
+    let modes: Vec<(AddressingMode, Option<u16>)> = if operand.is_empty() {
+        vec![(AddressingMode::Implied, None), (AddressingMode::Accumulator, None)]
+    } else if operand == "A" {
+        vec![(AddressingMode::Accumulator, None)]
+    } else if let Some(value) = operand.strip_prefix('#') {
+        vec![(AddressingMode::Immediate, Some(parse_value(value, symbols)?))]
+    } else if let Some(value) = operand.strip_prefix('(').and_then(|value| value.strip_suffix(",X)")) {
+        vec![(AddressingMode::XIndexedIndirect, Some(parse_value(value, symbols)?))]
+    } else if let Some(value) = operand.strip_prefix('(').and_then(|value| value.strip_suffix("),Y")) {
+        vec![(AddressingMode::IndirectYIndexed, Some(parse_value(value, symbols)?))]
+    } else if let Some(value) = operand.strip_prefix('(').and_then(|value| value.strip_suffix(')')) {
+        vec![(AddressingMode::Indirect, Some(parse_value(value, symbols)?))]
+    } else if let Some(value) = operand.strip_suffix(",X") {
+        let value = parse_value(value, symbols)?;
+        vec![(AddressingMode::ZeropageXIndexed, Some(value)), (AddressingMode::AbsoluteXIndexed, Some(value))]
+    } else if let Some(value) = operand.strip_suffix(",Y") {
+        let value = parse_value(value, symbols)?;
+        vec![(AddressingMode::ZeropageYIndexed, Some(value)), (AddressingMode::AbsoluteYIndexed, Some(value))]
+    } else {
+        let value = parse_value(&operand, symbols)?;
+        vec![(AddressingMode::Relative, Some(value)), (AddressingMode::Zeropage, Some(value)),
+             (AddressingMode::Absolute, Some(value))]
+    };
+
+    for (mode, value) in modes {
+        if mode.operand_length() == 1 && mode != AddressingMode::Relative && value.unwrap_or(0) > 0xFF {
+            continue;
+        }
+        let Some(opcode) = find_opcode(&mnemonic, mode) else {
+            continue;
+        };
+
+        let value = value.unwrap_or(0);
+        return Ok(match mode {
+            AddressingMode::Relative => {
+                let offset = value as i32 - (address as i32 + 2);
+                if !(-128..=127).contains(&offset) {
+                    return Err(format!("Branch target ${value:04X} is out of range"));
+                }
+                vec![opcode, offset as i8 as u8]
+            }
+            mode if mode.operand_length() == 2 => vec![opcode, value as u8, (value >> 8) as u8],
+            mode if mode.operand_length() == 1 => vec![opcode, value as u8],
+            _ => vec![opcode],
+        });
+    }
+    Err(format!("Invalid addressing mode for {mnemonic}"))
+}
+
+fn find_opcode(mnemonic: &str, mode: AddressingMode) -> Option<u8> {
+    OPCODES.iter().position(|instruction| match instruction {
+        Some(instruction) => instruction.mnemonic() == mnemonic && instruction.mode() == mode,
+        None => false,
+    }).map(|opcode| opcode as u8)
+}
+
+/* Parses a decimal, `$` or `0x` prefixed hex or `%` prefixed binary number */
+pub fn parse_number(text: &str) -> Option<u64> {
+    if let Some(hex) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix('%') {
+        u64::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/* Like parse_number, but only accepts numbers that fit an address */
+pub fn parse_address(text: &str) -> Option<u16> {
+    parse_number(text)?.try_into().ok()
+}
+
+/*
+ * Parses an address the way the monitor and gdb do, hex with or without a `$` or `0x` prefix,
+ * unless a `+` marks it as decimal or a `%` as binary. The command line tools take addresses this way.
+ */
+pub fn parse_hex_address(text: &str) -> Option<u16> {
+    match text.strip_prefix('+') {
+        Some(decimal) => decimal.parse().ok(),
+        None if text.starts_with(['$', '%']) || text.starts_with("0x") => parse_address(text),
+        None => u16::from_str_radix(text, 16).ok(),
+    }
+}
+
+fn parse_value(text: &str, symbols: Option<&SymbolTable>) -> Result<u16, String> {
+    let value = if text.starts_with(|character: char| character == '$' || character == '%' || character.is_ascii_digit()) {
+        parse_address(text)
+    } else {
+        /* Symbols keep their case, so look them up without the uppercasing */
+        symbols.and_then(|symbols| symbols.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(text))
+            .map(|(_, address)| address))
+    };
+    value.ok_or(format!("Invalid value '{text}'"))
+}
@@ -0,0 +1,107 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::process::exit;
+use std::rc::Rc;
+use m6052_emulator::analysis::Analyzer;
+use m6052_emulator::assembler::parse_hex_address;
+use m6052_emulator::coverage::Coverage;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::ram::Ram;
+use m6052_emulator::runner::Runner;
+use m6052_emulator::symbols::SymbolTable;
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {program} [options] <image> [address]");
+    eprintln!("Loads the image at the address, or at the end of memory without one, and prints");
+    eprintln!("an annotated disassembly of it separating code reachable from the entry points from data.");
+    eprintln!("Addresses are hex, with an optional $ or 0x prefix, or decimal with a + prefix.");
+    eprintln!("Options:");
+    eprintln!("  --entry <address>         Follow the code from the address as well");
+    eprintln!("  --no-vectors              Don't start from the NMI, reset and IRQ vectors");
+    eprintln!("  --labels <file>           Name addresses after a VICE label file or name = address list");
+    eprintln!("  --dot <file>              Write the control-flow graph in Graphviz DOT");
+    eprintln!("  --run <cycles>            Run the program first and use what it executed, read and wrote");
+    eprintln!("  --start <address>         Run from here instead of from the reset vector");
+    exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut analyzer = Analyzer::new();
+    let mut positional = Vec::new();
+    let mut symbols = None;
+    let mut dot = None;
+    let mut run = None;
+    let mut start = None;
+
+    let mut arguments = args[1..].iter();
+    while let Some(argument) = arguments.next() {
+        let mut value = || arguments.next().unwrap_or_else(|| usage(&args[0])).as_str();
+        match argument.as_str() {
+            "--entry" => analyzer.entry_points.push(parse_hex_address(value()).unwrap_or_else(|| usage(&args[0]))),
+            "--no-vectors" => analyzer.use_vectors = false,
+            "--labels" => symbols = Some(SymbolTable::load(value()).unwrap_or_else(|error| {
+                eprintln!("{error}");
+                exit(2);
+            })),
+            "--dot" => dot = Some(value().to_string()),
+            "--run" => run = Some(value().parse::<u64>().unwrap_or_else(|_| usage(&args[0]))),
+            "--start" => start = Some(parse_hex_address(value()).unwrap_or_else(|| usage(&args[0]))),
+            option if option.starts_with("--") => usage(&args[0]),
+            _ => positional.push(argument.as_str()),
+        }
+    }
+    if positional.is_empty() || positional.len() > 2 {
+        usage(&args[0]);
+    }
+
+    let image = fs::read(positional[0]).unwrap_or_else(|error| {
+        eprintln!("Couldn't read {}: {error}", positional[0]);
+        exit(2);
+    });
+    if image.is_empty() || image.len() > 0x10000 {
+        eprintln!("{} doesn't fit into memory", positional[0]);
+        exit(2);
+    }
+    let address = match positional.get(1) {
+        Some(address) => parse_hex_address(address).unwrap_or_else(|| usage(&args[0])),
+        None => (0x10000 - image.len()) as u16,
+    };
+    let end = address as usize + image.len() - 1;
+    if end > 0xFFFF {
+        eprintln!("{} doesn't fit into memory at ${address:04X}", positional[0]);
+        exit(2);
+    }
+
+    let mut ram = Ram::new();
+    ram.load(address, &image);
+    let analysis = match run {
+        Some(cycles) => {
+            let mut memory = ram.clone();
+            let coverage = Rc::new(RefCell::new(Coverage::new()));
+            let mut cpu = CPU::new(&mut memory);
+            cpu.observers.push(Box::new(coverage.clone()));
+            cpu.program_counter = start.unwrap_or_else(|| {
+                u16::from_le_bytes([cpu.memory.get(0xFFFC), cpu.memory.get(0xFFFD)])
+            });
+            let mut runner = Runner::new();
+            runner.cycle_limit = Some(cycles);
+            runner.stop_on_trap = true;
+            let halt = runner.run(&mut cpu);
+            eprintln!("{halt} after {} cycles", cpu.total_cycles);
+            drop(cpu);
+            let coverage = coverage.borrow();
+            analyzer.analyze_with_coverage(&ram, &coverage)
+        }
+        None => analyzer.analyze(&ram),
+    };
+
+    if let Some(dot) = dot {
+        if let Err(error) = fs::write(&dot, analysis.to_dot(symbols.as_ref())) {
+            eprintln!("Couldn't write {dot}: {error}");
+            exit(1);
+        }
+    }
+    println!("{}", analysis.listing(&ram, address, end as u16, symbols.as_ref()));
+}
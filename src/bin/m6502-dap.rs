@@ -0,0 +1,18 @@
+use std::io;
+use std::io::BufReader;
+use std::process::exit;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::dap::DapServer;
+use m6052_emulator::ram::Ram;
+
+/* Speaks the debug adapter protocol on stdin and stdout, the program is given by the launch request */
+fn main() {
+    let mut ram = Ram::new();
+    let mut cpu = CPU::new(&mut ram);
+    let mut server = DapServer::new();
+
+    if let Err(error) = server.serve(&mut cpu, BufReader::new(io::stdin()), &mut io::stdout()) {
+        eprintln!("The debug adapter failed: {error}");
+        exit(1);
+    }
+}
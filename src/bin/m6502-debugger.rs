@@ -0,0 +1,133 @@
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::panic;
+use std::process::{exit, Command, Stdio};
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::debugger::{Debugger, Key};
+use m6052_emulator::ram::Ram;
+
+/* Runs stty on the controlling terminal, which keeps this free of platform specific dependencies */
+fn stty(arguments: &[&str]) -> Option<String> {
+    let terminal = File::open("/dev/tty").ok()?;
+    let output = Command::new("stty")
+        .args(arguments)
+        .stdin(Stdio::from(terminal))
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    match output.status.success() {
+        true => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        false => None,
+    }
+}
+
+fn terminal_size() -> (usize, usize) {
+    let size = stty(&["size"]).unwrap_or_default();
+    let mut numbers = size.split_whitespace().filter_map(|number| number.parse().ok());
+    match (numbers.next(), numbers.next()) {
+        (Some(rows), Some(columns)) => (columns, rows),
+        _ => (80, 24),
+    }
+}
+
+fn restore_terminal(settings: &str) {
+    print!("\x1b[?25h\x1b[?1049l");
+    io::stdout().flush().expect("Couldn't restore the terminal.");
+    stty(&[settings]);
+}
+
+/* Reads a byte, or None once the terminal's read timeout of a tenth of a second passed */
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    Ok(match input.read(&mut byte)? {
+        0 => None,
+        _ => Some(byte[0]),
+    })
+}
+
+fn read_key(input: &mut impl Read) -> Option<Key> {
+    let byte = loop {
+        if let Some(byte) = read_byte(input).ok()? {
+            break byte;
+        }
+    };
+    Some(match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7F | 0x08 => Key::Backspace,
+        /* A lone escape isn't followed by the rest of a sequence before the timeout */
+        0x1B => match read_byte(input).ok()? {
+            Some(b'[') => match read_byte(input).ok()? {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(code @ (b'5' | b'6')) => {
+                    read_byte(input).ok()?;
+                    match code {
+                        b'5' => Key::PageUp,
+                        _ => Key::PageDown,
+                    }
+                }
+                _ => Key::Escape,
+            },
+            _ => Key::Escape,
+        },
+        byte => Key::Char(byte as char),
+    })
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("Usage: {} <image> [address]", args[0]);
+        exit(2);
+    }
+
+    let mut debugger = Debugger::new();
+    let image = fs::read(&args[1]).unwrap_or_else(|error| {
+        eprintln!("Couldn't read {}: {error}", args[1]);
+        exit(2);
+    });
+    let start = args.get(2).map(|address| debugger.monitor.parse_value(address).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        exit(2);
+    }));
+
+    let mut ram = Ram::new();
+    ram.load(start.unwrap_or(0x10000_usize.saturating_sub(image.len()) as u16), &image);
+    let mut cpu = CPU::new(&mut ram);
+    cpu.program_counter = start.unwrap_or_else(|| {
+        u16::from_le_bytes([cpu.memory.get(0xFFFC), cpu.memory.get(0xFFFD)])
+    });
+    debugger.attach(&mut cpu);
+
+    let Some(settings) = stty(&["-g"]) else {
+        eprintln!("The debugger needs a terminal");
+        exit(2);
+    };
+    /* Reads return after a tenth of a second without input, which tells a lone escape from a sequence */
+    stty(&["raw", "-echo", "min", "0", "time", "1"]);
+    let default_hook = panic::take_hook();
+    let restore = settings.clone();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal(&restore);
+        default_hook(info);
+    }));
+    let mut output = io::stdout();
+    print!("\x1b[?1049h\x1b[?25l");
+
+    let mut input = io::stdin();
+    loop {
+        let (width, height) = terminal_size();
+        write!(output, "{}", debugger.render(&cpu, width, height)).expect("Couldn't draw the debugger.");
+        output.flush().expect("Couldn't draw the debugger.");
+
+        match read_key(&mut input) {
+            Some(key) if debugger.handle_key(&mut cpu, key) => {}
+            _ => break,
+        }
+    }
+
+    restore_terminal(&settings);
+}
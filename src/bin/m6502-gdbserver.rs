@@ -0,0 +1,68 @@
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+use std::process::exit;
+use m6052_emulator::assembler::parse_hex_address;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::gdb::GdbStub;
+use m6052_emulator::ram::Ram;
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {program} [--port <port>] <image> [address]");
+    eprintln!("Loads the image at the address, or at the end of memory and starts at the reset vector.");
+    eprintln!("Addresses are hex, with an optional $ or 0x prefix, or decimal with a + prefix.");
+    exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut port = 6502;
+    let mut positional = Vec::new();
+    let mut arguments = args[1..].iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--port" => {
+                port = arguments.next().and_then(|port| port.parse().ok()).unwrap_or_else(|| usage(&args[0]))
+            }
+            option if option.starts_with("--") => usage(&args[0]),
+            _ => positional.push(argument.as_str()),
+        }
+    }
+    if positional.is_empty() || positional.len() > 2 {
+        usage(&args[0]);
+    }
+
+    let image = fs::read(positional[0]).unwrap_or_else(|error| {
+        eprintln!("Couldn't read {}: {error}", positional[0]);
+        exit(2);
+    });
+    let start = positional.get(1).map(|address| parse_hex_address(address).unwrap_or_else(|| usage(&args[0])));
+    let mut ram = Ram::new();
+    ram.load(start.unwrap_or(0x10000_usize.saturating_sub(image.len()) as u16), &image);
+    let mut cpu = CPU::new(&mut ram);
+    cpu.program_counter = start.unwrap_or_else(|| {
+        u16::from_le_bytes([cpu.memory.get(0xFFFC), cpu.memory.get(0xFFFD)])
+    });
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|error| {
+        eprintln!("Couldn't listen on port {port}: {error}");
+        exit(2);
+    });
+    eprintln!("Listening on 127.0.0.1:{port}");
+
+    let mut stub = GdbStub::new();
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("Couldn't accept a connection: {error}");
+                continue;
+            }
+        };
+        eprintln!("Client connected");
+        if let Err(error) = stub.serve(&mut cpu, &mut stream) {
+            eprintln!("Connection lost: {error}");
+        }
+        eprintln!("Client disconnected");
+    }
+}
@@ -0,0 +1,64 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Write};
+use std::process::exit;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::monitor::Monitor;
+use m6052_emulator::ram::Ram;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 3 {
+        eprintln!("Usage: {} [image [address]]", args[0]);
+        exit(2);
+    }
+
+    let mut monitor = Monitor::new();
+    let mut ram = Ram::new();
+    let mut start = None;
+    let loaded = args.len() > 1;
+    if let Some(image) = args.get(1) {
+        let bytes = fs::read(image).unwrap_or_else(|error| {
+            eprintln!("Couldn't read {image}: {error}");
+            exit(2);
+        });
+        let address = match args.get(2) {
+            Some(address) => monitor.parse_value(address).unwrap_or_else(|error| {
+                eprintln!("{error}");
+                exit(2);
+            }),
+            None => 0x10000_usize.saturating_sub(bytes.len()) as u16,
+        };
+        ram.load(address, &bytes);
+        start = args.get(2).map(|_| address);
+    }
+
+    let mut cpu = CPU::new(&mut ram);
+    /* Without an explicit address the image is placed at the end of memory and started from the reset vector */
+    if loaded {
+        cpu.program_counter = start.unwrap_or_else(|| {
+            u16::from_le_bytes([cpu.memory.get(0xFFFC), cpu.memory.get(0xFFFD)])
+        });
+    }
+
+    println!("{}", monitor.execute(&mut cpu, "r").unwrap_or_default());
+    let stdin = io::stdin();
+    loop {
+        print!("({:04X}) ", cpu.program_counter);
+        io::stdout().flush().expect("Couldn't flush the prompt.");
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).expect("Couldn't read the command.") == 0 {
+            break;
+        }
+        if matches!(line.trim(), "q" | "quit" | "x") {
+            break;
+        }
+        match monitor.execute(&mut cpu, &line) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{output}"),
+            Err(error) => println!("Error: {error}"),
+        }
+    }
+}
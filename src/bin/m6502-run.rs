@@ -0,0 +1,229 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::process::exit;
+use std::rc::Rc;
+use m6052_emulator::assembler::{parse_hex_address, parse_number};
+use m6052_emulator::binary_trace::BinaryTraceWriter;
+use m6052_emulator::cheats::{CheatCode, CheatMemory};
+use m6052_emulator::console::Console;
+use m6052_emulator::coverage::{Coverage, CoverageReport};
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::debug_info::DebugInfo;
+use m6052_emulator::heatmap::{HeatKind, Heatmap};
+use m6052_emulator::profiler::Profiler;
+use m6052_emulator::ram::Ram;
+use m6052_emulator::runner::{Halt, Runner};
+use m6052_emulator::symbols::SymbolTable;
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {program} [options] <image> [address]");
+    eprintln!("Loads the image at the address, or at the end of memory without one.");
+    eprintln!("Addresses are hex, with an optional $ or 0x prefix, or decimal with a + prefix.");
+    eprintln!("Options:");
+    eprintln!("  --start <address>         Start here instead of at the reset vector");
+    eprintln!("  --cycles <count>          Stop after this many cycles");
+    eprintln!("  --instructions <count>    Stop after this many instructions");
+    eprintln!("  --stop <address>          Stop when the program counter reaches the address");
+    eprintln!("  --no-trap                 Don't stop at instructions jumping to themselves");
+    eprintln!("  --break                   Stop at BRK instructions");
+    eprintln!("  --putc <address>          Write bytes stored to the address to stdout");
+    eprintln!("  --getc <address>          Read bytes loaded from the address from stdin");
+    eprintln!("  --exit-code <address>     Exit with the byte at the address once halted");
+    eprintln!("  --success <address>       Exit with 0 when halted at the address, 1 elsewhere");
+    eprintln!("  --profile <file>          Write a profile of where the cycles were spent");
+    eprintln!("  --flamegraph <file>       Write folded stacks weighted by cycles for flamegraph tools");
+    eprintln!("  --labels <file>           Name addresses in the profile after a label file");
+    eprintln!("  --debug-info <file>       Load symbols and source lines from an ld65 debug file");
+    eprintln!("  --lcov <file>             Write the coverage by source line as lcov, needs --debug-info");
+    eprintln!("  --heatmap <prefix>        Write <prefix>-read.ppm, -write.ppm, -execute.ppm and a <prefix>.txt");
+    eprintln!("                            summary of how often every address was accessed");
+    eprintln!("  --cheat <code>            Apply an address:value, address?compare:value or Game Genie code");
+    eprintln!("  --trace <file>            Record a binary trace for m6502-tracequery");
+    eprintln!("Exit codes: 2 for usage errors, 3 when a limit was hit, 4 on illegal opcodes, 5 once the");
+    eprintln!("console output couldn't be written, like when the reading end of a pipe closed.");
+    exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut runner = Runner::new();
+    runner.stop_on_trap = true;
+    let mut positional = Vec::new();
+    let mut start = None;
+    let mut putc = None;
+    let mut getc = None;
+    let mut exit_code = None;
+    let mut success = None;
+    let mut profile = None;
+    let mut flamegraph = None;
+    let mut labels = None;
+    let mut debug_info = None;
+    let mut lcov = None;
+    let mut heatmap = None;
+    let mut cheats = Vec::new();
+    let mut trace = None;
+
+    let mut arguments = args[1..].iter();
+    while let Some(argument) = arguments.next() {
+        let mut value = || arguments.next().unwrap_or_else(|| usage(&args[0])).as_str();
+        match argument.as_str() {
+            "--start" => start = Some(parse_hex_address(value()).unwrap_or_else(|| usage(&args[0]))),
+            "--cycles" => runner.cycle_limit = Some(parse_number(value()).unwrap_or_else(|| usage(&args[0]))),
+            "--instructions" => {
+                runner.instruction_limit = Some(parse_number(value()).unwrap_or_else(|| usage(&args[0])))
+            }
+            "--stop" => runner.stop_addresses.push(parse_hex_address(value()).unwrap_or_else(|| usage(&args[0]))),
+            "--no-trap" => runner.stop_on_trap = false,
+            "--break" => runner.stop_on_break = true,
+            "--putc" => putc = Some(parse_hex_address(value()).unwrap_or_else(|| usage(&args[0]))),
+            "--getc" => getc = Some(parse_hex_address(value()).unwrap_or_else(|| usage(&args[0]))),
+            "--exit-code" => exit_code = Some(parse_hex_address(value()).unwrap_or_else(|| usage(&args[0]))),
+            "--success" => success = Some(parse_hex_address(value()).unwrap_or_else(|| usage(&args[0]))),
+            "--profile" => profile = Some(value().to_string()),
+            "--flamegraph" => flamegraph = Some(value().to_string()),
+            "--labels" => labels = Some(SymbolTable::load(value()).unwrap_or_else(|error| {
+                eprintln!("{error}");
+                exit(2);
+            })),
+            "--debug-info" => debug_info = Some(DebugInfo::load(value()).unwrap_or_else(|error| {
+                eprintln!("{error}");
+                exit(2);
+            })),
+            "--lcov" => lcov = Some(value().to_string()),
+            "--heatmap" => heatmap = Some(value().to_string()),
+            "--cheat" => cheats.push(CheatCode::parse(value()).unwrap_or_else(|error| {
+                eprintln!("{error}");
+                exit(2);
+            })),
+            "--trace" => trace = Some(value().to_string()),
+            option if option.starts_with("--") => usage(&args[0]),
+            _ => positional.push(argument.as_str()),
+        }
+    }
+    if positional.is_empty() || positional.len() > 2 || (lcov.is_some() && debug_info.is_none()) {
+        usage(&args[0]);
+    }
+    if let Some(debug_info) = &debug_info {
+        labels.get_or_insert_with(SymbolTable::new).merge(&debug_info.symbol_table());
+    }
+
+    let image = fs::read(positional[0]).unwrap_or_else(|error| {
+        eprintln!("Couldn't read {}: {error}", positional[0]);
+        exit(2);
+    });
+    if image.len() > 0x10000 {
+        eprintln!("{} doesn't fit into memory", positional[0]);
+        exit(2);
+    }
+    let address = match positional.get(1) {
+        Some(address) => parse_hex_address(address).unwrap_or_else(|| usage(&args[0])),
+        None => (0x10000 - image.len()) as u16,
+    };
+
+    let mut ram = Ram::new();
+    ram.load(address, &image);
+    let mut console = Console::new(ram);
+    let stdout = Rc::new(RefCell::new(io::stdout()));
+    if let Some(putc) = putc {
+        console.map_output(putc, stdout.clone());
+    }
+    if let Some(getc) = getc {
+        console.map_input(getc, Rc::new(RefCell::new(io::stdin())));
+    }
+
+    let mut memory = CheatMemory::new(console);
+    for cheat in cheats {
+        memory.cheats.borrow_mut().add(cheat);
+    }
+
+    let mut cpu = CPU::new(&mut memory);
+    cpu.program_counter = start.unwrap_or_else(|| {
+        u16::from_le_bytes([cpu.memory.get(0xFFFC), cpu.memory.get(0xFFFD)])
+    });
+
+    let profiler = Rc::new(RefCell::new(Profiler::new()));
+    if profile.is_some() || flamegraph.is_some() {
+        cpu.observers.push(Box::new(profiler.clone()));
+    }
+    let coverage = Rc::new(RefCell::new(Coverage::new()));
+    if lcov.is_some() {
+        cpu.observers.push(Box::new(coverage.clone()));
+    }
+    let counters = Rc::new(RefCell::new(Heatmap::new()));
+    if heatmap.is_some() {
+        cpu.observers.push(Box::new(counters.clone()));
+    }
+
+    let writer = trace.as_ref().map(|trace| {
+        let file = fs::File::create(trace).unwrap_or_else(|error| {
+            eprintln!("Couldn't create {trace}: {error}");
+            exit(2);
+        });
+        Rc::new(RefCell::new(BinaryTraceWriter::new(BufWriter::new(file))))
+    });
+    if let Some(writer) = &writer {
+        cpu.observers.push(Box::new(writer.clone()));
+    }
+
+    let mut halt = runner.run(&mut cpu);
+    let flushed = stdout.borrow_mut().flush();
+    if let Some(error) = cpu.memory.output_error().or(flushed.as_ref().err()) {
+        eprintln!("Couldn't write to the console output: {error}");
+        halt = Halt::OutputClosed;
+    }
+    if let Some(profile) = profile {
+        if let Err(error) = fs::write(&profile, profiler.borrow().report(labels.as_ref(), 20)) {
+            eprintln!("Couldn't write {profile}: {error}");
+        }
+    }
+    if let (Some(lcov), Some(debug_info)) = (lcov, &debug_info) {
+        let report = CoverageReport::new(&coverage.borrow(), debug_info, &*cpu.memory);
+        eprintln!("{}", report.summary());
+        if let Err(error) = fs::write(&lcov, report.to_lcov(positional[0])) {
+            eprintln!("Couldn't write {lcov}: {error}");
+        }
+    }
+    if let Some(flamegraph) = flamegraph {
+        if let Err(error) = fs::write(&flamegraph, profiler.borrow().folded_stacks(labels.as_ref())) {
+            eprintln!("Couldn't write {flamegraph}: {error}");
+        }
+    }
+    if let (Some(trace), Some(writer)) = (trace, writer) {
+        let mut writer = writer.borrow_mut();
+        let result = match writer.error() {
+            Some(error) => Err(error.to_string()),
+            None => writer.get_mut().flush().map_err(|error| error.to_string()),
+        };
+        if let Err(error) = result {
+            eprintln!("Couldn't write {trace}: {error}");
+        }
+    }
+    if let Some(prefix) = heatmap {
+        let counters = counters.borrow();
+        for (kind, name) in [(HeatKind::Read, "read"), (HeatKind::Write, "write"), (HeatKind::Execute, "execute")] {
+            let file = format!("{prefix}-{name}.ppm");
+            if let Err(error) = fs::File::create(&file).and_then(|mut output| counters.write_ppm(kind, &mut output)) {
+                eprintln!("Couldn't write {file}: {error}");
+            }
+        }
+        if let Err(error) = fs::write(format!("{prefix}.txt"), counters.summary()) {
+            eprintln!("Couldn't write {prefix}.txt: {error}");
+        }
+    }
+
+    let code = match halt {
+        Halt::CycleLimit | Halt::InstructionLimit => 3,
+        Halt::IllegalOpcode(_) => 4,
+        Halt::OutputClosed => 5,
+        _ => match (exit_code, success) {
+            (Some(exit_code), _) => cpu.memory.get(exit_code) as i32,
+            (None, Some(success)) => (cpu.program_counter != success) as i32,
+            (None, None) => 0,
+        },
+    };
+    eprintln!("{halt} after {} cycles, exiting with {code}", cpu.total_cycles);
+    exit(code);
+}
@@ -0,0 +1,38 @@
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process::exit;
+use m6052_emulator::trace::diff;
+
+fn open(path: &str) -> BufReader<File> {
+    match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(error) => {
+            eprintln!("Couldn't open {path}: {error}");
+            exit(2);
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} <produced trace> <reference trace>", args[0]);
+        exit(2);
+    }
+
+    let divergence = diff(open(&args[1]), open(&args[2])).unwrap_or_else(|error| {
+        eprintln!("Couldn't read the traces: {error}");
+        exit(2);
+    });
+
+    match divergence {
+        None => println!("Traces are identical."),
+        Some(divergence) => {
+            println!("Traces diverge at line {}:", divergence.line);
+            println!("  produced:  {}", divergence.produced.as_deref().unwrap_or("<end of trace>"));
+            println!("  reference: {}", divergence.reference.as_deref().unwrap_or("<end of trace>"));
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process::exit;
+use m6052_emulator::assembler::{parse_hex_address, parse_number};
+use m6052_emulator::binary_trace::{find_executions, find_last_write, seek, TraceReader};
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {program} <trace> <query>");
+    eprintln!("Record a trace with m6502-run --trace <file>.");
+    eprintln!("Addresses are hex, with an optional $ or 0x prefix, or decimal with a + prefix.");
+    eprintln!("Queries:");
+    eprintln!("  last-write <address>   The last instruction that wrote to the address");
+    eprintln!("  executions <address>   All instructions executed at the address");
+    eprintln!("  state <index>          The cpu state right before the instruction");
+    exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 4 {
+        usage(&args[0]);
+    }
+
+    let file = File::open(&args[1]).unwrap_or_else(|error| {
+        eprintln!("Couldn't open {}: {error}", args[1]);
+        exit(2);
+    });
+    let mut reader = TraceReader::new(BufReader::new(file)).unwrap_or_else(|error| {
+        eprintln!("Couldn't read {}: {error}", args[1]);
+        exit(2);
+    });
+
+    let result = match args[2].as_str() {
+        "last-write" => {
+            let address = parse_hex_address(&args[3]).unwrap_or_else(|| usage(&args[0]));
+            find_last_write(&mut reader, address).map(|write| match write {
+                Some((index, value)) => println!("#{index}: ${address:04X} = {value:02X}"),
+                None => println!("${address:04X} was never written."),
+            })
+        }
+        "executions" => {
+            let address = parse_hex_address(&args[3]).unwrap_or_else(|| usage(&args[0]));
+            find_executions(&mut reader, address).map(|indices| {
+                for index in indices {
+                    println!("#{index}");
+                }
+            })
+        }
+        "state" => {
+            let index = parse_number(&args[3]).unwrap_or_else(|| usage(&args[0]));
+            seek(&mut reader, index).map(|found| {
+                if !found {
+                    println!("The trace only holds {} instructions.", reader.index());
+                    return;
+                }
+
+                let registers = reader.registers();
+                println!("#{index}: PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+                         registers.program_counter, registers.accumulator, registers.x,
+                         registers.y, registers.status, registers.stack_pointer,
+                         reader.total_cycles());
+                for (row, bytes) in reader.memory().chunks(16).enumerate().take(0x20) {
+                    let bytes: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+                    println!("{:04X}  {}", row * 16, bytes.join(" "));
+                }
+            })
+        }
+        _ => usage(&args[0]),
+    };
+
+    if let Err(error) = result {
+        eprintln!("Couldn't read {}: {error}", args[1]);
+        exit(2);
+    }
+}
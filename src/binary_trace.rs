@@ -0,0 +1,485 @@
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use crate::call_stack::FrameKind;
+use crate::cpu::{CPU, Registers};
+use crate::decoder::DecodedInstruction;
+use crate::instruction::OPCODES;
+use crate::observer::{Access, AccessKind, InterruptEntry, Observer};
+
+/*
+ * A trace starts with a header holding the initial registers, cycle count and memory image.
+ * Every executed instruction is followed by a record which only stores what changed:
+ *
+ *   mask, opcode, cycles, [A], [X], [Y], [SP], [P], [PC], [count, (kind, address, value)*]
+ *
+ * The program counter is only stored when it doesn't point past the executed instruction.
+ * Records with the special flag in the mask hold a record type instead of the opcode. An
+ * interrupt record looks like an instruction record for entering an IRQ or NMI, with the
+ * program counter stored unless it didn't change. A resync record holds the complete
+ * registers and cycle count after they were changed between instructions, an edit record a
+ * byte a debugger or hook changed between them:
+ *
+ *   special, resync, PC, A, X, Y, SP, P, total cycles
+ *   special, edit, address, value
+ */
+const MAGIC: &[u8; 8] = b"M6502TRC";
+const VERSION: u8 = 3;
+
+const CHANGED_ACCUMULATOR: u8 = 0x01;
+const CHANGED_X: u8 = 0x02;
+const CHANGED_Y: u8 = 0x04;
+const CHANGED_STACK_POINTER: u8 = 0x08;
+const CHANGED_STATUS: u8 = 0x10;
+const JUMPED: u8 = 0x20;
+const ACCESSED: u8 = 0x40;
+const SPECIAL: u8 = 0x80;
+
+const RECORD_RESYNC: u8 = 0;
+const RECORD_INTERRUPT: u8 = 1;
+const RECORD_NON_MASKABLE_INTERRUPT: u8 = 2;
+const RECORD_EDIT: u8 = 3;
+
+const KIND_READ: u8 = 0;
+const KIND_WRITE: u8 = 1;
+
+/* Records a trace while observing the cpu, stopping at the first write error which error() returns */
+pub struct BinaryTraceWriter<W: Write> {
+    output: W,
+    started: bool,
+    error: Option<io::Error>,
+    /* The state a reader ends up with after the records written so far */
+    registers: Registers,
+    total_cycles: u64,
+    memory: Vec<u8>,
+    opcode: u8,
+    length: u8,
+    accesses: Vec<Access>,
+}
+
+impl<W: Write> BinaryTraceWriter<W> {
+    pub fn new(output: W) -> BinaryTraceWriter<W> {
+        BinaryTraceWriter {
+            output,
+            started: false,
+            error: None,
+            registers: Registers::default(),
+            total_cycles: 0,
+            memory: Vec::new(),
+            opcode: 0,
+            length: 0,
+            accesses: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.output
+    }
+
+    /* Lets buffered outputs be flushed while the cpu still owns the writer */
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+
+    /* The error that stopped the trace, if any */
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /* Whether records are written, which ends at the first error */
+    fn writing(&self) -> bool {
+        self.started && self.error.is_none()
+    }
+
+    fn keep_error(&mut self, result: io::Result<()>) {
+        if let Err(error) = result {
+            self.error = Some(error);
+        }
+    }
+
+    fn write_header(&mut self, cpu: &CPU) -> io::Result<()> {
+        self.output.write_all(MAGIC)?;
+        self.output.write_all(&[VERSION])?;
+        write_registers(&mut self.output, &cpu.registers())?;
+        self.output.write_all(&cpu.total_cycles.to_le_bytes())?;
+
+        self.memory = (0..=0xFFFF).map(|address| cpu.memory.get_raw(address)).collect();
+        self.output.write_all(&self.memory)?;
+        self.registers = cpu.registers();
+        self.total_cycles = cpu.total_cycles;
+        Ok(())
+    }
+
+    fn write_edit(&mut self, address: u16, value: u8) -> io::Result<()> {
+        if self.memory[address as usize] == value {
+            return Ok(());
+        }
+        self.output.write_all(&[SPECIAL, RECORD_EDIT])?;
+        self.output.write_all(&address.to_le_bytes())?;
+        self.output.write_all(&[value])?;
+        self.memory[address as usize] = value;
+        Ok(())
+    }
+
+    /* Writes edit records for the bytes that differ from what a reader has, which hooks may have changed */
+    fn sync_memory(&mut self, cpu: &CPU) -> io::Result<()> {
+        for address in 0..=0xFFFF {
+            self.write_edit(address, cpu.memory.get_raw(address))?;
+        }
+        Ok(())
+    }
+
+    /* Writes a resync record if the state was changed outside of the traced instructions */
+    fn resync(&mut self, registers: Registers, total_cycles: u64) -> io::Result<()> {
+        if registers == self.registers && total_cycles == self.total_cycles {
+            return Ok(());
+        }
+        self.output.write_all(&[SPECIAL, RECORD_RESYNC])?;
+        write_registers(&mut self.output, &registers)?;
+        self.output.write_all(&total_cycles.to_le_bytes())?;
+        self.registers = registers;
+        self.total_cycles = total_cycles;
+        Ok(())
+    }
+
+    /* Writes the changes from the last record, with the program counter expected at sequential */
+    fn write_record(&mut self, mask: u8, opcode: u8, sequential: u16, cpu: &CPU) -> io::Result<()> {
+        let before = self.registers;
+        let after = cpu.registers();
+
+        let mut mask = mask;
+        let mut values = Vec::new();
+        for (flag, old, new) in [
+            (CHANGED_ACCUMULATOR, before.accumulator, after.accumulator),
+            (CHANGED_X, before.x, after.x),
+            (CHANGED_Y, before.y, after.y),
+            (CHANGED_STACK_POINTER, before.stack_pointer, after.stack_pointer),
+            (CHANGED_STATUS, before.status, after.status),
+        ] {
+            if old != new {
+                mask |= flag;
+                values.push(new);
+            }
+        }
+        if after.program_counter != sequential {
+            mask |= JUMPED;
+            values.extend_from_slice(&after.program_counter.to_le_bytes());
+        }
+        if !self.accesses.is_empty() {
+            mask |= ACCESSED;
+            values.push(self.accesses.len() as u8);
+            for access in self.accesses.iter() {
+                if access.kind == AccessKind::Write {
+                    self.memory[access.address as usize] = access.value;
+                }
+                values.push(if access.kind == AccessKind::Write { KIND_WRITE } else { KIND_READ });
+                values.extend_from_slice(&access.address.to_le_bytes());
+                values.push(access.value);
+            }
+        }
+
+        let cycles = (cpu.total_cycles - self.total_cycles).min(0xFF) as u8;
+        self.output.write_all(&[mask, opcode, cycles])?;
+        self.output.write_all(&values)?;
+        self.registers = after;
+        self.total_cycles = cpu.total_cycles;
+        self.accesses.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Observer for BinaryTraceWriter<W> {
+    fn before_instruction(&mut self, cpu: &CPU, instruction: &DecodedInstruction) {
+        if !self.started {
+            let result = self.write_header(cpu);
+            self.keep_error(result);
+            self.started = true;
+        }
+        if !self.writing() {
+            return;
+        }
+
+        /* A hook at the address just ran and may have changed memory the cpu never wrote */
+        let mut result = Ok(());
+        if cpu.hooks.contains(instruction.address) {
+            result = self.sync_memory(cpu);
+        }
+        let result = result.and_then(|_| self.resync(cpu.registers(), cpu.total_cycles));
+        self.keep_error(result);
+        self.opcode = instruction.opcode;
+        self.length = instruction.length;
+        self.accesses.clear();
+    }
+
+    fn memory_access(&mut self, access: &Access) {
+        if self.writing() && matches!(access.kind, AccessKind::Read | AccessKind::Write) {
+            self.accesses.push(*access);
+        }
+    }
+
+    fn after_instruction(&mut self, cpu: &CPU) {
+        if self.writing() {
+            let sequential = self.registers.program_counter.wrapping_add(self.length as u16);
+            let result = self.write_record(0, self.opcode, sequential, cpu);
+            self.keep_error(result);
+        }
+    }
+
+    /* Interrupts before the first instruction are part of the initial state */
+    fn interrupt(&mut self, cpu: &CPU, entry: &InterruptEntry) {
+        if !self.writing() {
+            return;
+        }
+        let record = match entry.kind {
+            FrameKind::NonMaskableInterrupt => RECORD_NON_MASKABLE_INTERRUPT,
+            _ => RECORD_INTERRUPT,
+        };
+        /* The accesses of the interrupt so far are kept, a resync record never has any */
+        let result = self.resync(entry.before, cpu.total_cycles - entry.cycles)
+            .and_then(|_| self.write_record(SPECIAL, record, entry.before.program_counter, cpu));
+        self.keep_error(result);
+    }
+
+    /* Edits before the first instruction are part of the memory image */
+    fn memory_edited(&mut self, _cpu: &CPU, address: u16, value: u8) {
+        if self.writing() {
+            let result = self.write_edit(address, value);
+            self.keep_error(result);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceStep {
+    pub index: u64,
+    pub before: Registers,
+    pub after: Registers,
+    /* The opcode of the instruction, meaningless for interrupts */
+    pub opcode: u8,
+    /* Set when the step entered an interrupt handler instead of executing an instruction */
+    pub interrupt: Option<FrameKind>,
+    /* The total cycle count before the instruction was executed */
+    pub total_cycles: u64,
+    pub cycles: u8,
+    pub accesses: Vec<Access>,
+}
+
+/* Replays a binary trace while keeping track of the registers and memory */
+pub struct TraceReader<R: Read> {
+    input: R,
+    index: u64,
+    registers: Registers,
+    total_cycles: u64,
+    memory: Vec<u8>,
+    /* The mask and opcode of the next record, read ahead to apply resync records early */
+    pending: Option<[u8; 2]>,
+}
+
+impl<R: Read> TraceReader<R> {
+    pub fn new(mut input: R) -> io::Result<TraceReader<R>> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u8(&mut input)? != VERSION {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Not a binary trace."));
+        }
+
+        let registers = read_registers(&mut input)?;
+        let mut total_cycles = [0; 8];
+        input.read_exact(&mut total_cycles)?;
+        let mut memory = vec![0; 0x10000];
+        input.read_exact(&mut memory)?;
+
+        Ok(TraceReader {
+            input,
+            index: 0,
+            registers,
+            total_cycles: u64::from_le_bytes(total_cycles),
+            memory,
+            pending: None,
+        })
+    }
+
+    /* The index of the next instruction */
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.registers
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.total_cycles
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    fn read_record_header(&mut self) -> io::Result<Option<[u8; 2]>> {
+        if let Some(header) = self.pending.take() {
+            return Ok(Some(header));
+        }
+        let mut header = [0; 2];
+        match self.input.read_exact(&mut header[..1]) {
+            Ok(()) => {}
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        }
+        self.input.read_exact(&mut header[1..])?;
+        Ok(Some(header))
+    }
+
+    /* Applies the resync and edit records in front of the next step */
+    fn resync(&mut self) -> io::Result<()> {
+        while let Some(header) = self.read_record_header()? {
+            match header {
+                [SPECIAL, RECORD_RESYNC] => {
+                    self.registers = read_registers(&mut self.input)?;
+                    let mut total_cycles = [0; 8];
+                    self.input.read_exact(&mut total_cycles)?;
+                    self.total_cycles = u64::from_le_bytes(total_cycles);
+                }
+                [SPECIAL, RECORD_EDIT] => {
+                    let address = read_u16(&mut self.input)?;
+                    self.memory[address as usize] = read_u8(&mut self.input)?;
+                }
+                _ => {
+                    self.pending = Some(header);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn next_step(&mut self) -> io::Result<Option<TraceStep>> {
+        self.resync()?;
+        let Some([mask, opcode]) = self.read_record_header()? else {
+            return Ok(None);
+        };
+        let cycles = read_u8(&mut self.input)?;
+        let interrupt = match (mask & SPECIAL, opcode) {
+            (0, _) => None,
+            (_, RECORD_INTERRUPT) => Some(FrameKind::Interrupt),
+            (_, RECORD_NON_MASKABLE_INTERRUPT) => Some(FrameKind::NonMaskableInterrupt),
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "Unknown trace record.")),
+        };
+
+        let before = self.registers;
+        let mut after = before;
+        if mask & CHANGED_ACCUMULATOR != 0 { after.accumulator = read_u8(&mut self.input)?; }
+        if mask & CHANGED_X != 0 { after.x = read_u8(&mut self.input)?; }
+        if mask & CHANGED_Y != 0 { after.y = read_u8(&mut self.input)?; }
+        if mask & CHANGED_STACK_POINTER != 0 { after.stack_pointer = read_u8(&mut self.input)?; }
+        if mask & CHANGED_STATUS != 0 { after.status = read_u8(&mut self.input)?; }
+
+        after.program_counter = if mask & JUMPED != 0 {
+            read_u16(&mut self.input)?
+        } else if interrupt.is_some() {
+            before.program_counter
+        } else {
+            let length = OPCODES[opcode as usize]
+                .map(|instruction| 1 + instruction.mode().operand_length())
+                .unwrap_or(1);
+            before.program_counter.wrapping_add(length as u16)
+        };
+
+        let mut accesses = Vec::new();
+        if mask & ACCESSED != 0 {
+            let count = read_u8(&mut self.input)?;
+            for _ in 0..count {
+                let kind = match read_u8(&mut self.input)? {
+                    KIND_WRITE => AccessKind::Write,
+                    _ => AccessKind::Read,
+                };
+                let address = read_u16(&mut self.input)?;
+                let value = read_u8(&mut self.input)?;
+                if kind == AccessKind::Write {
+                    self.memory[address as usize] = value;
+                }
+                accesses.push(Access { kind, address, value });
+            }
+        }
+
+        let step = TraceStep {
+            index: self.index,
+            before,
+            after,
+            opcode,
+            interrupt,
+            total_cycles: self.total_cycles,
+            cycles,
+            accesses,
+        };
+
+        self.index += 1;
+        self.registers = after;
+        self.total_cycles += cycles as u64;
+        self.resync()?;
+        Ok(Some(step))
+    }
+}
+
+/* Finds the last instruction that wrote to the address, returning its index and the written value */
+pub fn find_last_write<R: Read>(reader: &mut TraceReader<R>, address: u16) -> io::Result<Option<(u64, u8)>> {
+    let mut last = None;
+    while let Some(step) = reader.next_step()? {
+        for access in step.accesses.iter() {
+            if access.kind == AccessKind::Write && access.address == address {
+                last = Some((step.index, access.value));
+            }
+        }
+    }
+    Ok(last)
+}
+
+/* Finds the indices of all instructions executed at the address, leaving out interrupts taken there */
+pub fn find_executions<R: Read>(reader: &mut TraceReader<R>, address: u16) -> io::Result<Vec<u64>> {
+    let mut indices = Vec::new();
+    while let Some(step) = reader.next_step()? {
+        if step.interrupt.is_none() && step.before.program_counter == address {
+            indices.push(step.index);
+        }
+    }
+    Ok(indices)
+}
+
+/* Replays the trace up to the instruction with the given index, leaving the state right before it */
+pub fn seek<R: Read>(reader: &mut TraceReader<R>, index: u64) -> io::Result<bool> {
+    while reader.index() < index {
+        if reader.next_step()?.is_none() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn write_registers(output: &mut impl Write, registers: &Registers) -> io::Result<()> {
+    output.write_all(&registers.program_counter.to_le_bytes())?;
+    output.write_all(&[registers.accumulator, registers.x, registers.y,
+        registers.stack_pointer, registers.status])
+}
+
+fn read_registers(input: &mut impl Read) -> io::Result<Registers> {
+    let program_counter = read_u16(input)?;
+    let mut values = [0; 5];
+    input.read_exact(&mut values)?;
+    let [accumulator, x, y, stack_pointer, status] = values;
+    Ok(Registers { program_counter, accumulator, x, y, stack_pointer, status })
+}
+
+fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+    let mut buffer = [0; 1];
+    input.read_exact(&mut buffer)?;
+    Ok(buffer[0])
+}
+
+fn read_u16(input: &mut impl Read) -> io::Result<u16> {
+    let mut buffer = [0; 2];
+    input.read_exact(&mut buffer)?;
+    Ok(u16::from_le_bytes(buffer))
+}
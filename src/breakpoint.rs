@@ -0,0 +1,296 @@
+use std::collections::VecDeque;
+use crate::cpu::CPU;
+use crate::expression::{Expression, ParseError};
+use crate::observer::{Access, AccessKind};
+use crate::symbols::SymbolTable;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BreakpointKind {
+    /* Stops before the instruction at the address gets executed */
+    Execute(u16),
+    /* Stops after an instruction read from or wrote to an address within the inclusive range */
+    Read(u16, u16),
+    Write(u16, u16),
+    Access(u16, u16),
+    /* Stops before an instruction with the opcode gets executed */
+    Opcode(u8),
+    Break,
+    /* Stops right after the cpu entered an interrupt or non-maskable interrupt */
+    Interrupt,
+}
+
+/* Decides on which hit, counted while the condition holds, the breakpoint triggers */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HitCondition {
+    Equal(u64),
+    AtLeast(u64),
+    Multiple(u64),
+}
+
+impl HitCondition {
+    pub fn matches(&self, hits: u64) -> bool {
+        match self {
+            HitCondition::Equal(count) => hits == *count,
+            HitCondition::AtLeast(count) => hits >= *count,
+            HitCondition::Multiple(count) => *count != 0 && hits.is_multiple_of(*count),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum MessagePart {
+    Text(String),
+    Value(Expression, bool),
+}
+
+/* A tracepoint message with embedded expressions like `A is {A}, pointer is {[$FB]:x}` */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogMessage {
+    parts: Vec<MessagePart>,
+}
+
+impl LogMessage {
+    pub fn parse(text: &str, symbols: Option<&SymbolTable>) -> Result<LogMessage, ParseError> {
+        let mut parts = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').ok_or(ParseError {
+                position: text.len() - rest.len() + start,
+                message: "Expected '}'".to_string(),
+            })? + start;
+
+            if start > 0 {
+                parts.push(MessagePart::Text(rest[..start].to_string()));
+            }
+            let value = &rest[start + 1..end];
+            let (value, hex) = match value.strip_suffix(":x") {
+                Some(value) => (value, true),
+                None => (value, false),
+            };
+            let expression = Expression::parse(value, symbols).map_err(|error| ParseError {
+                position: text.len() - rest.len() + start + 1 + error.position,
+                message: error.message,
+            })?;
+            parts.push(MessagePart::Value(expression, hex));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(MessagePart::Text(rest.to_string()));
+        }
+        Ok(LogMessage { parts })
+    }
+
+    pub fn format(&self, cpu: &CPU) -> String {
+        self.parts.iter().map(|part| match part {
+            MessagePart::Text(text) => text.clone(),
+            MessagePart::Value(expression, false) => expression.evaluate(cpu).to_string(),
+            MessagePart::Value(expression, true) => format!("${:02X}", expression.evaluate(cpu)),
+        }).collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub kind: BreakpointKind,
+    pub enabled: bool,
+    pub hits: u64,
+    /* Hits only count while the condition holds */
+    pub condition: Option<Expression>,
+    pub hit_condition: Option<HitCondition>,
+    /* Turns the breakpoint into a tracepoint which logs the message instead of stopping */
+    pub log_message: Option<LogMessage>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint { id: usize, address: u16 },
+    Watchpoint { id: usize, access: Access },
+    Interrupt { id: usize, vector: u16 },
+    /* Not a breakpoint, the cpu can't execute the opcode at the address and stays in front of it */
+    IllegalOpcode { address: u16 },
+}
+
+/* How many tracepoint messages are kept until they're taken, older ones get dropped */
+pub const MESSAGE_LIMIT: usize = 1000;
+
+#[derive(Clone, Debug, Default)]
+pub struct Breakpoints {
+    breakpoints: Vec<Breakpoint>,
+    next_id: usize,
+    messages: VecDeque<String>,
+}
+
+impl Breakpoints {
+    pub fn new() -> Breakpoints {
+        Breakpoints::default()
+    }
+
+    pub fn add(&mut self, kind: BreakpointKind) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push(Breakpoint {
+            id,
+            kind,
+            enabled: true,
+            hits: 0,
+            condition: None,
+            hit_condition: None,
+            log_message: None,
+        });
+        id
+    }
+
+    pub fn set_condition(&mut self, id: usize, condition: Option<Expression>) -> bool {
+        match self.get_mut(id) {
+            Some(breakpoint) => {
+                breakpoint.condition = condition;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_hit_condition(&mut self, id: usize, hit_condition: Option<HitCondition>) -> bool {
+        match self.get_mut(id) {
+            Some(breakpoint) => {
+                breakpoint.hit_condition = hit_condition;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_log_message(&mut self, id: usize, log_message: Option<LogMessage>) -> bool {
+        match self.get_mut(id) {
+            Some(breakpoint) => {
+                breakpoint.log_message = log_message;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /* Returns the messages logged by tracepoints since the last call, at most the latest MESSAGE_LIMIT */
+    pub fn take_messages(&mut self) -> Vec<String> {
+        self.messages.drain(..).collect()
+    }
+
+    pub fn remove(&mut self, id: usize) -> bool {
+        let length = self.breakpoints.len();
+        self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+        self.breakpoints.len() != length
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn set_enabled(&mut self, id: usize, enabled: bool) -> bool {
+        match self.get_mut(id) {
+            Some(breakpoint) => {
+                breakpoint.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Breakpoint> {
+        self.breakpoints.iter().find(|breakpoint| breakpoint.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Breakpoint> {
+        self.breakpoints.iter_mut().find(|breakpoint| breakpoint.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&Breakpoint> {
+        self.breakpoints.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    /* The first enabled breakpoint of a matching kind, regardless of its conditions */
+    pub fn find_enabled(&self, matches: impl Fn(BreakpointKind) -> bool) -> Option<usize> {
+        self.breakpoints.iter()
+            .find(|breakpoint| breakpoint.enabled && matches(breakpoint.kind))
+            .map(|breakpoint| breakpoint.id)
+    }
+
+    /* Checks the breakpoints on the instruction about to execute, leaving out tracepoints */
+    pub fn check_instruction(&mut self, cpu: &CPU, address: u16, opcode: u8) -> Option<StopReason> {
+        let breakpoint = self.hit(cpu, |breakpoint| {
+            breakpoint.log_message.is_none() && instruction_matches(breakpoint.kind, address, opcode)
+        })?;
+        Some(StopReason::Breakpoint { id: breakpoint, address })
+    }
+
+    /* Logs the messages of the tracepoints on the instruction, right before it gets executed */
+    pub fn trace_instruction(&mut self, cpu: &CPU, address: u16, opcode: u8) {
+        self.hit(cpu, |breakpoint| {
+            breakpoint.log_message.is_some() && instruction_matches(breakpoint.kind, address, opcode)
+        });
+    }
+
+    pub fn check_access(&mut self, cpu: &CPU, access: &Access) -> Option<StopReason> {
+        let address = access.address;
+        let breakpoint = self.hit(cpu, |breakpoint| match (breakpoint.kind, access.kind) {
+            (BreakpointKind::Read(start, end), AccessKind::Read)
+            | (BreakpointKind::Write(start, end), AccessKind::Write)
+            | (BreakpointKind::Access(start, end), AccessKind::Read | AccessKind::Write) => {
+                (start..=end).contains(&address)
+            }
+            _ => false,
+        })?;
+        Some(StopReason::Watchpoint { id: breakpoint, access: *access })
+    }
+
+    pub fn check_interrupt(&mut self, cpu: &CPU, vector: u16) -> Option<StopReason> {
+        let breakpoint = self.hit(cpu, |breakpoint| breakpoint.kind == BreakpointKind::Interrupt)?;
+        Some(StopReason::Interrupt { id: breakpoint, vector })
+    }
+
+    /* Counts a hit on every enabled matching breakpoint and returns the first one that stops */
+    fn hit(&mut self, cpu: &CPU, matches: impl Fn(&Breakpoint) -> bool) -> Option<usize> {
+        let mut first = None;
+        for breakpoint in self.breakpoints.iter_mut() {
+            if !breakpoint.enabled || !matches(breakpoint) {
+                continue;
+            }
+            if let Some(condition) = &breakpoint.condition {
+                if !condition.is_true(cpu) {
+                    continue;
+                }
+            }
+
+            breakpoint.hits += 1;
+            if let Some(hit_condition) = breakpoint.hit_condition {
+                if !hit_condition.matches(breakpoint.hits) {
+                    continue;
+                }
+            }
+
+            match &breakpoint.log_message {
+                Some(message) => {
+                    if self.messages.len() == MESSAGE_LIMIT {
+                        self.messages.pop_front();
+                    }
+                    self.messages.push_back(message.format(cpu));
+                }
+                None => first = first.or(Some(breakpoint.id)),
+            }
+        }
+        first
+    }
+}
+
+fn instruction_matches(kind: BreakpointKind, address: u16, opcode: u8) -> bool {
+    match kind {
+        BreakpointKind::Execute(target) => target == address,
+        BreakpointKind::Opcode(target) => target == opcode,
+        BreakpointKind::Break => opcode == 0x00,
+        _ => false,
+    }
+}
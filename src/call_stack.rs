@@ -0,0 +1,100 @@
+use crate::instruction::Instruction;
+use crate::symbols::SymbolTable;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameKind {
+    Subroutine,
+    Break,
+    Interrupt,
+    NonMaskableInterrupt,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    /* The address of the calling instruction, or the interrupted one */
+    pub caller: u16,
+    /* The address of the subroutine or interrupt handler */
+    pub target: u16,
+    pub return_address: u16,
+    /* The stack pointer right after the return address was pushed */
+    pub stack_pointer: u8,
+}
+
+impl Frame {
+    /* The symbol of the subroutine along with the offset of the target from it */
+    pub fn symbol<'s>(&self, symbols: &'s SymbolTable) -> Option<(&'s str, u16)> {
+        symbols.lookup(self.target)
+    }
+}
+
+/*
+ * A shadow of the call stack built by watching calls, returns and interrupts. Instead of
+ * popping a frame on every return, frames get dropped once the stack pointer moves above
+ * them. That way returning through pushed addresses or discarding return addresses with
+ * pulls or TXS keeps the shadow stack in line with the real one.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        CallStack::default()
+    }
+
+    /* The frames from the outermost to the innermost one */
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /* Updates the call stack after the instruction at the address was executed */
+    pub fn update(&mut self, instruction: Instruction, address: u16, program_counter: u16, stack_pointer: u8) {
+        self.unwind(stack_pointer);
+
+        let kind = match instruction {
+            Instruction::JumpSubroutine(_) => FrameKind::Subroutine,
+            Instruction::Break(_) => FrameKind::Break,
+            _ => return,
+        };
+        self.frames.push(Frame {
+            kind,
+            caller: address,
+            target: program_counter,
+            return_address: match kind {
+                FrameKind::Subroutine => address.wrapping_add(3),
+                _ => address.wrapping_add(2),
+            },
+            stack_pointer,
+        });
+    }
+
+    pub fn enter_interrupt(&mut self, kind: FrameKind, interrupted: u16, handler: u16, stack_pointer: u8) {
+        self.unwind(stack_pointer);
+        self.frames.push(Frame {
+            kind,
+            caller: interrupted,
+            target: handler,
+            return_address: interrupted,
+            stack_pointer,
+        });
+    }
+
+    fn unwind(&mut self, stack_pointer: u8) {
+        while let Some(frame) = self.frames.last() {
+            if frame.stack_pointer >= stack_pointer {
+                break;
+            }
+            self.frames.pop();
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use crate::cpu::Memory;
+
+const GAME_GENIE_LETTERS: &str = "APZLGITYEOXUKSVN";
+
+/* Replaces the byte read from the address, but only if the original byte equals compare when given */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CheatCode {
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+impl CheatCode {
+    /* Parses `address:value`, `address?compare:value` in hex, or a Game Genie code */
+    pub fn parse(text: &str) -> Result<CheatCode, String> {
+        let Some((target, value)) = text.split_once(':') else {
+            return CheatCode::game_genie(text);
+        };
+        let (address, compare) = match target.split_once('?') {
+            Some((address, compare)) => (address, Some(compare)),
+            None => (target, None),
+        };
+        let hex = |text: &str| text.strip_prefix('$').unwrap_or(text).to_string();
+        let address = u16::from_str_radix(&hex(address), 16)
+            .map_err(|_| format!("Invalid address '{address}'"))?;
+        let byte = |text: &str| u8::from_str_radix(&hex(text), 16).map_err(|_| format!("Invalid byte '{text}'"));
+        Ok(CheatCode { address, value: byte(value)?, compare: compare.map(byte).transpose()? })
+    }
+
+    /* Decodes a six or eight letter NES Game Genie code, the longer ones compare against the original byte */
+    pub fn game_genie(code: &str) -> Result<CheatCode, String> {
+        let n: Vec<u16> = code.chars()
+            .map(|letter| GAME_GENIE_LETTERS.find(letter.to_ascii_uppercase()).map(|index| index as u16))
+            .collect::<Option<_>>()
+            .ok_or(format!("Invalid Game Genie code '{code}'"))?;
+        if n.len() != 6 && n.len() != 8 {
+            return Err(format!("Expected six or eight letters, got '{code}'"));
+        }
+
+        let address = 0x8000 | (n[3] & 7) << 12 | (n[5] & 7) << 8 | (n[4] & 8) << 8
+            | (n[2] & 7) << 4 | (n[1] & 8) << 4 | (n[4] & 7) | (n[3] & 8);
+        let value = |low: u16, high: u16, last: u16| ((high & 7) << 4 | (low & 8) << 4 | (low & 7) | (last & 8)) as u8;
+        Ok(match n.len() {
+            6 => CheatCode { address, value: value(n[0], n[1], n[5]), compare: None },
+            _ => CheatCode { address, value: value(n[0], n[1], n[7]), compare: Some(value(n[6], n[7], n[5])) },
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cheat {
+    pub id: usize,
+    pub code: CheatCode,
+    pub enabled: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CheatList {
+    cheats: Vec<Cheat>,
+    next_id: usize,
+}
+
+impl CheatList {
+    pub fn new() -> CheatList {
+        CheatList::default()
+    }
+
+    pub fn add(&mut self, code: CheatCode) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.cheats.push(Cheat { id, code, enabled: true });
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> bool {
+        let length = self.cheats.len();
+        self.cheats.retain(|cheat| cheat.id != id);
+        self.cheats.len() != length
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+    }
+
+    pub fn set_enabled(&mut self, id: usize, enabled: bool) -> bool {
+        match self.cheats.iter_mut().find(|cheat| cheat.id == id) {
+            Some(cheat) => {
+                cheat.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Cheat> {
+        self.cheats.iter().find(|cheat| cheat.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&Cheat> {
+        self.cheats.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    /* The byte seen at the address when the memory holds the original one, the first matching cheat wins */
+    pub fn apply(&self, address: u16, original: u8) -> u8 {
+        self.cheats.iter()
+            .find(|cheat| {
+                cheat.enabled && cheat.code.address == address
+                    && cheat.code.compare.is_none_or(|compare| compare == original)
+            })
+            .map_or(original, |cheat| cheat.code.value)
+    }
+}
+
+/*
+ * Applies cheat codes to the reads from another memory without modifying it. Frozen addresses
+ * always read the value of their cheat while writes still reach the memory below. The list is
+ * shared, so the caller can keep a handle to toggle cheats while the cpu runs. The history,
+ * save states and snapshots go through get_raw, so they never pick up cheated bytes.
+ */
+#[derive(Clone)]
+pub struct CheatMemory<M> {
+    pub inner: M,
+    pub cheats: Rc<RefCell<CheatList>>,
+}
+
+impl<M: Memory> CheatMemory<M> {
+    pub fn new(inner: M) -> CheatMemory<M> {
+        CheatMemory { inner, cheats: Rc::new(RefCell::new(CheatList::new())) }
+    }
+}
+
+impl<M: Memory + Clone + 'static> Memory for CheatMemory<M> {
+    fn read(&self, cycles: &mut isize, address: u16) -> u8 {
+        let original = self.inner.read(cycles, address);
+        self.cheats.borrow().apply(address, original)
+    }
+
+    fn get(&self, address: u16) -> u8 {
+        self.cheats.borrow().apply(address, self.inner.get(address))
+    }
+
+    fn get_raw(&self, address: u16) -> u8 {
+        self.inner.get_raw(address)
+    }
+
+    fn write(&mut self, cycles: &mut isize, address: u16, value: u8) {
+        self.inner.write(cycles, address, value);
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        self.inner.set(address, value);
+    }
+
+    fn checkpoint(&mut self) -> Option<Vec<u8>> {
+        self.inner.checkpoint()
+    }
+
+    fn output_error(&self) -> Option<&io::Error> {
+        self.inner.output_error()
+    }
+
+    /* The fork gets a copy of the cheats, toggling them doesn't affect the original */
+    fn fork(&self) -> Box<dyn Memory> {
+        Box::new(CheatMemory {
+            inner: self.inner.fork(),
+            cheats: Rc::new(RefCell::new(self.cheats.borrow().clone())),
+        })
+    }
+}
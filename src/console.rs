@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+use std::io;
+use std::io::{Read, Write};
+use std::rc::Rc;
+use crate::cpu::Memory;
+
+/*
+ * Maps a character device into the memory of another one. Writes to the output address
+ * go to the writer and reads from the input address take the next byte of the reader,
+ * or 0 once it is exhausted. Every other access is passed through. The first error writing
+ * the output closes it, later writes are dropped and output_error returns the error.
+ */
+#[derive(Clone)]
+pub struct Console<M> {
+    pub inner: M,
+    output: Option<(u16, Rc<RefCell<dyn Write>>)>,
+    input: Option<(u16, Rc<RefCell<dyn Read>>)>,
+    error: Option<Rc<io::Error>>,
+}
+
+impl<M: Memory> Console<M> {
+    pub fn new(inner: M) -> Console<M> {
+        Console { inner, output: None, input: None, error: None }
+    }
+
+    /* The device is shared, so the caller can keep a handle to collect the output */
+    pub fn map_output<W: Write + 'static>(&mut self, address: u16, output: Rc<RefCell<W>>) {
+        self.output = Some((address, output));
+    }
+
+    pub fn map_input<R: Read + 'static>(&mut self, address: u16, input: Rc<RefCell<R>>) {
+        self.input = Some((address, input));
+    }
+}
+
+impl<M: Memory + Clone + 'static> Memory for Console<M> {
+    fn read(&self, cycles: &mut isize, address: u16) -> u8 {
+        match &self.input {
+            Some((input_address, input)) if *input_address == address => {
+                *cycles -= 1;
+                let mut byte = [0];
+                match input.borrow_mut().read(&mut byte) {
+                    Ok(1) => byte[0],
+                    _ => 0,
+                }
+            }
+            _ => self.inner.read(cycles, address),
+        }
+    }
+
+    /* Peeking at the input address doesn't consume any input */
+    fn get(&self, address: u16) -> u8 {
+        match &self.input {
+            Some((input_address, _)) if *input_address == address => 0,
+            _ => self.inner.get(address),
+        }
+    }
+
+    fn write(&mut self, cycles: &mut isize, address: u16, value: u8) {
+        match &self.output {
+            Some((output_address, output)) if *output_address == address => {
+                *cycles -= 1;
+                if self.error.is_none() {
+                    self.error = output.borrow_mut().write_all(&[value]).err().map(Rc::new);
+                }
+            }
+            _ => self.inner.write(cycles, address, value),
+        }
+    }
+
+    fn get_raw(&self, address: u16) -> u8 {
+        self.inner.get_raw(address)
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        self.inner.set(address, value);
+    }
+
+    fn checkpoint(&mut self) -> Option<Vec<u8>> {
+        self.inner.checkpoint()
+    }
+
+    fn output_error(&self) -> Option<&io::Error> {
+        self.error.as_deref().or(self.inner.output_error())
+    }
+
+    /* Streams can't be copied, so the fork has no devices and its accesses reach the memory below */
+    fn fork(&self) -> Box<dyn Memory> {
+        self.inner.fork()
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use crate::cpu::{CPU, Memory};
+use crate::decoder::{decode, DecodedInstruction};
+use crate::instruction::{AddressingMode, Instruction};
+use crate::observer::{Access, AccessKind, InterruptEntry, Observer};
+use crate::source_map::SourceMap;
+
+const EXECUTED: u8 = 1;
+const OPERAND: u8 = 2;
+const READ: u8 = 4;
+const WRITTEN: u8 = 8;
+
+const JSR_OPCODE: u8 = 0x20;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BranchCount {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/*
+ * Records how every address was used during a run: fetched as an opcode, fetched as an
+ * operand, or read and written as data. It counts how often every instruction ran and which
+ * way every branch went, and remembers the control transfers a static analysis can't
+ * follow: jumps through pointers, RTS used as a jump and interrupt handlers.
+ */
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    flags: Vec<u8>,
+    executions: HashMap<u16, u64>,
+    /* The instruction being executed */
+    current: Option<DecodedInstruction>,
+    pub branches: BTreeMap<u16, BranchCount>,
+    /* The destinations of indirect jumps and of RTS instructions not returning behind a JSR */
+    pub jumps: BTreeMap<u16, BTreeSet<u16>>,
+    /* The first instructions executed after BRK, IRQ and NMI */
+    pub interrupts: BTreeSet<u16>,
+}
+
+impl Default for Coverage {
+    fn default() -> Coverage {
+        Coverage::new()
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage {
+            flags: vec![0; 0x10000],
+            executions: HashMap::new(),
+            current: None,
+            branches: BTreeMap::new(),
+            jumps: BTreeMap::new(),
+            interrupts: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_executed(&self, address: u16) -> bool {
+        self.flags[address as usize] & EXECUTED != 0
+    }
+
+    pub fn is_operand(&self, address: u16) -> bool {
+        self.flags[address as usize] & OPERAND != 0
+    }
+
+    pub fn is_read(&self, address: u16) -> bool {
+        self.flags[address as usize] & READ != 0
+    }
+
+    pub fn is_written(&self, address: u16) -> bool {
+        self.flags[address as usize] & WRITTEN != 0
+    }
+
+    /* How often the instruction at the address was executed */
+    pub fn executions(&self, address: u16) -> u64 {
+        self.executions.get(&address).copied().unwrap_or(0)
+    }
+
+    /* The addresses fetched as opcodes in ascending order */
+    pub fn executed(&self) -> impl Iterator<Item=u16> + '_ {
+        (0..=0xFFFF).filter(|address| self.is_executed(*address))
+    }
+
+    pub fn clear(&mut self) {
+        *self = Coverage::new();
+    }
+}
+
+impl Observer for Coverage {
+    fn before_instruction(&mut self, _cpu: &CPU, instruction: &DecodedInstruction) {
+        *self.executions.entry(instruction.address).or_default() += 1;
+        self.current = Some(*instruction);
+    }
+
+    fn memory_access(&mut self, access: &Access) {
+        self.flags[access.address as usize] |= match access.kind {
+            AccessKind::Opcode => EXECUTED,
+            AccessKind::Operand => OPERAND,
+            AccessKind::Read => READ,
+            AccessKind::Write => WRITTEN,
+        };
+    }
+
+    fn after_instruction(&mut self, cpu: &CPU) {
+        let Some(instruction) = self.current.take() else {
+            return;
+        };
+        let destination = cpu.program_counter;
+        match instruction.instruction {
+            Instruction::Jump(AddressingMode::Indirect) => {
+                self.jumps.entry(instruction.address).or_default().insert(destination);
+            }
+            Instruction::ReturnFromSubroutine(_) if cpu.memory.get(destination.wrapping_sub(3)) != JSR_OPCODE => {
+                self.jumps.entry(instruction.address).or_default().insert(destination);
+            }
+            Instruction::Break(_) => {
+                self.interrupts.insert(destination);
+            }
+            _ if instruction.mode() == AddressingMode::Relative => {
+                let count = self.branches.entry(instruction.address).or_default();
+                match destination == instruction.next_address() {
+                    true => count.not_taken += 1,
+                    false => count.taken += 1,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn interrupt(&mut self, cpu: &CPU, _entry: &InterruptEntry) {
+        self.interrupts.insert(cpu.program_counter);
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineCoverage {
+    /* How often the most executed instruction of the line ran */
+    pub hits: u64,
+    /* The branches of the line by address */
+    pub branches: BTreeMap<u16, BranchCount>,
+}
+
+/*
+ * Coverage by source line. Lines count when code of them was executed or when their first
+ * byte decodes to an instruction that was never touched as data, so tables don't show up as
+ * uncovered code.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub files: BTreeMap<String, BTreeMap<u32, LineCoverage>>,
+}
+
+impl CoverageReport {
+    pub fn new(coverage: &Coverage, source_map: &dyn SourceMap, memory: &dyn Memory) -> CoverageReport {
+        let mut report = CoverageReport::default();
+        let mut previous = None;
+        for address in 0..=0xFFFF_u16 {
+            let location = source_map.location(address);
+            let first = location.is_some() && location != previous;
+            previous = location.clone();
+            let Some(location) = location else {
+                continue;
+            };
+
+            let untouched = !coverage.is_operand(address) && !coverage.is_read(address) && !coverage.is_written(address);
+            let instruction = match coverage.is_executed(address) || (first && untouched) {
+                true => decode(memory, address),
+                false => None,
+            };
+            let Some(instruction) = instruction else {
+                continue;
+            };
+
+            let line = report.files.entry(location.file).or_default().entry(location.line).or_default();
+            line.hits = line.hits.max(coverage.executions(address));
+            if instruction.mode() == AddressingMode::Relative {
+                let count = coverage.branches.get(&address).copied().unwrap_or_default();
+                line.branches.insert(address, count);
+            }
+        }
+        report
+    }
+
+    /* The share of lines hit and of branch outcomes seen over all files */
+    pub fn summary(&self) -> String {
+        let lines: Vec<&LineCoverage> = self.files.values().flat_map(|lines| lines.values()).collect();
+        let lines_hit = lines.iter().filter(|line| line.hits > 0).count();
+        let outcomes: Vec<u64> = lines.iter()
+            .flat_map(|line| line.branches.values())
+            .flat_map(|count| [count.taken, count.not_taken])
+            .collect();
+        let outcomes_hit = outcomes.iter().filter(|count| **count > 0).count();
+
+        let percent = |hit: usize, found: usize| match found {
+            0 => 100.0,
+            found => hit as f64 * 100.0 / found as f64,
+        };
+        format!("Lines: {lines_hit}/{} ({:.1}%), branches: {outcomes_hit}/{} ({:.1}%)",
+                lines.len(), percent(lines_hit, lines.len()), outcomes.len(), percent(outcomes_hit, outcomes.len()))
+    }
+
+    /* The report as an lcov tracefile, with every branch as two outcomes, taken first */
+    pub fn to_lcov(&self, test_name: &str) -> String {
+        let mut lcov = String::new();
+        for (file, lines) in &self.files {
+            writeln!(lcov, "TN:{test_name}\nSF:{file}").unwrap();
+            let mut found = 0;
+            let mut hit = 0;
+            for (number, line) in lines {
+                for (address, count) in &line.branches {
+                    for (outcome, taken) in [count.taken, count.not_taken].into_iter().enumerate() {
+                        match line.hits {
+                            0 => writeln!(lcov, "BRDA:{number},{address},{outcome},-").unwrap(),
+                            _ => writeln!(lcov, "BRDA:{number},{address},{outcome},{taken}").unwrap(),
+                        }
+                        found += 1;
+                        hit += (taken > 0) as usize;
+                    }
+                }
+            }
+            writeln!(lcov, "BRF:{found}\nBRH:{hit}").unwrap();
+            for (number, line) in lines {
+                writeln!(lcov, "DA:{number},{}", line.hits).unwrap();
+            }
+            let hit = lines.values().filter(|line| line.hits > 0).count();
+            writeln!(lcov, "LF:{}\nLH:{hit}\nend_of_record", lines.len()).unwrap();
+        }
+        lcov
+    }
+}
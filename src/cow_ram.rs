@@ -0,0 +1,118 @@
+use std::rc::Rc;
+use crate::cpu::Memory;
+use crate::history::Overwrite;
+
+pub const PAGE_SIZE: usize = 0x100;
+
+/*
+ * 64K of memory split into pages shared between clones until one of them writes to a page,
+ * which makes cloning about as cheap as copying 256 pointers and clones usable as snapshots.
+ * Pages changed since the last checkpoint are marked dirty. Every access takes one cycle.
+ */
+#[derive(Clone)]
+pub struct CowRam {
+    pages: Vec<Rc<[u8; PAGE_SIZE]>>,
+    dirty: Vec<bool>,
+}
+
+impl CowRam {
+    pub fn new() -> CowRam {
+        /* All pages start out as the same zeroed page */
+        let zeroes = Rc::new([0; PAGE_SIZE]);
+        CowRam {
+            pages: (0..0x10000 / PAGE_SIZE).map(|_| zeroes.clone()).collect(),
+            dirty: vec![false; 0x10000 / PAGE_SIZE],
+        }
+    }
+
+    /* Copies the contents of other memory without causing any side effects */
+    pub fn from_memory(memory: &dyn Memory) -> CowRam {
+        let mut ram = CowRam::new();
+        for address in 0..=0xFFFF {
+            let value = memory.get_raw(address);
+            if value != 0 {
+                ram.set(address, value);
+            }
+        }
+        ram
+    }
+
+    /* Copies the bytes to the address, wrapping around at the end of memory */
+    pub fn load(&mut self, address: u16, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.set(address.wrapping_add(offset as u16), *byte);
+        }
+    }
+
+    pub fn page(&self, page: u8) -> &[u8] {
+        &self.pages[page as usize][..]
+    }
+
+    pub fn is_dirty(&self, page: u8) -> bool {
+        self.dirty[page as usize]
+    }
+
+    /* The pages changed since the last checkpoint in ascending order */
+    pub fn dirty_pages(&self) -> Vec<u8> {
+        (0..=0xFF).filter(|page| self.is_dirty(*page)).collect()
+    }
+
+    /* The bytes that differ from the other memory, skipping the pages both still share */
+    pub fn diff(&self, other: &CowRam) -> Vec<Overwrite> {
+        let mut changes = Vec::new();
+        for (number, (page, other)) in self.pages.iter().zip(&other.pages).enumerate() {
+            if Rc::ptr_eq(page, other) {
+                continue;
+            }
+            for (offset, (old, new)) in page.iter().zip(other.iter()).enumerate() {
+                if old != new {
+                    let address = (number * PAGE_SIZE + offset) as u16;
+                    changes.push(Overwrite { address, old: *old, new: *new });
+                }
+            }
+        }
+        changes
+    }
+
+    /* The amount of pages both memories still share */
+    pub fn shared_pages(&self, other: &CowRam) -> usize {
+        self.pages.iter().zip(&other.pages).filter(|(page, other)| Rc::ptr_eq(page, other)).count()
+    }
+}
+
+impl Default for CowRam {
+    fn default() -> CowRam {
+        CowRam::new()
+    }
+}
+
+impl Memory for CowRam {
+    fn read(&self, cycles: &mut isize, address: u16) -> u8 {
+        *cycles -= 1;
+        self.get(address)
+    }
+
+    fn get(&self, address: u16) -> u8 {
+        self.pages[address as usize / PAGE_SIZE][address as usize % PAGE_SIZE]
+    }
+
+    fn write(&mut self, cycles: &mut isize, address: u16, value: u8) {
+        *cycles -= 1;
+        self.set(address, value);
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        let number = address as usize / PAGE_SIZE;
+        let page = &mut self.pages[number];
+        if page[address as usize % PAGE_SIZE] != value {
+            Rc::make_mut(page)[address as usize % PAGE_SIZE] = value;
+            self.dirty[number] = true;
+        }
+    }
+
+    fn checkpoint(&mut self) -> Option<Vec<u8>> {
+        let pages = self.dirty_pages();
+        self.dirty.fill(false);
+        Some(pages)
+    }
+}
@@ -1,13 +1,42 @@
+use std::io;
+use std::mem;
+use crate::breakpoint::{BreakpointKind, Breakpoints, StopReason};
+use crate::call_stack::{CallStack, FrameKind};
+use crate::decoder::{decode, DecodedInstruction, Operand};
+use crate::history::{History, Overwrite, UndoRecord};
+use crate::hooks::{HookAction, Hooks};
 use crate::instruction::{AddressingMode, Instruction, OPCODES};
+use crate::machine::Machine;
+use crate::observer::{Access, AccessKind, InterruptEntry, Observer};
 
 pub trait Memory: MemoryClone {
     fn read(&self, cycles: &mut isize, address: u16) -> u8;
 
     fn get(&self, address: u16) -> u8;
 
+    /* The byte stored at the address, without the patches a wrapper like CheatMemory applies to reads */
+    fn get_raw(&self, address: u16) -> u8 {
+        self.get(address)
+    }
+
     fn write(&mut self, cycles: &mut isize, address: u16, value: u8);
 
     fn set(&mut self, address: u16, value: u8);
+
+    /* Starts a new checkpoint, returning the pages written since the last one if the memory tracks them */
+    fn checkpoint(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /* Copies the memory for a forked machine, devices override it to keep their state out of the fork */
+    fn fork(&self) -> Box<dyn Memory> {
+        self.clone_box()
+    }
+
+    /* The error that closed an output device like the Console's, which drops what's written to it since */
+    fn output_error(&self) -> Option<&io::Error> {
+        None
+    }
 }
 
 pub trait MemoryClone {
@@ -26,6 +55,51 @@ impl Clone for Box<dyn Memory> {
     }
 }
 
+/* Lets devices wrap the memory of a machine or fork */
+impl Memory for Box<dyn Memory> {
+    fn read(&self, cycles: &mut isize, address: u16) -> u8 {
+        (**self).read(cycles, address)
+    }
+
+    fn get(&self, address: u16) -> u8 {
+        (**self).get(address)
+    }
+
+    fn get_raw(&self, address: u16) -> u8 {
+        (**self).get_raw(address)
+    }
+
+    fn write(&mut self, cycles: &mut isize, address: u16, value: u8) {
+        (**self).write(cycles, address, value)
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        (**self).set(address, value)
+    }
+
+    fn checkpoint(&mut self) -> Option<Vec<u8>> {
+        (**self).checkpoint()
+    }
+
+    fn fork(&self) -> Box<dyn Memory> {
+        (**self).fork()
+    }
+
+    fn output_error(&self) -> Option<&io::Error> {
+        (**self).output_error()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Registers {
+    pub program_counter: u16,
+    pub accumulator: u8,
+    pub x: u8,
+    pub y: u8,
+    pub stack_pointer: u8,
+    pub status: u8,
+}
+
 pub struct CPU<'a> {
     pub program_counter: u16,
     pub accumulator: u8,
@@ -34,6 +108,16 @@ pub struct CPU<'a> {
     pub stack_pointer: u8,
 
     pub memory: &'a mut (dyn Memory + 'a),
+    pub observers: Vec<Box<dyn Observer + 'a>>,
+    pub hooks: Hooks<'a>,
+    pub breakpoints: Breakpoints,
+    pub call_stack: CallStack,
+    /* Undo records of the latest instructions, enabled by giving it a capacity */
+    pub history: History,
+    stop_reason: Option<StopReason>,
+
+    /* The amount of cycles executed since creation */
+    pub total_cycles: u64,
 
     /* All the status register flags */
     pub negative: bool,
@@ -53,6 +137,13 @@ impl<'a> CPU<'a> {
             x: 0,
             y: 0,
             memory,
+            observers: Vec::new(),
+            hooks: Hooks::new(),
+            breakpoints: Breakpoints::new(),
+            call_stack: CallStack::new(),
+            history: History::default(),
+            stop_reason: None,
+            total_cycles: 0,
             stack_pointer: 0xFF,
             negative: false,
             overflow: false,
@@ -64,23 +155,333 @@ impl<'a> CPU<'a> {
         }
     }
 
-    pub fn run(&mut self, cycles: &mut isize) {
-        while *cycles > 0 {
+    /*
+     * Runs until the cycles are used up, a breakpoint is hit or an illegal opcode comes up. The
+     * breakpoints at the current program counter are skipped, so a stopped cpu can simply be resumed.
+     */
+    pub fn run(&mut self, cycles: &mut isize) -> Option<StopReason> {
+        self.run_until(cycles, |_| false)
+    }
+
+    /* Like run, but also stops without a reason once done returns true after an instruction */
+    pub fn run_until(&mut self, cycles: &mut isize, done: impl Fn(&CPU) -> bool) -> Option<StopReason> {
+        self.run_from(cycles, true, done)
+    }
+
+    /*
+     * Runs like run_until, but only skips the breakpoints at the program counter when resuming.
+     * Running on after a cycle limit passes false, since that instruction wasn't stopped at.
+     */
+    pub fn run_from(&mut self, cycles: &mut isize, mut resuming: bool, done: impl Fn(&CPU) -> bool) -> Option<StopReason> {
+        loop {
+            if let Some(reason) = self.stop_reason.take() {
+                return Some(reason);
+            }
+            if *cycles <= 0 || (!resuming && done(self)) {
+                return None;
+            }
+
+            if let Some(reason) = self.check_instruction(resuming) {
+                return Some(reason);
+            }
+            resuming = false;
+
             self.cycle(cycles);
         }
     }
 
+    /* Executes a single instruction, reporting any watchpoint it triggered or an illegal opcode */
+    pub fn step(&mut self, cycles: &mut isize) -> Option<StopReason> {
+        self.check_instruction(true);
+        self.cycle(cycles);
+        self.stop_reason.take()
+    }
+
+    /*
+     * Checks the breakpoints on the next instruction unless resuming from it, then logs its
+     * tracepoints if it doesn't stop. Tracepoints log on every executed instruction that way.
+     */
+    fn check_instruction(&mut self, resuming: bool) -> Option<StopReason> {
+        if self.breakpoints.is_empty() {
+            return None;
+        }
+        let opcode = self.memory.get(self.program_counter);
+        let mut breakpoints = mem::take(&mut self.breakpoints);
+        let reason = match resuming {
+            true => None,
+            false => breakpoints.check_instruction(self, self.program_counter, opcode),
+        };
+        if reason.is_none() {
+            breakpoints.trace_instruction(self, self.program_counter, opcode);
+        }
+        self.breakpoints = breakpoints;
+        reason
+    }
+
+    /* Executes the next instruction, running a called subroutine until it returns or an illegal opcode comes up */
+    pub fn step_over(&mut self, cycles: &mut isize) -> Option<StopReason> {
+        match self.next_instruction() {
+            Some(next) if matches!(next.instruction, Instruction::JumpSubroutine(_)) => {
+                let depth = self.call_stack.depth();
+                if let Some(reason) = self.step(cycles) {
+                    return Some(reason);
+                }
+                /* The subroutine's first instruction wasn't stopped at yet, so its breakpoints count */
+                self.run_from(cycles, false, |cpu| cpu.call_stack.depth() <= depth)
+            }
+            _ => self.step(cycles),
+        }
+    }
+
+    /* Runs until the current subroutine or interrupt handler returns or an illegal opcode comes up */
+    pub fn step_out(&mut self, cycles: &mut isize) -> Option<StopReason> {
+        let depth = self.call_stack.depth();
+        self.run_until(cycles, |cpu| cpu.call_stack.depth() < depth)
+    }
+
+    /* Undoes the latest recorded instruction or interrupt, returning false once the history is exhausted */
+    pub fn step_back(&mut self) -> bool {
+        self.undo().is_some()
+    }
+
+    /* Steps back until the program counter reaches the address, returning false if the history ran out first */
+    pub fn run_back_to(&mut self, address: u16) -> bool {
+        while self.step_back() {
+            if self.program_counter == address {
+                return true;
+            }
+        }
+        false
+    }
+
+    /*
+     * Steps back until right before an instruction writing to an address watched by an
+     * enabled write or access watchpoint, or onto an enabled execution breakpoint. Conditions
+     * and hit counts are ignored. Returns None once the history ran out.
+     */
+    pub fn reverse_continue(&mut self) -> Option<StopReason> {
+        while let Some(record) = self.undo() {
+            for write in &record.writes {
+                let watchpoint = self.breakpoints.find_enabled(|kind| match kind {
+                    BreakpointKind::Write(start, end) | BreakpointKind::Access(start, end) => {
+                        (start..=end).contains(&write.address)
+                    }
+                    _ => false,
+                });
+                if let Some(id) = watchpoint {
+                    let access = Access { kind: AccessKind::Write, address: write.address, value: write.new };
+                    return Some(StopReason::Watchpoint { id, access });
+                }
+            }
+
+            let address = self.program_counter;
+            if let Some(id) = self.breakpoints.find_enabled(|kind| kind == BreakpointKind::Execute(address)) {
+                return Some(StopReason::Breakpoint { id, address });
+            }
+        }
+        None
+    }
+
+    fn undo(&mut self) -> Option<UndoRecord> {
+        let record = self.history.pop()?;
+        for write in record.writes.iter().rev() {
+            self.memory.set(write.address, write.old);
+        }
+        self.set_registers(record.registers);
+        self.total_cycles = record.total_cycles;
+        self.call_stack = record.call_stack.clone();
+        Some(record)
+    }
+
+    fn begin_undo_record(&mut self) {
+        if self.history.is_recording() {
+            self.history.push(UndoRecord {
+                registers: self.registers(),
+                total_cycles: self.total_cycles,
+                call_stack: self.call_stack.clone(),
+                writes: Vec::new(),
+            });
+        }
+    }
+
+    pub fn interrupt_request(&mut self, cycles: &mut isize) {
+        if !self.interrupt {
+            self.hardware_interrupt(cycles, 0xFFFE, FrameKind::Interrupt);
+        }
+    }
+
+    pub fn non_maskable_interrupt(&mut self, cycles: &mut isize) {
+        self.hardware_interrupt(cycles, 0xFFFA, FrameKind::NonMaskableInterrupt);
+    }
+
+    fn hardware_interrupt(&mut self, cycles: &mut isize, vector: u16, kind: FrameKind) {
+        self.begin_undo_record();
+        let before = self.registers();
+        let start = *cycles;
+        *cycles -= 2;
+        let interrupted = self.program_counter;
+        self.enter_interrupt(cycles, vector, false);
+        self.total_cycles += (start - *cycles) as u64;
+
+        self.call_stack.enter_interrupt(kind, interrupted, self.program_counter, self.stack_pointer);
+        if !self.observers.is_empty() {
+            let entry = InterruptEntry { kind, before, cycles: (start - *cycles) as u64 };
+            let mut observers = mem::take(&mut self.observers);
+            for observer in observers.iter_mut() {
+                observer.interrupt(self, &entry);
+            }
+            self.observers = observers;
+        }
+        self.check_interrupt(vector);
+    }
+
     pub fn cycle(&mut self, cycles: &mut isize) {
+        if !self.hooks.is_empty() && self.run_hook(cycles) {
+            return;
+        }
+        let start = *cycles;
+
+        if OPCODES[self.memory.get(self.program_counter) as usize].is_none() {
+            self.stop_reason = Some(StopReason::IllegalOpcode { address: self.program_counter });
+            return;
+        }
+
+        if !self.observers.is_empty() {
+            if let Some(instruction) = self.next_instruction() {
+                let mut observers = mem::take(&mut self.observers);
+                for observer in observers.iter_mut() {
+                    observer.before_instruction(self, &instruction);
+                }
+                self.observers = observers;
+            }
+        }
+
+        self.begin_undo_record();
+
         /* Fetch */
-        let opcode = self.memory.read(cycles, self.program_counter);
+        let address = self.program_counter;
+        let opcode = self.read(cycles, self.program_counter, AccessKind::Opcode);
         self.program_counter += 1;
 
-        /* Decode */
-        let instruction = OPCODES[opcode as usize]
-            .expect("Illegal opcode!");
+        /* Decode, where only a device answering the fetch differently than get can still fail */
+        let Some(instruction) = OPCODES[opcode as usize] else {
+            self.program_counter = address;
+            self.stop_reason = Some(StopReason::IllegalOpcode { address });
+            return;
+        };
 
         /* Execute */
         self.execute(cycles, instruction);
+        self.call_stack.update(instruction, address, self.program_counter, self.stack_pointer);
+
+        self.total_cycles += (start - *cycles) as u64;
+
+        if !self.observers.is_empty() {
+            let mut observers = mem::take(&mut self.observers);
+            for observer in observers.iter_mut() {
+                observer.after_instruction(self);
+            }
+            self.observers = observers;
+        }
+    }
+
+    /* Runs the hook at the program counter, returning true if it simulated an RTS in place of the instruction */
+    fn run_hook(&mut self, cycles: &mut isize) -> bool {
+        let address = self.program_counter;
+        let mut hooks = mem::take(&mut self.hooks);
+        let action = hooks.call(address, self);
+        self.hooks = hooks;
+        if action != Some(HookAction::Return) {
+            return false;
+        }
+
+        /* Observers see the simulated RTS like any other instruction, which encloses its stack reads */
+        let instruction = Instruction::ReturnFromSubroutine(AddressingMode::Implied);
+        if !self.observers.is_empty() {
+            let decoded = DecodedInstruction { address, opcode: 0x60, instruction, operand: Operand::None, length: 1 };
+            let mut observers = mem::take(&mut self.observers);
+            for observer in observers.iter_mut() {
+                observer.before_instruction(self, &decoded);
+            }
+            self.observers = observers;
+        }
+
+        self.begin_undo_record();
+        let start = *cycles;
+        /* Takes as long as a real RTS, including the opcode fetch */
+        *cycles -= 1;
+        self.return_from_subroutine(cycles);
+        self.call_stack.update(instruction, address, self.program_counter, self.stack_pointer);
+        self.total_cycles += (start - *cycles) as u64;
+
+        if !self.observers.is_empty() {
+            let mut observers = mem::take(&mut self.observers);
+            for observer in observers.iter_mut() {
+                observer.after_instruction(self);
+            }
+            self.observers = observers;
+        }
+        true
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            program_counter: self.program_counter,
+            accumulator: self.accumulator,
+            x: self.x,
+            y: self.y,
+            stack_pointer: self.stack_pointer,
+            status: self.get_processor_status(),
+        }
+    }
+
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.program_counter = registers.program_counter;
+        self.accumulator = registers.accumulator;
+        self.x = registers.x;
+        self.y = registers.y;
+        self.stack_pointer = registers.stack_pointer;
+        self.set_processor_status(registers.status);
+    }
+
+    /*
+     * Copies the machine the cpu runs through Memory::fork. The observers and hooks aren't
+     * carried over, they stay with this cpu, and devices leave the fork without them.
+     */
+    pub fn fork(&self) -> Machine {
+        Machine {
+            memory: self.memory.fork(),
+            registers: self.registers(),
+            total_cycles: self.total_cycles,
+            call_stack: self.call_stack.clone(),
+            breakpoints: self.breakpoints.clone(),
+            history: self.history.clone(),
+        }
+    }
+
+    /* Changes a byte from outside of the program like a debugger does, which observers get told about */
+    pub fn edit(&mut self, address: u16, value: u8) {
+        self.memory.set(address, value);
+        if !self.observers.is_empty() {
+            let mut observers = mem::take(&mut self.observers);
+            for observer in observers.iter_mut() {
+                observer.memory_edited(self, address, value);
+            }
+            self.observers = observers;
+        }
+    }
+
+    /* Decodes the instruction at the program counter without executing it */
+    pub fn next_instruction(&self) -> Option<DecodedInstruction> {
+        decode(&*self.memory, self.program_counter)
+    }
+
+    /*
+     * Whether the cpu can't go on. A hook at an illegal opcode may take its place, so that only
+     * stops with StopReason::IllegalOpcode once the hook returned HookAction::Continue.
+     */
+    pub fn at_illegal_opcode(&self) -> bool {
+        !self.hooks.contains(self.program_counter) && self.next_instruction().is_none()
     }
 
     pub fn execute(&mut self, cycles: &mut isize, instruction: Instruction) {
@@ -95,7 +496,7 @@ impl<'a> CPU<'a> {
             Instruction::BranchOnMinus(_) => {}
             Instruction::BranchOnNotEqual(_) => {}
             Instruction::BranchOnPlus(_) => {}
-            Instruction::Break(_) => {}
+            Instruction::Break(_) => { self.break_(cycles) }
             Instruction::BranchOnOverflowClear(_) => {}
             Instruction::BranchOnOverflowSet(_) => {}
             Instruction::ClearCarry(_) => {}
@@ -112,8 +513,8 @@ impl<'a> CPU<'a> {
             Instruction::Increment(_) => {}
             Instruction::IncrementX(_) => {}
             Instruction::IncrementY(_) => {}
-            Instruction::Jump(_) => {}
-            Instruction::JumpSubroutine(_) => {}
+            Instruction::Jump(mode) => { self.jump(cycles, mode) }
+            Instruction::JumpSubroutine(_) => { self.jump_subroutine(cycles) }
             Instruction::LoadAccumulator(mode) => { self.load_accumulator(cycles, mode) }
             Instruction::LoadX(mode) => { self.load_x(cycles, mode) }
             Instruction::LoadY(mode) => { self.load_y(cycles, mode) }
@@ -126,8 +527,8 @@ impl<'a> CPU<'a> {
             Instruction::PullProcessorStatus(_) => { self.pull_processor_status(cycles) }
             Instruction::RotateLeft(_) => {}
             Instruction::RotateRight(_) => {}
-            Instruction::ReturnFormInterrupt(_) => {}
-            Instruction::ReturnFromSubroutine(_) => {}
+            Instruction::ReturnFormInterrupt(_) => { self.return_from_interrupt(cycles) }
+            Instruction::ReturnFromSubroutine(_) => { self.return_from_subroutine(cycles) }
             Instruction::SubtractWithCarry(_) => {}
             Instruction::SetCarry(_) => {}
             Instruction::SetDecimal(_) => {}
@@ -144,14 +545,14 @@ impl<'a> CPU<'a> {
         }
     }
 
-    fn get_processor_status(&self) -> u8 {
+    pub fn get_processor_status(&self) -> u8 {
         (self.negative as u8) << 7 | (self.overflow as u8) << 6 | 0x20
             | (self._break as u8) << 4 | (self.decimal as u8) << 3
             | (self.interrupt as u8) << 2 | (self.zero as u8) << 1
-            | (self.carry as u8) << 0
+            | (self.carry as u8)
     }
 
-    fn set_processor_status(&mut self, value: u8) {
+    pub fn set_processor_status(&mut self, value: u8) {
         self.negative = (value & 0x80) != 0;
         self.overflow = (value & 0x40) != 0;
         self._break = (value & 0x10) != 0;
@@ -165,19 +566,15 @@ impl<'a> CPU<'a> {
                    mode: AddressingMode) -> Option<u16> {
         match mode {
             AddressingMode::Absolute => {
-                let address_low = self.memory.read(cycles, self.program_counter) as u16;
-                self.program_counter += 1;
-                let address_high = self.memory.read(cycles, self.program_counter) as u16;
-                self.program_counter += 1;
+                let address_low = self.fetch(cycles) as u16;
+                let address_high = self.fetch(cycles) as u16;
 
                 let address = address_low + (address_high << 8);
                 Some(address)
             }
             AddressingMode::AbsoluteXIndexed => {
-                let address_low = self.memory.read(cycles, self.program_counter) as u16;
-                self.program_counter += 1;
-                let address_high = self.memory.read(cycles, self.program_counter) as u16;
-                self.program_counter += 1;
+                let address_low = self.fetch(cycles) as u16;
+                let address_high = self.fetch(cycles) as u16;
 
                 let mut address = address_low + (address_high << 8);
                 address += self.x as u16;
@@ -192,10 +589,8 @@ impl<'a> CPU<'a> {
                 Some(address)
             }
             AddressingMode::AbsoluteYIndexed => {
-                let address_low = self.memory.read(cycles, self.program_counter) as u16;
-                self.program_counter += 1;
-                let address_high = self.memory.read(cycles, self.program_counter) as u16;
-                self.program_counter += 1;
+                let address_low = self.fetch(cycles) as u16;
+                let address_high = self.fetch(cycles) as u16;
 
                 let mut address = address_low + (address_high << 8);
                 address += self.y as u16;
@@ -210,31 +605,38 @@ impl<'a> CPU<'a> {
                 Some(address)
             }
             AddressingMode::Relative => {
-                let offset = self.memory.read(cycles, self.program_counter) as i8;
-                self.program_counter += 1;
+                let offset = self.fetch(cycles) as i8;
                 let address = self.program_counter.wrapping_add(offset as u16);
                 Some(address)
             }
             AddressingMode::Zeropage => {
-                let address = self.memory.read(cycles, self.program_counter);
-                self.program_counter += 1;
+                let address = self.fetch(cycles);
                 Some(address as u16)
             }
             AddressingMode::ZeropageXIndexed => {
-                let mut address = self.memory.read(cycles, self.program_counter);
-                self.program_counter += 1;
+                let mut address = self.fetch(cycles);
                 address += self.x;
                 *cycles -= 1;
                 Some(address as u16)
             }
             AddressingMode::ZeropageYIndexed => {
-                let mut address = self.memory.read(cycles, self.program_counter);
-                self.program_counter += 1;
+                let mut address = self.fetch(cycles);
                 address += self.y;
                 *cycles -= 1;
                 Some(address as u16)
             }
-            AddressingMode::Indirect => { panic!("Not implemented yet!") }
+            AddressingMode::Indirect => {
+                let pointer_low = self.fetch(cycles) as u16;
+                let pointer_high = self.fetch(cycles) as u16;
+
+                /* The high byte is read without carrying into the page of the pointer */
+                let pointer = pointer_low + (pointer_high << 8);
+                let pointer_next = ((pointer_low + 1) & 0xFF) + (pointer_high << 8);
+
+                let address_low = self.read(cycles, pointer, AccessKind::Read) as u16;
+                let address_high = self.read(cycles, pointer_next, AccessKind::Read) as u16;
+                Some(address_low + (address_high << 8))
+            }
             AddressingMode::XIndexedIndirect => { panic!("Not implemented yet!") }
             AddressingMode::IndirectYIndexed => { panic!("Not implemented yet!") }
             _ => None,
@@ -244,35 +646,131 @@ impl<'a> CPU<'a> {
     fn get_value(&mut self, page_crossing: bool, cycles: &mut isize, mode: AddressingMode) -> u8 {
         let address = self.get_address(page_crossing, cycles, mode);
         match address {
-            Some(address) => {
-                let value = self.memory.read(cycles, address);
-                value
-            }
+            Some(address) => self.read(cycles, address, AccessKind::Read),
             None => match mode {
                 AddressingMode::Accumulator => self.accumulator,
-                AddressingMode::Immediate => {
-                    let value = self.memory.read(cycles, self.program_counter);
-                    self.program_counter += 1;
-                    value
-                }
+                AddressingMode::Immediate => self.fetch(cycles),
                 _ => { panic!("There went something terribly wrong.") }
             }
         }
     }
 
-    fn push_stack(&mut self, cycles: &mut isize, value: u8) {
-        let address = 0x0100 + self.stack_pointer as u16;
+    fn read(&mut self, cycles: &mut isize, address: u16, kind: AccessKind) -> u8 {
+        let value = self.memory.read(cycles, address);
+        self.notify_access(Access { kind, address, value });
+        value
+    }
+
+    /* Reads the next operand byte at the program counter */
+    fn fetch(&mut self, cycles: &mut isize) -> u8 {
+        let value = self.read(cycles, self.program_counter, AccessKind::Operand);
+        self.program_counter += 1;
+        value
+    }
+
+    fn write(&mut self, cycles: &mut isize, address: u16, value: u8) {
+        if self.history.is_recording() {
+            let old = self.memory.get_raw(address);
+            self.history.record_write(Overwrite { address, old, new: value });
+        }
         self.memory.write(cycles, address, value);
-        self.stack_pointer -= 1;
+        self.notify_access(Access { kind: AccessKind::Write, address, value });
+    }
+
+    fn notify_access(&mut self, access: Access) {
+        if !self.breakpoints.is_empty() && self.stop_reason.is_none() {
+            let mut breakpoints = mem::take(&mut self.breakpoints);
+            self.stop_reason = breakpoints.check_access(self, &access);
+            self.breakpoints = breakpoints;
+        }
+        for observer in self.observers.iter_mut() {
+            observer.memory_access(&access);
+        }
+    }
+
+    fn check_interrupt(&mut self, vector: u16) {
+        if !self.breakpoints.is_empty() && self.stop_reason.is_none() {
+            let mut breakpoints = mem::take(&mut self.breakpoints);
+            self.stop_reason = breakpoints.check_interrupt(self, vector);
+            self.breakpoints = breakpoints;
+        }
+    }
+
+    fn push_stack(&mut self, cycles: &mut isize, value: u8) {
+        self.push_byte(cycles, value);
         *cycles -= 1;
     }
 
     fn pop_stack(&mut self, cycles: &mut isize) -> u8 {
-        self.stack_pointer += 1;
         *cycles -= 1;
+        self.pull_byte(cycles)
+    }
+
+    /* Stack accesses without the additional cycle most stack instructions take */
+    fn push_byte(&mut self, cycles: &mut isize, value: u8) {
         let address = 0x0100 + self.stack_pointer as u16;
-        let value = self.memory.read(cycles, address);
-        value
+        self.write(cycles, address, value);
+        self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+    }
+
+    fn pull_byte(&mut self, cycles: &mut isize) -> u8 {
+        self.stack_pointer = self.stack_pointer.wrapping_add(1);
+        let address = 0x0100 + self.stack_pointer as u16;
+        self.read(cycles, address, AccessKind::Read)
+    }
+
+    fn enter_interrupt(&mut self, cycles: &mut isize, vector: u16, _break: bool) {
+        let [low, high] = self.program_counter.to_le_bytes();
+        self.push_byte(cycles, high);
+        self.push_byte(cycles, low);
+        let status = self.get_processor_status() | (_break as u8) << 4;
+        self.push_byte(cycles, status);
+        self.interrupt = true;
+
+        let low = self.read(cycles, vector, AccessKind::Read) as u16;
+        let high = self.read(cycles, vector + 1, AccessKind::Read) as u16;
+        self.program_counter = low | (high << 8);
+    }
+
+    fn break_(&mut self, cycles: &mut isize) {
+        /* The byte following the opcode is skipped as padding */
+        self.fetch(cycles);
+        self.enter_interrupt(cycles, 0xFFFE, true);
+    }
+
+    fn jump(&mut self, cycles: &mut isize, mode: AddressingMode) {
+        self.program_counter = self.get_address(false, cycles, mode)
+            .expect("Couldn't get the address of this instruction.");
+    }
+
+    fn jump_subroutine(&mut self, cycles: &mut isize) {
+        let address_low = self.fetch(cycles) as u16;
+        *cycles -= 1;
+
+        /* The pushed return address points to the last byte of the instruction */
+        let [low, high] = self.program_counter.to_le_bytes();
+        self.push_byte(cycles, high);
+        self.push_byte(cycles, low);
+
+        let address_high = self.fetch(cycles) as u16;
+        self.program_counter = address_low + (address_high << 8);
+    }
+
+    fn return_from_subroutine(&mut self, cycles: &mut isize) {
+        *cycles -= 2;
+        let low = self.pull_byte(cycles) as u16;
+        let high = self.pull_byte(cycles) as u16;
+        self.program_counter = (low | (high << 8)).wrapping_add(1);
+        *cycles -= 1;
+    }
+
+    fn return_from_interrupt(&mut self, cycles: &mut isize) {
+        *cycles -= 2;
+        let status = self.pull_byte(cycles);
+        self.set_processor_status(status);
+        let low = self.pull_byte(cycles) as u16;
+        let high = self.pull_byte(cycles) as u16;
+        self.program_counter = low | (high << 8);
     }
 
     fn load_accumulator(&mut self, cycles: &mut isize, mode: AddressingMode) {
@@ -299,19 +797,19 @@ impl<'a> CPU<'a> {
     fn store_accumulator(&mut self, cycles: &mut isize, mode: AddressingMode) {
         let address = self.get_address(false, cycles, mode)
             .expect("Couldn't get the address of this instruction.");
-        self.memory.write(cycles, address, self.accumulator);
+        self.write(cycles, address, self.accumulator);
     }
 
     fn store_x(&mut self, cycles: &mut isize, mode: AddressingMode) {
         let address = self.get_address(false, cycles, mode)
             .expect("Couldn't get the address of this instruction.");
-        self.memory.write(cycles, address, self.x);
+        self.write(cycles, address, self.x);
     }
 
     fn store_y(&mut self, cycles: &mut isize, mode: AddressingMode) {
         let address = self.get_address(false, cycles, mode)
             .expect("Couldn't get the address of this instruction.");
-        self.memory.write(cycles, address, self.y);
+        self.write(cycles, address, self.y);
     }
 
     fn transfer_accumulator_to_x(&mut self, cycles: &mut isize) {
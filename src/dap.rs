@@ -0,0 +1,704 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::io::{BufRead, ErrorKind, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use crate::breakpoint::{BreakpointKind, HitCondition, LogMessage, StopReason};
+use crate::cpu::CPU;
+use crate::debug_info::DebugInfo;
+use crate::decoder::{decode, next_address, previous_address};
+use crate::expression::Expression;
+use crate::json::Json;
+use crate::monitor::{describe_stop, Monitor};
+use crate::source_map::{step_line, SourceLocation};
+
+const THREAD: i64 = 1;
+const REGISTERS: i64 = 1;
+const FLAGS: i64 = 2;
+/* How many cycles continuing runs before checking for a pause request */
+const CHUNK: isize = 100_000;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for index in 0..4 {
+            match index <= chunk.len() {
+                true => text.push(BASE64[(value >> (18 - 6 * index) & 0x3F) as usize] as char),
+                false => text.push('='),
+            }
+        }
+    }
+    text
+}
+
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let mut value = 0u32;
+    let mut bits = 0;
+    for character in text.bytes().filter(|character| *character != b'=') {
+        value = value << 6 | BASE64.iter().position(|digit| *digit == character)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((value >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/* Reads a message framed by a Content-Length header, returning None at the end of the input */
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let mut body = vec![0; length.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    let text = String::from_utf8(body).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+    Json::parse(&text).map(Some).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/* Accepts decimal numbers, `0x` or `$` prefixed hex and symbols */
+fn parse_reference(text: &str, monitor: &Monitor) -> Option<u16> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None if text.starts_with('$') => monitor.parse_value(text).ok(),
+        None => text.parse().ok().or_else(|| monitor.symbols.address_of(text)),
+    }
+}
+
+fn parse_hit_condition(text: &str) -> Option<HitCondition> {
+    let text = text.trim();
+    if let Some(count) = text.strip_prefix(">=") {
+        Some(HitCondition::AtLeast(count.trim().parse().ok()?))
+    } else if let Some(count) = text.strip_prefix('%') {
+        Some(HitCondition::Multiple(count.trim().parse().ok()?))
+    } else {
+        Some(HitCondition::Equal(text.strip_prefix("==").unwrap_or(text).trim().parse().ok()?))
+    }
+}
+
+fn is_pause(request: &Json) -> bool {
+    request.get("command").and_then(Json::as_str) == Some("pause")
+}
+
+/*
+ * A Debug Adapter Protocol server. It works on a cpu set up by the caller, loads the program
+ * on launch and maps addresses to source lines through the monitor's optional source map,
+ * which the `debugInfo` launch argument fills from ld65 debug info. Sharing it with the
+ * monitor lets the REPL's source commands use the same debug info.
+ */
+pub struct DapServer {
+    pub monitor: Monitor,
+    seq: i64,
+    /* The cpu breakpoints created for each source file, and the protocol id of each one */
+    source_breakpoints: HashMap<String, Vec<usize>>,
+    instruction_breakpoints: Vec<usize>,
+    breakpoint_ids: HashMap<usize, usize>,
+    stop_on_entry: bool,
+    disconnected: bool,
+}
+
+impl Default for DapServer {
+    fn default() -> DapServer {
+        DapServer::new()
+    }
+}
+
+impl DapServer {
+    pub fn new() -> DapServer {
+        DapServer {
+            monitor: Monitor::new(),
+            seq: 0,
+            source_breakpoints: HashMap::new(),
+            instruction_breakpoints: Vec::new(),
+            breakpoint_ids: HashMap::new(),
+            stop_on_entry: false,
+            disconnected: false,
+        }
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    /*
+     * Serves requests until the client disconnects or the input ends. A thread reads the input,
+     * which lets a pause request stop the running program. It ends with the input or the
+     * next request after the disconnect, so a server ought to be the last one on its input.
+     */
+    pub fn serve(&mut self, cpu: &mut CPU, mut input: impl BufRead + Send + 'static, output: &mut impl Write) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || loop {
+            let message = read_message(&mut input);
+            let end = !matches!(message, Ok(Some(_)));
+            if sender.send(message).is_err() || end {
+                break;
+            }
+        });
+
+        /* Requests that arrived while the program ran */
+        let mut pending = VecDeque::new();
+        while !self.disconnected {
+            let message = match pending.pop_front() {
+                Some(message) => message,
+                None => receiver.recv().unwrap_or(Ok(None)),
+            };
+            let Some(request) = message? else {
+                return Ok(());
+            };
+            let mut interrupted = || {
+                while let Ok(message) = receiver.try_recv() {
+                    let pause = matches!(&message, Ok(Some(request)) if is_pause(request));
+                    pending.push_back(message);
+                    if pause {
+                        return true;
+                    }
+                }
+                false
+            };
+            for message in self.handle_interruptible(cpu, &request, &mut interrupted) {
+                write_message(output, &message)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn next_seq(&mut self) -> i64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> Json {
+        Json::object([
+            ("seq", self.next_seq().into()),
+            ("type", "event".into()),
+            ("event", event.into()),
+            ("body", body),
+        ])
+    }
+
+    /* Handles a request, returning the response followed by any events */
+    pub fn handle(&mut self, cpu: &mut CPU, request: &Json) -> Vec<Json> {
+        self.handle_interruptible(cpu, request, &mut || false)
+    }
+
+    /* Like handle, but continuing polls interrupted between chunks of cycles to pause */
+    pub fn handle_interruptible(&mut self, cpu: &mut CPU, request: &Json, interrupted: &mut dyn FnMut() -> bool) -> Vec<Json> {
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("");
+        let empty = Json::Object(Vec::new());
+        let arguments = request.get("arguments").unwrap_or(&empty);
+        let mut events = Vec::new();
+
+        let result = match command {
+            "initialize" => {
+                events.push(self.event("initialized", Json::Object(Vec::new())));
+                Ok(Json::object([
+                    ("supportsConfigurationDoneRequest", true.into()),
+                    ("supportsConditionalBreakpoints", true.into()),
+                    ("supportsHitConditionalBreakpoints", true.into()),
+                    ("supportsLogPoints", true.into()),
+                    ("supportsInstructionBreakpoints", true.into()),
+                    ("supportsSteppingGranularity", true.into()),
+                    ("supportsReadMemoryRequest", true.into()),
+                    ("supportsWriteMemoryRequest", true.into()),
+                    ("supportsDisassembleRequest", true.into()),
+                    ("supportsSetVariable", true.into()),
+                    ("supportsEvaluateForHovers", true.into()),
+                ]))
+            }
+            "launch" => self.launch(cpu, arguments),
+            "configurationDone" => {
+                match self.stop_on_entry {
+                    true => events.push(self.stopped("entry", None, Vec::new())),
+                    false => events.extend(self.resume(cpu, interrupted)),
+                }
+                Ok(Json::Null)
+            }
+            "setBreakpoints" => self.set_breakpoints(cpu, arguments),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(cpu, arguments),
+            "setExceptionBreakpoints" => Ok(Json::object([("breakpoints", Json::Array(Vec::new()))])),
+            "threads" => Ok(Json::object([("threads", vec![
+                Json::object([("id", THREAD.into()), ("name", "6502".into())]),
+            ].into())])),
+            "stackTrace" => Ok(self.stack_trace(cpu, arguments)),
+            "scopes" => Ok(Json::object([("scopes", vec![
+                Json::object([("name", "Registers".into()), ("variablesReference", REGISTERS.into()), ("expensive", false.into())]),
+                Json::object([("name", "Flags".into()), ("variablesReference", FLAGS.into()), ("expensive", false.into())]),
+            ].into())])),
+            "variables" => Ok(self.variables(cpu, arguments)),
+            "setVariable" => self.set_variable(cpu, arguments),
+            "continue" => {
+                events.extend(self.resume(cpu, interrupted));
+                Ok(Json::object([("allThreadsContinued", true.into())]))
+            }
+            "next" | "stepIn" => {
+                let over = command == "next";
+                let instruction = arguments.get("granularity").and_then(Json::as_str) == Some("instruction");
+                events.extend(self.step(cpu, over, instruction));
+                Ok(Json::Null)
+            }
+            "stepOut" => {
+                events.extend(self.run(cpu, |cpu, cycles| cpu.step_out(cycles), "step"));
+                Ok(Json::Null)
+            }
+            /* The program only runs while handling another request, which the pause already stopped */
+            "pause" => Ok(Json::Null),
+            "readMemory" => self.read_memory(cpu, arguments),
+            "writeMemory" => self.write_memory(cpu, arguments),
+            "disassemble" => self.disassemble(cpu, arguments),
+            "evaluate" => self.evaluate(cpu, arguments),
+            "disconnect" | "terminate" => {
+                self.disconnected = true;
+                Ok(Json::Null)
+            }
+            _ => Err(format!("Unsupported command '{command}'")),
+        };
+
+        let mut response = Json::object([
+            ("seq", self.next_seq().into()),
+            ("type", "response".into()),
+            ("request_seq", request.get("seq").cloned().unwrap_or(Json::Null)),
+            ("command", command.into()),
+            ("success", result.is_ok().into()),
+        ]);
+        match result {
+            Ok(Json::Null) => {}
+            Ok(body) => response.set("body", body),
+            Err(message) => response.set("message", message.into()),
+        }
+
+        /* Events were numbered before the response, renumber them to follow it */
+        for event in events.iter_mut() {
+            let seq = self.next_seq();
+            event.set("seq", seq.into());
+        }
+        let mut messages = vec![response];
+        messages.extend(events);
+        messages
+    }
+
+    fn launch(&mut self, cpu: &mut CPU, arguments: &Json) -> Result<Json, String> {
+        if let Some(path) = arguments.get("debugInfo").and_then(Json::as_str) {
+            let debug_info = DebugInfo::load(path)?;
+            self.monitor.symbols.merge(&debug_info.symbol_table());
+            self.monitor.source_map = Some(Box::new(debug_info));
+        }
+
+        let number = |key: &str| -> Result<Option<u16>, String> {
+            match arguments.get(key) {
+                Some(Json::String(text)) => parse_reference(text, &self.monitor)
+                    .map(Some).ok_or(format!("Invalid {key} '{text}'")),
+                Some(value) => match value.as_i64() {
+                    Some(number @ 0..=0xFFFF) => Ok(Some(number as u16)),
+                    _ => Err(format!("Invalid {key}")),
+                },
+                None => Ok(None),
+            }
+        };
+        let address = number("address")?;
+        let start = number("start")?;
+
+        if let Some(program) = arguments.get("program").and_then(Json::as_str) {
+            let bytes = fs::read(program).map_err(|error| format!("Couldn't read {program}: {error}"))?;
+            let address = address.unwrap_or(0x10000_usize.saturating_sub(bytes.len()) as u16);
+            if bytes.len() > 0x10000 - address as usize {
+                return Err(format!("{program} doesn't fit into memory at ${address:04X}"));
+            }
+            for (offset, byte) in bytes.iter().enumerate() {
+                cpu.edit(address + offset as u16, *byte);
+            }
+        }
+
+        cpu.program_counter = start.or(address).unwrap_or_else(|| {
+            u16::from_le_bytes([cpu.memory.get(0xFFFC), cpu.memory.get(0xFFFD)])
+        });
+        cpu.call_stack.clear();
+        self.stop_on_entry = arguments.get("stopOnEntry").and_then(Json::as_bool).unwrap_or(false);
+        Ok(Json::Null)
+    }
+
+    /* Applies the condition, hit condition and log message of a protocol breakpoint */
+    fn configure_breakpoint(&self, cpu: &mut CPU, id: usize, breakpoint: &Json) -> Result<(), String> {
+        let symbols = Some(&self.monitor.symbols);
+        if let Some(condition) = breakpoint.get("condition").and_then(Json::as_str) {
+            let condition = Expression::parse(condition, symbols).map_err(|error| error.to_string())?;
+            cpu.breakpoints.set_condition(id, Some(condition));
+        }
+        if let Some(hit_condition) = breakpoint.get("hitCondition").and_then(Json::as_str) {
+            let hit_condition = parse_hit_condition(hit_condition)
+                .ok_or(format!("Invalid hit condition '{hit_condition}'"))?;
+            cpu.breakpoints.set_hit_condition(id, Some(hit_condition));
+        }
+        if let Some(message) = breakpoint.get("logMessage").and_then(Json::as_str) {
+            let message = LogMessage::parse(message, symbols).map_err(|error| error.to_string())?;
+            cpu.breakpoints.set_log_message(id, Some(message));
+        }
+        Ok(())
+    }
+
+    fn remove_breakpoints(&mut self, cpu: &mut CPU, ids: Vec<usize>) {
+        for id in ids {
+            cpu.breakpoints.remove(id);
+            self.breakpoint_ids.remove(&id);
+        }
+    }
+
+    fn add_breakpoints(&mut self, cpu: &mut CPU, addresses: &[u16], breakpoint: &Json) -> Result<Vec<usize>, String> {
+        let ids: Vec<usize> = addresses.iter()
+            .map(|address| cpu.breakpoints.add(BreakpointKind::Execute(*address)))
+            .collect();
+        for id in &ids {
+            self.breakpoint_ids.insert(*id, ids[0]);
+            if let Err(error) = self.configure_breakpoint(cpu, *id, breakpoint) {
+                self.remove_breakpoints(cpu, ids);
+                return Err(error);
+            }
+        }
+        Ok(ids)
+    }
+
+    fn set_breakpoints(&mut self, cpu: &mut CPU, arguments: &Json) -> Result<Json, String> {
+        let path = arguments.get("source").and_then(|source| source.get("path")).and_then(Json::as_str)
+            .ok_or("Expected a source path")?
+            .to_string();
+        let previous = self.source_breakpoints.remove(&path).unwrap_or_default();
+        self.remove_breakpoints(cpu, previous);
+
+        let mut ids = Vec::new();
+        let mut results = Vec::new();
+        for breakpoint in arguments.get("breakpoints").and_then(Json::as_array).unwrap_or(&[]) {
+            let line = breakpoint.get("line").and_then(Json::as_i64).unwrap_or(0);
+            let addresses = match &self.monitor.source_map {
+                Some(source_map) => source_map.addresses(&path, line as u32),
+                None => Vec::new(),
+            };
+            let result = match addresses.is_empty() {
+                true => Err("No code at this line".to_string()),
+                false => self.add_breakpoints(cpu, &addresses, breakpoint),
+            };
+            results.push(match result {
+                Ok(added) => {
+                    let id = added[0];
+                    ids.extend(added);
+                    Json::object([("id", id.into()), ("verified", true.into()), ("line", line.into())])
+                }
+                Err(message) => Json::object([("verified", false.into()), ("line", line.into()), ("message", message.into())]),
+            });
+        }
+        self.source_breakpoints.insert(path, ids);
+        Ok(Json::object([("breakpoints", results.into())]))
+    }
+
+    fn set_instruction_breakpoints(&mut self, cpu: &mut CPU, arguments: &Json) -> Result<Json, String> {
+        let previous = std::mem::take(&mut self.instruction_breakpoints);
+        self.remove_breakpoints(cpu, previous);
+
+        let mut results = Vec::new();
+        for breakpoint in arguments.get("breakpoints").and_then(Json::as_array).unwrap_or(&[]) {
+            let address = breakpoint.get("instructionReference").and_then(Json::as_str)
+                .and_then(|reference| parse_reference(reference, &self.monitor))
+                .map(|address| address.wrapping_add(breakpoint.get("offset").and_then(Json::as_i64).unwrap_or(0) as u16));
+            let result = match address {
+                Some(address) => self.add_breakpoints(cpu, &[address], breakpoint),
+                None => Err("Invalid instruction reference".to_string()),
+            };
+            results.push(match result {
+                Ok(added) => {
+                    self.instruction_breakpoints.extend(&added);
+                    Json::object([("id", added[0].into()), ("verified", true.into())])
+                }
+                Err(message) => Json::object([("verified", false.into()), ("message", message.into())]),
+            });
+        }
+        Ok(Json::object([("breakpoints", results.into())]))
+    }
+
+    fn location(&self, address: u16) -> Option<SourceLocation> {
+        self.monitor.source_map.as_ref()?.location(address)
+    }
+
+    fn source(location: &SourceLocation) -> Json {
+        let name = Path::new(&location.file).file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or(location.file.clone());
+        Json::object([("name", name.into()), ("path", location.file.as_str().into())])
+    }
+
+    fn symbol(&self, address: u16) -> String {
+        match self.monitor.symbols.lookup(address) {
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{name}+{offset}"),
+            None => format!("${address:04X}"),
+        }
+    }
+
+    fn stack_trace(&self, cpu: &CPU, arguments: &Json) -> Json {
+        let mut addresses = vec![cpu.program_counter];
+        addresses.extend(cpu.call_stack.frames().iter().rev().map(|frame| frame.caller));
+
+        let start = arguments.get("startFrame").and_then(Json::as_i64).unwrap_or(0).max(0) as usize;
+        let levels = match arguments.get("levels").and_then(Json::as_i64) {
+            Some(levels) if levels > 0 => levels as usize,
+            _ => addresses.len(),
+        };
+        let frames: Vec<Json> = addresses.iter().enumerate().skip(start).take(levels).map(|(index, address)| {
+            let mut frame = Json::object([
+                ("id", index.into()),
+                ("name", self.symbol(*address).into()),
+                ("instructionPointerReference", format!("0x{address:04X}").into()),
+                ("line", 0i64.into()),
+                ("column", 0i64.into()),
+            ]);
+            if let Some(location) = self.location(*address) {
+                frame.set("source", DapServer::source(&location));
+                frame.set("line", (location.line as i64).into());
+                frame.set("column", 1i64.into());
+            }
+            frame
+        }).collect();
+        Json::object([("stackFrames", frames.into()), ("totalFrames", addresses.len().into())])
+    }
+
+    fn variables(&self, cpu: &CPU, arguments: &Json) -> Json {
+        let variable = |name: &str, value: String| Json::object([
+            ("name", name.into()),
+            ("value", value.into()),
+            ("variablesReference", 0i64.into()),
+        ]);
+        let variables = match arguments.get("variablesReference").and_then(Json::as_i64) {
+            Some(REGISTERS) => vec![
+                variable("A", format!("${:02X}", cpu.accumulator)),
+                variable("X", format!("${:02X}", cpu.x)),
+                variable("Y", format!("${:02X}", cpu.y)),
+                variable("SP", format!("${:02X}", cpu.stack_pointer)),
+                variable("PC", format!("${:04X}", cpu.program_counter)),
+                variable("P", format!("${:02X}", cpu.get_processor_status())),
+            ],
+            Some(FLAGS) => [("N", cpu.negative), ("V", cpu.overflow), ("B", cpu._break), ("D", cpu.decimal),
+                ("I", cpu.interrupt), ("Z", cpu.zero), ("C", cpu.carry)].iter()
+                .map(|(name, value)| variable(name, (*value as u8).to_string()))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Json::object([("variables", variables.into())])
+    }
+
+    fn set_variable(&self, cpu: &mut CPU, arguments: &Json) -> Result<Json, String> {
+        let name = arguments.get("name").and_then(Json::as_str).ok_or("Expected a name")?;
+        let value = arguments.get("value").and_then(Json::as_str).ok_or("Expected a value")?;
+        let value = Expression::parse(value, Some(&self.monitor.symbols))
+            .map_err(|error| error.to_string())?
+            .evaluate(cpu);
+
+        let text = match name {
+            "A" => { cpu.accumulator = value as u8; format!("${:02X}", cpu.accumulator) }
+            "X" => { cpu.x = value as u8; format!("${:02X}", cpu.x) }
+            "Y" => { cpu.y = value as u8; format!("${:02X}", cpu.y) }
+            "SP" => { cpu.stack_pointer = value as u8; format!("${:02X}", cpu.stack_pointer) }
+            "PC" => { cpu.program_counter = value as u16; format!("${:04X}", cpu.program_counter) }
+            "P" => { cpu.set_processor_status(value as u8); format!("${:02X}", cpu.get_processor_status()) }
+            flag => {
+                let set = value != 0;
+                match flag {
+                    "N" => cpu.negative = set,
+                    "V" => cpu.overflow = set,
+                    "B" => cpu._break = set,
+                    "D" => cpu.decimal = set,
+                    "I" => cpu.interrupt = set,
+                    "Z" => cpu.zero = set,
+                    "C" => cpu.carry = set,
+                    _ => return Err(format!("Unknown variable '{name}'")),
+                }
+                (set as u8).to_string()
+            }
+        };
+        Ok(Json::object([("value", text.into())]))
+    }
+
+    fn memory_range(&self, arguments: &Json) -> Result<u16, String> {
+        let reference = arguments.get("memoryReference").and_then(Json::as_str).ok_or("Expected a memory reference")?;
+        let address = parse_reference(reference, &self.monitor).ok_or(format!("Invalid memory reference '{reference}'"))?;
+        let offset = arguments.get("offset").and_then(Json::as_i64).unwrap_or(0);
+        Ok(address.wrapping_add(offset as u16))
+    }
+
+    fn read_memory(&self, cpu: &CPU, arguments: &Json) -> Result<Json, String> {
+        let address = self.memory_range(arguments)?;
+        let count = arguments.get("count").and_then(Json::as_i64).unwrap_or(0).max(0) as usize;
+        let readable = count.min(0x10000 - address as usize);
+        let bytes: Vec<u8> = (0..readable).map(|offset| cpu.memory.get(address + offset as u16)).collect();
+        Ok(Json::object([
+            ("address", format!("0x{address:04X}").into()),
+            ("data", encode_base64(&bytes).into()),
+            ("unreadableBytes", (count - readable).into()),
+        ]))
+    }
+
+    fn write_memory(&self, cpu: &mut CPU, arguments: &Json) -> Result<Json, String> {
+        let address = self.memory_range(arguments)?;
+        let data = arguments.get("data").and_then(Json::as_str).ok_or("Expected data")?;
+        let bytes = decode_base64(data).ok_or("Invalid base64 data")?;
+        for (offset, byte) in bytes.iter().enumerate() {
+            cpu.edit(address.wrapping_add(offset as u16), *byte);
+        }
+        Ok(Json::object([("bytesWritten", bytes.len().into())]))
+    }
+
+    fn disassemble(&self, cpu: &CPU, arguments: &Json) -> Result<Json, String> {
+        let mut address = self.memory_range(arguments)?;
+        let memory = &*cpu.memory;
+        let offset = arguments.get("instructionOffset").and_then(Json::as_i64).unwrap_or(0);
+        for _ in 0..offset.unsigned_abs() {
+            address = match offset < 0 {
+                true => previous_address(memory, address),
+                false => next_address(memory, address),
+            };
+        }
+
+        let count = arguments.get("instructionCount").and_then(Json::as_i64).unwrap_or(0).max(0);
+        let mut instructions = Vec::new();
+        for _ in 0..count {
+            let (bytes, text) = match decode(memory, address) {
+                Some(instruction) => (instruction.bytes(), instruction.to_string()),
+                None => (vec![memory.get(address)], "???".to_string()),
+            };
+            let bytes: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            let mut instruction = Json::object([
+                ("address", format!("0x{address:04X}").into()),
+                ("instructionBytes", bytes.join(" ").into()),
+                ("instruction", text.into()),
+            ]);
+            if let Some(name) = self.monitor.symbols.name_of(address) {
+                instruction.set("symbol", name.into());
+            }
+            if let Some(location) = self.location(address) {
+                instruction.set("location", DapServer::source(&location));
+                instruction.set("line", (location.line as i64).into());
+            }
+            instructions.push(instruction);
+            address = next_address(memory, address);
+        }
+        Ok(Json::object([("instructions", instructions.into())]))
+    }
+
+    fn evaluate(&mut self, cpu: &mut CPU, arguments: &Json) -> Result<Json, String> {
+        let expression = arguments.get("expression").and_then(Json::as_str).ok_or("Expected an expression")?;
+        let result = match arguments.get("context").and_then(Json::as_str) {
+            /* The debug console takes monitor commands */
+            Some("repl") => self.monitor.execute(cpu, expression)?,
+            _ => {
+                let value = Expression::parse(expression, Some(&self.monitor.symbols))
+                    .map_err(|error| error.to_string())?
+                    .evaluate(cpu);
+                format!("{value} (${value:X})")
+            }
+        };
+        Ok(Json::object([("result", result.into()), ("variablesReference", 0i64.into())]))
+    }
+
+    fn stopped(&mut self, reason: &str, description: Option<String>, hit: Vec<usize>) -> Json {
+        let mut body = Json::object([
+            ("reason", reason.into()),
+            ("threadId", THREAD.into()),
+            ("allThreadsStopped", true.into()),
+        ]);
+        if let Some(description) = description {
+            body.set("description", description.clone().into());
+            body.set("text", description.into());
+        }
+        if !hit.is_empty() {
+            body.set("hitBreakpointIds", hit.into_iter().map(Json::from).collect::<Vec<_>>().into());
+        }
+        self.event("stopped", body)
+    }
+
+    /* Runs the action and reports how it stopped, along with messages logged on the way */
+    fn run(&mut self, cpu: &mut CPU, action: impl FnOnce(&mut CPU, &mut isize) -> Option<StopReason>,
+           reason: &str) -> Vec<Json> {
+        let mut cycles = self.monitor.cycle_limit;
+        let stop = action(cpu, &mut cycles);
+        self.report(cpu, stop, cycles, reason)
+    }
+
+    /* Continues up to the cycle limit in chunks, stopping with a pause once interrupted returns true between them */
+    fn resume(&mut self, cpu: &mut CPU, interrupted: &mut dyn FnMut() -> bool) -> Vec<Json> {
+        let mut cycles = self.monitor.cycle_limit;
+        let mut resuming = true;
+        loop {
+            let mut chunk = cycles.min(CHUNK);
+            let start = chunk;
+            let stop = cpu.run_from(&mut chunk, resuming, |_| false);
+            cycles -= start - chunk;
+            resuming = false;
+            if stop.is_some() || cycles <= 0 {
+                return self.report(cpu, stop, cycles, "breakpoint");
+            }
+            if interrupted() {
+                return self.report(cpu, None, cycles, "pause");
+            }
+        }
+    }
+
+    fn report(&mut self, cpu: &mut CPU, stop: Option<StopReason>, cycles: isize, reason: &str) -> Vec<Json> {
+        let mut events = Vec::new();
+        for message in cpu.breakpoints.take_messages() {
+            let body = Json::object([("category", "console".into()), ("output", format!("{message}\n").into())]);
+            events.push(self.event("output", body));
+        }
+
+        events.push(match stop {
+            Some(StopReason::Breakpoint { id, .. }) => {
+                let id = self.breakpoint_ids.get(&id).copied().unwrap_or(id);
+                self.stopped("breakpoint", None, vec![id])
+            }
+            Some(StopReason::Watchpoint { .. }) => self.stopped("data breakpoint", stop.map(|stop| describe_stop(&stop)), Vec::new()),
+            Some(StopReason::Interrupt { .. } | StopReason::IllegalOpcode { .. }) => {
+                self.stopped("exception", stop.map(|stop| describe_stop(&stop)), Vec::new())
+            }
+            None if cycles <= 0 => {
+                self.stopped("pause", Some(format!("Stopped after {} cycles", self.monitor.cycle_limit)), Vec::new())
+            }
+            None => self.stopped(reason, None, Vec::new()),
+        });
+        events
+    }
+
+    /* Steps a source line when the program counter has one, otherwise a single instruction */
+    fn step(&mut self, cpu: &mut CPU, over: bool, instruction: bool) -> Vec<Json> {
+        let mut cycles = self.monitor.cycle_limit;
+        let stop = match (&self.monitor.source_map, instruction, over) {
+            (Some(source_map), false, _) => step_line(cpu, source_map.as_ref(), over, &mut cycles),
+            (_, _, true) => cpu.step_over(&mut cycles),
+            (_, _, false) => cpu.step(&mut cycles),
+        };
+        self.report(cpu, stop, cycles, "step")
+    }
+}
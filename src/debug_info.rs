@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+use crate::cpu::CPU;
+use crate::decoder::DecodedInstruction;
+use crate::observer::Observer;
+use crate::source_map::{SourceLocation, SourceMap};
+use crate::symbols::SymbolTable;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceFile {
+    pub id: usize,
+    pub name: String,
+    pub size: u64,
+    pub mtime: u64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    Assembler,
+    External,
+    Macro,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub id: usize,
+    pub file: usize,
+    pub line: u32,
+    pub kind: LineKind,
+    pub spans: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub id: usize,
+    pub name: String,
+    pub start: u32,
+    pub size: u32,
+    pub output_name: Option<String>,
+    pub output_offset: Option<u32>,
+}
+
+/* A range of bytes, relative to the start of its segment */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub id: usize,
+    pub segment: usize,
+    pub start: u32,
+    pub size: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub id: usize,
+    pub name: String,
+    pub value: i64,
+    pub scope: Option<usize>,
+    pub segment: Option<usize>,
+    pub size: Option<u32>,
+    /* `lab` for labels, `equ` for equates and `imp` for imports */
+    pub kind: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scope {
+    pub id: usize,
+    pub name: String,
+    pub parent: Option<usize>,
+    pub size: Option<u32>,
+    pub spans: Vec<usize>,
+}
+
+type Attributes<'t> = HashMap<&'t str, &'t str>;
+
+fn parse_attributes(text: &str) -> Result<Attributes<'_>, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=').ok_or(format!("Expected key=value in '{rest}'"))?;
+        let (value, next) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').ok_or("Unterminated string")?;
+                (&quoted[..end], quoted[end + 1..].strip_prefix(',').unwrap_or(&quoted[end + 1..]))
+            }
+            None => match value.split_once(',') {
+                Some((value, next)) => (value, next),
+                None => (value, ""),
+            },
+        };
+        attributes.insert(key.trim(), value);
+        rest = next;
+    }
+    Ok(attributes)
+}
+
+fn number(attributes: &Attributes, key: &str) -> Result<Option<u64>, String> {
+    let Some(text) = attributes.get(key) else {
+        return Ok(None);
+    };
+    let value = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    value.map(Some).map_err(|_| format!("Invalid {key} '{text}'"))
+}
+
+fn required(attributes: &Attributes, key: &str) -> Result<u64, String> {
+    number(attributes, key)?.ok_or(format!("Missing {key}"))
+}
+
+fn id_list(attributes: &Attributes, key: &str) -> Result<Vec<usize>, String> {
+    match attributes.get(key) {
+        Some(list) => list.split('+')
+            .map(|id| id.parse().map_err(|_| format!("Invalid {key} '{list}'")))
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+/*
+ * The debug info written by `ld65 --dbgfile`. It maps addresses to the source lines and
+ * scopes that generated them and provides the symbols of the program.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct DebugInfo {
+    pub files: HashMap<usize, SourceFile>,
+    pub lines: HashMap<usize, Line>,
+    pub segments: HashMap<usize, Segment>,
+    pub spans: HashMap<usize, Span>,
+    pub symbols: HashMap<usize, Symbol>,
+    pub scopes: HashMap<usize, Scope>,
+    /* The best line for each address, assembler lines win over C and macro lines, then shorter spans */
+    addresses: HashMap<u16, usize>,
+}
+
+impl DebugInfo {
+    pub fn load(path: &str) -> Result<DebugInfo, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("Couldn't read {path}: {error}"))?;
+        DebugInfo::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<DebugInfo, String> {
+        let mut info = DebugInfo::default();
+        for (index, line) in text.lines().enumerate() {
+            info.parse_line(line).map_err(|message| format!("Line {}: {message}", index + 1))?;
+        }
+        info.index();
+        Ok(info)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(());
+        }
+        let (record, attributes) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let attributes = parse_attributes(attributes.trim())?;
+        let id = || required(&attributes, "id").map(|id| id as usize);
+        let optional_id = |key| number(&attributes, key).map(|id| id.map(|id| id as usize));
+
+        match record {
+            "version" if required(&attributes, "major")? != 2 => {
+                return Err("Only version 2 debug info is supported".to_string());
+            }
+            "file" => {
+                let file = SourceFile {
+                    id: id()?,
+                    name: attributes.get("name").ok_or("Missing name")?.to_string(),
+                    size: number(&attributes, "size")?.unwrap_or(0),
+                    mtime: number(&attributes, "mtime")?.unwrap_or(0),
+                };
+                self.files.insert(file.id, file);
+            }
+            "line" => {
+                let line = Line {
+                    id: id()?,
+                    file: required(&attributes, "file")? as usize,
+                    line: required(&attributes, "line")? as u32,
+                    kind: match number(&attributes, "type")? {
+                        None | Some(0) => LineKind::Assembler,
+                        Some(1) => LineKind::External,
+                        Some(_) => LineKind::Macro,
+                    },
+                    spans: id_list(&attributes, "span")?,
+                };
+                self.lines.insert(line.id, line);
+            }
+            "seg" => {
+                let segment = Segment {
+                    id: id()?,
+                    name: attributes.get("name").ok_or("Missing name")?.to_string(),
+                    start: required(&attributes, "start")? as u32,
+                    size: required(&attributes, "size")? as u32,
+                    output_name: attributes.get("oname").map(|name| name.to_string()),
+                    output_offset: number(&attributes, "ooffs")?.map(|offset| offset as u32),
+                };
+                self.segments.insert(segment.id, segment);
+            }
+            "span" => {
+                let span = Span {
+                    id: id()?,
+                    segment: required(&attributes, "seg")? as usize,
+                    start: required(&attributes, "start")? as u32,
+                    size: required(&attributes, "size")? as u32,
+                };
+                self.spans.insert(span.id, span);
+            }
+            "sym" => {
+                let symbol = Symbol {
+                    id: id()?,
+                    name: attributes.get("name").ok_or("Missing name")?.to_string(),
+                    value: number(&attributes, "val")?.unwrap_or(0) as i64,
+                    scope: optional_id("scope")?,
+                    segment: optional_id("seg")?,
+                    size: number(&attributes, "size")?.map(|size| size as u32),
+                    kind: attributes.get("type").unwrap_or(&"lab").to_string(),
+                };
+                self.symbols.insert(symbol.id, symbol);
+            }
+            "scope" => {
+                let scope = Scope {
+                    id: id()?,
+                    name: attributes.get("name").unwrap_or(&"").to_string(),
+                    parent: optional_id("parent")?,
+                    size: number(&attributes, "size")?.map(|size| size as u32),
+                    spans: id_list(&attributes, "span")?,
+                };
+                self.scopes.insert(scope.id, scope);
+            }
+            /* Info, libraries, modules, C symbols and types aren't needed for debugging */
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /* The absolute addresses covered by a span */
+    pub fn span_range(&self, span: usize) -> Option<(u32, u32)> {
+        let span = self.spans.get(&span)?;
+        let segment = self.segments.get(&span.segment)?;
+        let start = segment.start + span.start;
+        Some((start, start + span.size))
+    }
+
+    fn index(&mut self) {
+        let rank = |kind| match kind {
+            LineKind::Assembler => 0,
+            LineKind::External => 1,
+            LineKind::Macro => 2,
+        };
+        let mut best: HashMap<u16, (u8, u32, usize)> = HashMap::new();
+        for line in self.lines.values() {
+            for span in &line.spans {
+                let Some((start, end)) = self.span_range(*span) else {
+                    continue;
+                };
+                let candidate = (rank(line.kind), end - start, line.id);
+                for address in start..end.min(0x10000) {
+                    let entry = best.entry(address as u16).or_insert(candidate);
+                    if candidate < *entry {
+                        *entry = candidate;
+                    }
+                }
+            }
+        }
+        self.addresses = best.into_iter().map(|(address, (_, _, line))| (address, line)).collect();
+    }
+
+    pub fn line_at(&self, address: u16) -> Option<&Line> {
+        self.lines.get(self.addresses.get(&address)?)
+    }
+
+    /* The innermost named scope covering the address */
+    pub fn scope_at(&self, address: u16) -> Option<&Scope> {
+        self.scopes.values()
+            .filter(|scope| scope.spans.iter().any(|span| {
+                self.span_range(*span).is_some_and(|(start, end)| (start..end).contains(&(address as u32)))
+            }))
+            .min_by_key(|scope| (scope.size.unwrap_or(u32::MAX), scope.id))
+    }
+
+    /* The scope names from the outermost to the innermost, joined by `::` like ca65 does */
+    pub fn scope_name(&self, scope: usize) -> String {
+        let mut names = Vec::new();
+        let mut current = self.scopes.get(&scope);
+        while let Some(scope) = current {
+            if !scope.name.is_empty() {
+                names.push(scope.name.as_str());
+            }
+            current = scope.parent.and_then(|parent| self.scopes.get(&parent));
+        }
+        names.reverse();
+        names.join("::")
+    }
+
+    /* Labels and equates fitting into 16 bits, qualified by their scope unless they're global */
+    pub fn symbol_table(&self) -> SymbolTable {
+        let mut symbols: Vec<&Symbol> = self.symbols.values()
+            .filter(|symbol| symbol.kind != "imp" && (0..=0xFFFF).contains(&symbol.value))
+            .collect();
+        symbols.sort_by_key(|symbol| symbol.id);
+
+        let mut table = SymbolTable::new();
+        for symbol in symbols {
+            let scope = symbol.scope.map(|scope| self.scope_name(scope)).unwrap_or_default();
+            let name = match scope.is_empty() {
+                true => symbol.name.clone(),
+                false => format!("{scope}::{}", symbol.name),
+            };
+            table.insert(&name, symbol.value as u16);
+        }
+        table
+    }
+}
+
+fn matches_file(path: &str, file: &str) -> bool {
+    let path = path.replace('\\', "/");
+    let file = file.replace('\\', "/");
+    path == file || path.ends_with(&format!("/{file}")) || file.ends_with(&format!("/{path}"))
+}
+
+impl SourceMap for DebugInfo {
+    fn location(&self, address: u16) -> Option<SourceLocation> {
+        let line = self.line_at(address)?;
+        Some(SourceLocation { file: self.files.get(&line.file)?.name.clone(), line: line.line })
+    }
+
+    fn addresses(&self, file: &str, line: u32) -> Vec<u16> {
+        let mut addresses: Vec<u16> = self.lines.values()
+            .filter(|entry| entry.line == line)
+            .filter(|entry| self.files.get(&entry.file).is_some_and(|source| matches_file(&source.name, file)))
+            .flat_map(|entry| entry.spans.iter().filter_map(|span| self.span_range(*span)))
+            .filter(|(start, _)| *start <= 0xFFFF)
+            .map(|(start, _)| start as u16)
+            .collect();
+        addresses.sort();
+        addresses.dedup();
+        addresses
+    }
+}
+
+/*
+ * Writes one line per instruction with the nearest symbol and the source line that generated it.
+ * Like the Tracer it stops at the first write error, which error() returns.
+ */
+pub struct SourceTracer<W: Write> {
+    output: W,
+    error: Option<io::Error>,
+    symbols: SymbolTable,
+    debug_info: Rc<DebugInfo>,
+}
+
+impl<W: Write> SourceTracer<W> {
+    pub fn new(output: W, debug_info: Rc<DebugInfo>) -> SourceTracer<W> {
+        SourceTracer { output, error: None, symbols: debug_info.symbol_table(), debug_info }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.output
+    }
+
+    /* The error that stopped the trace, if any */
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+impl<W: Write> Observer for SourceTracer<W> {
+    fn before_instruction(&mut self, _cpu: &CPU, instruction: &DecodedInstruction) {
+        if self.error.is_some() {
+            return;
+        }
+        let symbol = match self.symbols.lookup(instruction.address) {
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{name}+{offset}"),
+            None => String::new(),
+        };
+        let location = match self.debug_info.location(instruction.address) {
+            Some(location) => format!("{}:{}", location.file, location.line),
+            None => String::new(),
+        };
+        let line = format!("{:04X}  {:<20}  {:<16}  {}", instruction.address, symbol, instruction.to_string(), location);
+        self.error = writeln!(self.output, "{}", line.trim_end()).err();
+    }
+}
@@ -0,0 +1,310 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use crate::breakpoint::{BreakpointKind, StopReason};
+use crate::cpu::CPU;
+use crate::decoder::{next_address, previous_address, DecodedInstruction};
+use crate::monitor::{describe_breakpoint, describe_stop, Monitor};
+use crate::observer::Observer;
+
+const TRACE_LENGTH: usize = 64;
+const MEMORY_ROWS: usize = 6;
+const TRACE_ROWS: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Enter,
+    Backspace,
+    Escape,
+}
+
+/* Remembers the most recently executed instructions for the trace pane */
+#[derive(Clone, Debug, Default)]
+pub struct TraceTail {
+    lines: VecDeque<String>,
+}
+
+impl TraceTail {
+    pub fn lines(&self) -> impl Iterator<Item=&String> {
+        self.lines.iter()
+    }
+}
+
+impl Observer for TraceTail {
+    fn before_instruction(&mut self, _cpu: &CPU, instruction: &DecodedInstruction) {
+        if self.lines.len() == TRACE_LENGTH {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(format!("{:04X}  {}", instruction.address, instruction));
+    }
+}
+
+/*
+ * A full-screen debugger drawn with plain ANSI escape sequences. It is driven by keys and
+ * renders complete frames, leaving the terminal handling to the caller.
+ */
+pub struct Debugger {
+    pub monitor: Monitor,
+    pub trace: Rc<RefCell<TraceTail>>,
+    /* The first address shown in the disassembly pane and the selected instruction */
+    top: u16,
+    cursor: u16,
+    pub memory_address: u16,
+    command: Option<String>,
+    message: String,
+}
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            monitor: Monitor::new(),
+            trace: Rc::new(RefCell::new(TraceTail::default())),
+            top: 0,
+            cursor: 0,
+            memory_address: 0,
+            command: None,
+            message: String::new(),
+        }
+    }
+
+    /* Registers the trace tail with the cpu and moves the cursor to the program counter */
+    pub fn attach(&mut self, cpu: &mut CPU) {
+        cpu.observers.push(Box::new(self.trace.clone()));
+        self.follow(cpu);
+    }
+
+    pub fn cursor(&self) -> u16 {
+        self.cursor
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /* Handles a key press, returning false once the user quits */
+    pub fn handle_key(&mut self, cpu: &mut CPU, key: Key) -> bool {
+        if let Some(command) = &mut self.command {
+            match key {
+                Key::Char(character) => command.push(character),
+                Key::Backspace => {
+                    command.pop();
+                }
+                Key::Enter => {
+                    let command = self.command.take().unwrap_or_default();
+                    self.message = match self.monitor.execute(cpu, &command) {
+                        Ok(output) => output.lines().last().unwrap_or("").to_string(),
+                        Err(error) => format!("Error: {error}"),
+                    };
+                    self.follow(cpu);
+                }
+                Key::Escape => self.command = None,
+                _ => {}
+            }
+            return true;
+        }
+
+        match key {
+            Key::Char('q') => return false,
+            Key::Char('s') => self.execute(cpu, |cpu, cycles| cpu.step(cycles)),
+            Key::Char('n') => self.execute(cpu, |cpu, cycles| cpu.step_over(cycles)),
+            Key::Char('o') => self.execute(cpu, |cpu, cycles| cpu.step_out(cycles)),
+            Key::Char('g') => {
+                self.execute(cpu, |cpu, cycles| cpu.run(cycles))
+            }
+            Key::Char('c') => {
+                let cursor = self.cursor;
+                self.execute(cpu, |cpu, cycles| cpu.run_until(cycles, |cpu| cpu.program_counter == cursor))
+            }
+            Key::Char('b') => self.toggle_breakpoint(cpu),
+            Key::Char(':') => self.command = Some(String::new()),
+            Key::Up | Key::Char('k') => self.cursor = previous_address(&*cpu.memory, self.cursor),
+            Key::Down | Key::Char('j') => self.cursor = next_address(&*cpu.memory, self.cursor),
+            Key::PageUp | Key::Char('[') => {
+                self.memory_address = self.memory_address.wrapping_sub(16 * MEMORY_ROWS as u16)
+            }
+            Key::PageDown | Key::Char(']') => {
+                self.memory_address = self.memory_address.wrapping_add(16 * MEMORY_ROWS as u16)
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn execute(&mut self, cpu: &mut CPU, action: impl FnOnce(&mut CPU, &mut isize) -> Option<StopReason>) {
+        let mut cycles = self.monitor.cycle_limit;
+        let reason = action(cpu, &mut cycles);
+        let mut messages = cpu.breakpoints.take_messages();
+        match reason {
+            Some(reason) => messages.push(describe_stop(&reason)),
+            None if cycles <= 0 => messages.push("Stopped at the cycle limit".to_string()),
+            None => {}
+        }
+        self.message = messages.pop().unwrap_or_default();
+        self.follow(cpu);
+    }
+
+    fn toggle_breakpoint(&mut self, cpu: &mut CPU) {
+        let existing = cpu.breakpoints.iter()
+            .find(|breakpoint| breakpoint.kind == BreakpointKind::Execute(self.cursor))
+            .map(|breakpoint| breakpoint.id);
+        match existing {
+            Some(id) => {
+                cpu.breakpoints.remove(id);
+            }
+            None => {
+                cpu.breakpoints.add(BreakpointKind::Execute(self.cursor));
+            }
+        }
+    }
+
+    fn follow(&mut self, cpu: &CPU) {
+        self.cursor = cpu.program_counter;
+    }
+
+    /* Renders a complete frame for a terminal of the size, including the escape sequences */
+    pub fn render(&mut self, cpu: &CPU, width: usize, height: usize) -> String {
+        if width < 60 || height < 24 {
+            return format!("\x1b[H\x1b[2JThe terminal needs to be at least 60x24, it is {width}x{height}");
+        }
+
+        let top_height = height - 4 - MEMORY_ROWS - TRACE_ROWS;
+        let left_width = width / 2;
+        let right_width = width - left_width - 1;
+
+        let mut lines = vec![inverse(&fit(
+            " s step  n next  o out  g run  c run to cursor  b breakpoint  : command  q quit", width))];
+
+        let disassembly = self.disassembly(cpu, top_height - 1, left_width);
+        let mut right = pane("Registers", self.registers(cpu), right_width, 3);
+        right.extend(pane("Stack", self.stack(cpu), right_width, 4));
+        let breakpoints: Vec<String> = cpu.breakpoints.iter().map(describe_breakpoint).collect();
+        right.extend(pane("Breakpoints", breakpoints, right_width, top_height - 7));
+        for (left, right) in pane_lines("Disassembly", disassembly, left_width, top_height).iter().zip(right) {
+            lines.push(format!("{left} {right}"));
+        }
+
+        lines.extend(pane("Memory", self.memory(cpu), width, MEMORY_ROWS + 1));
+        let tail = self.trace.borrow();
+        let trace: Vec<String> = tail.lines().skip(tail.lines.len().saturating_sub(TRACE_ROWS)).cloned().collect();
+        lines.extend(pane("Trace", trace, width, TRACE_ROWS + 1));
+
+        lines.push(match &self.command {
+            Some(command) => fit(&format!(":{command}"), width),
+            None => fit(&self.message, width),
+        });
+        format!("\x1b[H{}", lines.join("\x1b[K\r\n"))
+    }
+
+    fn disassembly(&mut self, cpu: &CPU, rows: usize, width: usize) -> Vec<String> {
+        let mut addresses = self.addresses(cpu, self.top, rows);
+        /*
+         * Puts the cursor a third of the way down with the instructions leading up to it above,
+         * backing up less when decoding from a guessed start doesn't line up with the cursor
+         */
+        let mut back = rows / 3;
+        while rows > 0 && !addresses.contains(&self.cursor) {
+            self.top = (0..back).fold(self.cursor, |address, _| previous_address(&*cpu.memory, address));
+            addresses = self.addresses(cpu, self.top, rows);
+            back = back.saturating_sub(1);
+        }
+
+        addresses.iter().map(|address| {
+            let marker = match (*address == cpu.program_counter, *address == self.cursor) {
+                (true, _) => '>',
+                (false, true) => '-',
+                _ => ' ',
+            };
+            let breakpoint = match cpu.breakpoints.iter().any(|breakpoint| {
+                breakpoint.enabled && breakpoint.kind == BreakpointKind::Execute(*address)
+            }) {
+                true => '*',
+                false => ' ',
+            };
+            let (text, _) = self.monitor.format_instruction(cpu, *address);
+            let text = fit(&format!("{breakpoint}{marker}{}", text.lines().last().unwrap_or("")), width);
+            match *address == self.cursor {
+                true => inverse(&text),
+                false => text,
+            }
+        }).collect()
+    }
+
+    fn addresses(&self, cpu: &CPU, start: u16, rows: usize) -> Vec<u16> {
+        let mut addresses = Vec::with_capacity(rows);
+        let mut address = start;
+        for _ in 0..rows {
+            addresses.push(address);
+            address = next_address(&*cpu.memory, address);
+        }
+        addresses
+    }
+
+    fn registers(&self, cpu: &CPU) -> Vec<String> {
+        let flags: String = "NV-BDIZC".chars().enumerate().map(|(bit, name)| {
+            match cpu.get_processor_status() & (0x80 >> bit) != 0 {
+                true => name,
+                false => name.to_ascii_lowercase(),
+            }
+        }).collect();
+        vec![
+            format!("PC {:04X}  A {:02X}  X {:02X}  Y {:02X}  SP {:02X}",
+                    cpu.program_counter, cpu.accumulator, cpu.x, cpu.y, cpu.stack_pointer),
+            format!("P {flags}  CYC {}", cpu.total_cycles),
+        ]
+    }
+
+    fn stack(&self, cpu: &CPU) -> Vec<String> {
+        (0..3).map(|row| {
+            let start = cpu.stack_pointer.wrapping_add(1 + row * 8);
+            let bytes: Vec<String> = (0..8)
+                .map(|offset| format!("{:02X}", cpu.memory.get(0x0100 | start.wrapping_add(offset) as u16)))
+                .collect();
+            format!("{:04X}  {}", 0x0100 | start as u16, bytes.join(" "))
+        }).collect()
+    }
+
+    fn memory(&self, cpu: &CPU) -> Vec<String> {
+        (0..MEMORY_ROWS as u16).map(|row| {
+            let start = self.memory_address.wrapping_add(row * 16);
+            let bytes: Vec<u8> = (0..16).map(|offset| cpu.memory.get(start.wrapping_add(offset))).collect();
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            let text: String = bytes.iter()
+                .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+                .collect();
+            format!("{:04X}  {}  {}", start, hex.join(" "), text)
+        }).collect()
+    }
+}
+
+/* Pads or cuts the text to exactly the width */
+fn fit(text: &str, width: usize) -> String {
+    let text: String = text.chars().take(width).collect();
+    format!("{text:<width$}")
+}
+
+fn inverse(text: &str) -> String {
+    format!("\x1b[7m{text}\x1b[0m")
+}
+
+/* A titled pane of exactly the height, with lines that already fit the width passed through */
+fn pane_lines(title: &str, lines: Vec<String>, width: usize, height: usize) -> Vec<String> {
+    let mut result = vec![fit(&format!("-- {title} {}", "-".repeat(width)), width)];
+    result.extend(lines.into_iter().take(height - 1));
+    result.resize(height, " ".repeat(width));
+    result
+}
+
+fn pane(title: &str, lines: Vec<String>, width: usize, height: usize) -> Vec<String> {
+    pane_lines(title, lines.iter().map(|line| fit(line, width)).collect(), width, height)
+}
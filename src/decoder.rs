@@ -0,0 +1,150 @@
+use std::fmt;
+use std::fmt::Formatter;
+use crate::cpu::{CPU, Memory};
+use crate::instruction::{AddressingMode, Instruction, OPCODES};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    Byte(u8),
+    Word(u16),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    pub address: u16,
+    pub opcode: u8,
+    pub instruction: Instruction,
+    pub operand: Operand,
+    pub length: u8,
+}
+
+/* Decodes the instruction at the given address using only side-effect-free memory accesses */
+pub fn decode(memory: &dyn Memory, address: u16) -> Option<DecodedInstruction> {
+    let opcode = memory.get(address);
+    let instruction = OPCODES[opcode as usize]?;
+
+    let operand = match instruction.mode().operand_length() {
+        1 => Operand::Byte(memory.get(address.wrapping_add(1))),
+        2 => {
+            let low = memory.get(address.wrapping_add(1)) as u16;
+            let high = memory.get(address.wrapping_add(2)) as u16;
+            Operand::Word(low | (high << 8))
+        }
+        _ => Operand::None,
+    };
+
+    Some(DecodedInstruction {
+        address,
+        opcode,
+        instruction,
+        operand,
+        length: 1 + instruction.mode().operand_length(),
+    })
+}
+
+/* The address after the instruction, treating illegal opcodes as single bytes */
+pub fn next_address(memory: &dyn Memory, address: u16) -> u16 {
+    match decode(memory, address) {
+        Some(instruction) => instruction.next_address(),
+        None => address.wrapping_add(1),
+    }
+}
+
+/* Guesses the instruction before the address, preferring the longest one that ends right at it */
+pub fn previous_address(memory: &dyn Memory, address: u16) -> u16 {
+    (1..=3).rev()
+        .map(|length| address.wrapping_sub(length))
+        .find(|candidate| next_address(memory, *candidate) == address)
+        .unwrap_or(address.wrapping_sub(1))
+}
+
+impl DecodedInstruction {
+    pub fn mode(&self) -> AddressingMode {
+        self.instruction.mode()
+    }
+
+    /* The address of the instruction following this one */
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.length as u16)
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.opcode];
+        match self.operand {
+            Operand::None => {}
+            Operand::Byte(value) => bytes.push(value),
+            Operand::Word(value) => bytes.extend_from_slice(&value.to_le_bytes()),
+        }
+        bytes
+    }
+
+    /*
+     * Resolves the address the instruction would access given the current state of the
+     * cpu, without consuming any cycles. Returns None for modes that don't access memory.
+     */
+    pub fn effective_address(&self, cpu: &CPU) -> Option<u16> {
+        let memory: &dyn Memory = &*cpu.memory;
+        match (self.mode(), self.operand) {
+            (AddressingMode::Absolute, Operand::Word(address)) => Some(address),
+            (AddressingMode::AbsoluteXIndexed, Operand::Word(address)) => {
+                Some(address.wrapping_add(cpu.x as u16))
+            }
+            (AddressingMode::AbsoluteYIndexed, Operand::Word(address)) => {
+                Some(address.wrapping_add(cpu.y as u16))
+            }
+            (AddressingMode::Indirect, Operand::Word(pointer)) => {
+                /* The 6502 doesn't carry into the high byte when the pointer sits on a page boundary */
+                let high_pointer = (pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF);
+                Some(read_pointer(memory, pointer, high_pointer))
+            }
+            (AddressingMode::XIndexedIndirect, Operand::Byte(zeropage)) => {
+                let pointer = zeropage.wrapping_add(cpu.x);
+                Some(read_pointer(memory, pointer as u16, pointer.wrapping_add(1) as u16))
+            }
+            (AddressingMode::IndirectYIndexed, Operand::Byte(zeropage)) => {
+                let address = read_pointer(memory, zeropage as u16, zeropage.wrapping_add(1) as u16);
+                Some(address.wrapping_add(cpu.y as u16))
+            }
+            (AddressingMode::Relative, Operand::Byte(offset)) => {
+                Some(self.next_address().wrapping_add(offset as i8 as u16))
+            }
+            (AddressingMode::Zeropage, Operand::Byte(zeropage)) => Some(zeropage as u16),
+            (AddressingMode::ZeropageXIndexed, Operand::Byte(zeropage)) => {
+                Some(zeropage.wrapping_add(cpu.x) as u16)
+            }
+            (AddressingMode::ZeropageYIndexed, Operand::Byte(zeropage)) => {
+                Some(zeropage.wrapping_add(cpu.y) as u16)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mnemonic = self.instruction.mnemonic();
+        match (self.mode(), self.operand) {
+            (AddressingMode::Accumulator, _) => write!(f, "{mnemonic} A"),
+            (AddressingMode::Immediate, Operand::Byte(value)) => write!(f, "{mnemonic} #${value:02X}"),
+            (AddressingMode::Zeropage, Operand::Byte(address)) => write!(f, "{mnemonic} ${address:02X}"),
+            (AddressingMode::ZeropageXIndexed, Operand::Byte(address)) => write!(f, "{mnemonic} ${address:02X},X"),
+            (AddressingMode::ZeropageYIndexed, Operand::Byte(address)) => write!(f, "{mnemonic} ${address:02X},Y"),
+            (AddressingMode::XIndexedIndirect, Operand::Byte(address)) => write!(f, "{mnemonic} (${address:02X},X)"),
+            (AddressingMode::IndirectYIndexed, Operand::Byte(address)) => write!(f, "{mnemonic} (${address:02X}),Y"),
+            (AddressingMode::Relative, Operand::Byte(offset)) => {
+                let target = self.next_address().wrapping_add(offset as i8 as u16);
+                write!(f, "{mnemonic} ${target:04X}")
+            }
+            (AddressingMode::Absolute, Operand::Word(address)) => write!(f, "{mnemonic} ${address:04X}"),
+            (AddressingMode::AbsoluteXIndexed, Operand::Word(address)) => write!(f, "{mnemonic} ${address:04X},X"),
+            (AddressingMode::AbsoluteYIndexed, Operand::Word(address)) => write!(f, "{mnemonic} ${address:04X},Y"),
+            (AddressingMode::Indirect, Operand::Word(address)) => write!(f, "{mnemonic} (${address:04X})"),
+            _ => write!(f, "{mnemonic}"),
+        }
+    }
+}
+
+fn read_pointer(memory: &dyn Memory, low: u16, high: u16) -> u16 {
+    memory.get(low) as u16 | (memory.get(high) as u16) << 8
+}
@@ -0,0 +1,308 @@
+use std::fmt;
+use std::fmt::Formatter;
+use crate::cpu::CPU;
+use crate::symbols::SymbolTable;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Register {
+    Accumulator,
+    X,
+    Y,
+    StackPointer,
+    ProgramCounter,
+    Status,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Flag {
+    Negative,
+    Overflow,
+    Break,
+    Decimal,
+    Interrupt,
+    Zero,
+    Carry,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Not,
+    Complement,
+    Negate,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    BitOr,
+    BitXor,
+    BitAnd,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+/*
+ * An expression over the cpu state, like `A == $10 && [$00FB] > 3 && !C`.
+ * Numbers are decimal, `$` or `0x` prefixed hex or `%` prefixed binary, `[address]` reads
+ * a byte from memory and identifiers which aren't registers or flags are looked up as symbols.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expression {
+    Number(i64),
+    Register(Register),
+    Flag(Flag),
+    Memory(Box<Expression>),
+    Unary(UnaryOperator, Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl Expression {
+    pub fn parse(text: &str, symbols: Option<&SymbolTable>) -> Result<Expression, ParseError> {
+        let mut parser = Parser { text, position: 0, symbols };
+        let expression = parser.parse_binary(0)?;
+        parser.skip_whitespace();
+        if parser.position != text.len() {
+            return Err(parser.error("Unexpected input"));
+        }
+        Ok(expression)
+    }
+
+    pub fn evaluate(&self, cpu: &CPU) -> i64 {
+        match self {
+            Expression::Number(value) => *value,
+            Expression::Register(register) => match register {
+                Register::Accumulator => cpu.accumulator as i64,
+                Register::X => cpu.x as i64,
+                Register::Y => cpu.y as i64,
+                Register::StackPointer => cpu.stack_pointer as i64,
+                Register::ProgramCounter => cpu.program_counter as i64,
+                Register::Status => cpu.get_processor_status() as i64,
+            },
+            Expression::Flag(flag) => match flag {
+                Flag::Negative => cpu.negative as i64,
+                Flag::Overflow => cpu.overflow as i64,
+                Flag::Break => cpu._break as i64,
+                Flag::Decimal => cpu.decimal as i64,
+                Flag::Interrupt => cpu.interrupt as i64,
+                Flag::Zero => cpu.zero as i64,
+                Flag::Carry => cpu.carry as i64,
+            },
+            Expression::Memory(address) => cpu.memory.get(address.evaluate(cpu) as u16) as i64,
+            Expression::Unary(operator, operand) => {
+                let value = operand.evaluate(cpu);
+                match operator {
+                    UnaryOperator::Not => (value == 0) as i64,
+                    UnaryOperator::Complement => !value,
+                    UnaryOperator::Negate => value.wrapping_neg(),
+                }
+            }
+            Expression::Binary(BinaryOperator::Or, left, right) => {
+                (left.evaluate(cpu) != 0 || right.evaluate(cpu) != 0) as i64
+            }
+            Expression::Binary(BinaryOperator::And, left, right) => {
+                (left.evaluate(cpu) != 0 && right.evaluate(cpu) != 0) as i64
+            }
+            Expression::Binary(operator, left, right) => {
+                let left = left.evaluate(cpu);
+                let right = right.evaluate(cpu);
+                match operator {
+                    BinaryOperator::Equal => (left == right) as i64,
+                    BinaryOperator::NotEqual => (left != right) as i64,
+                    BinaryOperator::Less => (left < right) as i64,
+                    BinaryOperator::LessEqual => (left <= right) as i64,
+                    BinaryOperator::Greater => (left > right) as i64,
+                    BinaryOperator::GreaterEqual => (left >= right) as i64,
+                    BinaryOperator::BitOr => left | right,
+                    BinaryOperator::BitXor => left ^ right,
+                    BinaryOperator::BitAnd => left & right,
+                    BinaryOperator::ShiftLeft => left.wrapping_shl(right as u32),
+                    BinaryOperator::ShiftRight => left.wrapping_shr(right as u32),
+                    BinaryOperator::Add => left.wrapping_add(right),
+                    BinaryOperator::Subtract => left.wrapping_sub(right),
+                    BinaryOperator::Multiply => left.wrapping_mul(right),
+                    BinaryOperator::Divide => left.checked_div(right).unwrap_or(0),
+                    BinaryOperator::Remainder => left.checked_rem(right).unwrap_or(0),
+                    BinaryOperator::Or | BinaryOperator::And => unreachable!(),
+                }
+            }
+        }
+    }
+
+    pub fn is_true(&self, cpu: &CPU) -> bool {
+        self.evaluate(cpu) != 0
+    }
+}
+
+/* Binary operators from the lowest to the highest precedence */
+static PRECEDENCE: [&[(&str, BinaryOperator)]; 10] = [
+    &[("||", BinaryOperator::Or)],
+    &[("&&", BinaryOperator::And)],
+    &[("|", BinaryOperator::BitOr)],
+    &[("^", BinaryOperator::BitXor)],
+    &[("&", BinaryOperator::BitAnd)],
+    &[("==", BinaryOperator::Equal), ("!=", BinaryOperator::NotEqual)],
+    &[("<=", BinaryOperator::LessEqual), (">=", BinaryOperator::GreaterEqual),
+        ("<", BinaryOperator::Less), (">", BinaryOperator::Greater)],
+    &[("<<", BinaryOperator::ShiftLeft), (">>", BinaryOperator::ShiftRight)],
+    &[("+", BinaryOperator::Add), ("-", BinaryOperator::Subtract)],
+    &[("*", BinaryOperator::Multiply), ("/", BinaryOperator::Divide), ("%", BinaryOperator::Remainder)],
+];
+
+struct Parser<'t> {
+    text: &'t str,
+    position: usize,
+    symbols: Option<&'t SymbolTable>,
+}
+
+impl<'t> Parser<'t> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError { position: self.position, message: message.to_string() }
+    }
+
+    fn rest(&self) -> &'t str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expression, ParseError> {
+        if level == PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+
+        let mut left = self.parse_binary(level + 1)?;
+        'operators: loop {
+            self.skip_whitespace();
+            for (token, operator) in PRECEDENCE[level] {
+                /* Don't mistake `||`, `&&`, `<<` and `>>` for their single character counterparts */
+                let doubled = token.len() == 1 && self.rest().starts_with(&token.repeat(2));
+                if !doubled && self.eat(token) {
+                    let right = self.parse_binary(level + 1)?;
+                    left = Expression::Binary(*operator, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, ParseError> {
+        let operator = if self.eat("!") {
+            UnaryOperator::Not
+        } else if self.eat("~") {
+            UnaryOperator::Complement
+        } else if self.eat("-") {
+            UnaryOperator::Negate
+        } else {
+            return self.parse_primary();
+        };
+        Ok(Expression::Unary(operator, Box::new(self.parse_unary()?)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ParseError> {
+        if self.eat("(") {
+            let expression = self.parse_binary(0)?;
+            if !self.eat(")") {
+                return Err(self.error("Expected ')'"));
+            }
+            return Ok(expression);
+        }
+        if self.eat("[") {
+            let address = self.parse_binary(0)?;
+            if !self.eat("]") {
+                return Err(self.error("Expected ']'"));
+            }
+            return Ok(Expression::Memory(Box::new(address)));
+        }
+
+        self.skip_whitespace();
+        let start = self.position;
+        let (radix, prefix) = if self.rest().starts_with('$') {
+            (16, 1)
+        } else if self.rest().starts_with("0x") {
+            (16, 2)
+        } else if self.rest().starts_with('%') {
+            (2, 1)
+        } else {
+            (10, 0)
+        };
+
+        let word = self.rest()[prefix..]
+            .split(|character: char| !(character.is_ascii_alphanumeric() || character == '_' || character == '.'))
+            .next()
+            .unwrap_or("");
+        if word.is_empty() {
+            return Err(self.error("Expected a value"));
+        }
+
+        let is_number = word.starts_with(|character: char| character.is_ascii_digit());
+        if prefix != 0 || is_number {
+            let value = i64::from_str_radix(word, radix)
+                .map_err(|_| ParseError { position: start, message: format!("Invalid number '{word}'") })?;
+            self.position += prefix + word.len();
+            return Ok(Expression::Number(value));
+        }
+
+        let expression = match word.to_ascii_uppercase().as_str() {
+            "A" => Expression::Register(Register::Accumulator),
+            "X" => Expression::Register(Register::X),
+            "Y" => Expression::Register(Register::Y),
+            "SP" | "S" => Expression::Register(Register::StackPointer),
+            "PC" => Expression::Register(Register::ProgramCounter),
+            "P" => Expression::Register(Register::Status),
+            "N" => Expression::Flag(Flag::Negative),
+            "V" => Expression::Flag(Flag::Overflow),
+            "B" => Expression::Flag(Flag::Break),
+            "D" => Expression::Flag(Flag::Decimal),
+            "I" => Expression::Flag(Flag::Interrupt),
+            "Z" => Expression::Flag(Flag::Zero),
+            "C" => Expression::Flag(Flag::Carry),
+            /* VICE writes labels with a leading dot */
+            _ => match self.symbols.and_then(|symbols| {
+                symbols.address_of(word).or_else(|| symbols.address_of(word.strip_prefix('.')?))
+            }) {
+                Some(address) => Expression::Number(address as i64),
+                None => return Err(self.error(&format!("Unknown symbol '{word}'"))),
+            },
+        };
+        self.position += word.len();
+        Ok(expression)
+    }
+}
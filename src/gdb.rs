@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use crate::breakpoint::{BreakpointKind, StopReason};
+use crate::cpu::CPU;
+use crate::observer::{Access, AccessKind};
+
+/* The cycles run between checks for an interrupt from the client */
+const CHUNK: isize = 100_000;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.m6502.core">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="x" bitsize="8" type="uint8"/>
+    <reg name="y" bitsize="8" type="uint8"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="p" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+pub fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, |sum, byte| sum.wrapping_add(byte))
+}
+
+/* Frames the data as `$data#checksum`, escaping the characters the protocol reserves */
+pub fn encode(data: &str) -> String {
+    let mut escaped = String::with_capacity(data.len());
+    for character in data.chars() {
+        match character {
+            '$' | '#' | '}' | '*' => {
+                escaped.push('}');
+                escaped.push((character as u8 ^ 0x20) as char);
+            }
+            _ => escaped.push(character),
+        }
+    }
+    format!("${}#{:02x}", escaped, checksum(&escaped))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_number(text: &str) -> Option<u16> {
+    u16::from_str_radix(text, 16).ok()
+}
+
+/*
+ * A GDB remote serial protocol stub. Registers are exposed in the order A, X, Y, SP, PC, P,
+ * as described by the target.xml it serves, and memory goes through the side-effect-free
+ * accessors so that inspecting memory mapped devices doesn't disturb them.
+ */
+#[derive(Debug, Default)]
+pub struct GdbStub {
+    no_ack: bool,
+    closed: bool,
+    /* The cpu breakpoints inserted for each Z packet type and address range */
+    breakpoints: HashMap<(u8, u16, u16), usize>,
+    /* Tracepoint messages logged while running, waiting to be sent as console output */
+    output: Vec<String>,
+}
+
+impl GdbStub {
+    pub fn new() -> GdbStub {
+        GdbStub::default()
+    }
+
+    /* The `O` packets for the console output that goes out before the next reply */
+    pub fn take_output(&mut self) -> Vec<String> {
+        self.output.drain(..)
+            .map(|message| format!("O{}", hex(format!("{message}\n").as_bytes())))
+            .collect()
+    }
+
+    /* Whether the client detached or killed the session */
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /*
+     * Handles the packet data without the framing and returns the reply, if any.
+     * Continuing polls interrupted between chunks of cycles to notice a Ctrl-C.
+     */
+    pub fn handle_packet(&mut self, cpu: &mut CPU, packet: &str, interrupted: &mut dyn FnMut() -> bool) -> Option<String> {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => format!("S{SIGTRAP:02x}"),
+            Some(b'g') => self.read_registers(cpu),
+            Some(b'G') => self.write_registers(cpu, &packet[1..]),
+            Some(b'p') => self.read_register(cpu, &packet[1..]),
+            Some(b'P') => self.write_register(cpu, &packet[1..]),
+            Some(b'm') => self.read_memory(cpu, &packet[1..]),
+            Some(b'M') => self.write_memory(cpu, &packet[1..]),
+            Some(b'c') => self.resume(cpu, &packet[1..], false, interrupted),
+            Some(b's') => self.resume(cpu, &packet[1..], true, interrupted),
+            Some(b'b') => self.reverse(cpu, &packet[1..]),
+            Some(b'Z') => self.insert_breakpoint(cpu, &packet[1..]),
+            Some(b'z') => self.remove_breakpoint(cpu, &packet[1..]),
+            Some(b'H') => "OK".to_string(),
+            Some(b'D') => {
+                self.detach(cpu);
+                "OK".to_string()
+            }
+            Some(b'k') => {
+                self.detach(cpu);
+                return None;
+            }
+            _ => self.query(packet),
+        };
+        Some(reply)
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+;ReverseStep+;ReverseContinue+".to_string();
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, length)) = range.split_once(',') else {
+                return "E01".to_string();
+            };
+            let offset = usize::from_str_radix(offset, 16).unwrap_or(usize::MAX);
+            let length = usize::from_str_radix(length, 16).unwrap_or(0);
+            return match TARGET_XML.get(offset..) {
+                Some(rest) if rest.len() > length => format!("m{}", &rest[..length]),
+                Some(rest) => format!("l{rest}"),
+                None => "E01".to_string(),
+            };
+        }
+        match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn registers(&self, cpu: &CPU) -> [u8; 7] {
+        let [low, high] = cpu.program_counter.to_le_bytes();
+        [cpu.accumulator, cpu.x, cpu.y, cpu.stack_pointer, low, high, cpu.get_processor_status()]
+    }
+
+    fn read_registers(&self, cpu: &CPU) -> String {
+        hex(&self.registers(cpu))
+    }
+
+    fn write_registers(&self, cpu: &mut CPU, data: &str) -> String {
+        match parse_hex(data).as_deref() {
+            Some(&[accumulator, x, y, stack_pointer, low, high, status]) => {
+                cpu.accumulator = accumulator;
+                cpu.x = x;
+                cpu.y = y;
+                cpu.stack_pointer = stack_pointer;
+                cpu.program_counter = u16::from_le_bytes([low, high]);
+                cpu.set_processor_status(status);
+                "OK".to_string()
+            }
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn read_register(&self, cpu: &CPU, data: &str) -> String {
+        let registers = self.registers(cpu);
+        match parse_number(data) {
+            Some(number @ 0..=3) => hex(&registers[number as usize..=number as usize]),
+            Some(4) => hex(&registers[4..6]),
+            Some(5) => hex(&registers[6..]),
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn write_register(&self, cpu: &mut CPU, data: &str) -> String {
+        let Some((number, value)) = data.split_once('=') else {
+            return "E01".to_string();
+        };
+        match (parse_number(number), parse_hex(value).as_deref()) {
+            (Some(0), Some(&[value])) => cpu.accumulator = value,
+            (Some(1), Some(&[value])) => cpu.x = value,
+            (Some(2), Some(&[value])) => cpu.y = value,
+            (Some(3), Some(&[value])) => cpu.stack_pointer = value,
+            (Some(4), Some(&[low, high])) => cpu.program_counter = u16::from_le_bytes([low, high]),
+            (Some(5), Some(&[value])) => cpu.set_processor_status(value),
+            _ => return "E01".to_string(),
+        }
+        "OK".to_string()
+    }
+
+    fn parse_range(&self, data: &str) -> Option<(u16, usize)> {
+        let (address, length) = data.split_once(',')?;
+        Some((parse_number(address)?, usize::from_str_radix(length, 16).ok()?))
+    }
+
+    fn read_memory(&self, cpu: &CPU, data: &str) -> String {
+        match self.parse_range(data) {
+            Some((address, length)) => {
+                let bytes: Vec<u8> = (0..length.min(0x10000))
+                    .map(|offset| cpu.memory.get(address.wrapping_add(offset as u16)))
+                    .collect();
+                hex(&bytes)
+            }
+            None => "E01".to_string(),
+        }
+    }
+
+    fn write_memory(&self, cpu: &mut CPU, data: &str) -> String {
+        let Some((range, bytes)) = data.split_once(':') else {
+            return "E01".to_string();
+        };
+        match (self.parse_range(range), parse_hex(bytes)) {
+            (Some((address, length)), Some(bytes)) if bytes.len() == length => {
+                for (offset, byte) in bytes.iter().enumerate() {
+                    cpu.edit(address.wrapping_add(offset as u16), *byte);
+                }
+                "OK".to_string()
+            }
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn resume(&mut self, cpu: &mut CPU, address: &str, step: bool, interrupted: &mut dyn FnMut() -> bool) -> String {
+        if let Some(address) = parse_number(address) {
+            cpu.program_counter = address;
+        }
+
+        /* Only the instruction the client resumes from skips its breakpoints, not the ones where chunks end */
+        let mut resuming = true;
+        loop {
+            let mut cycles = CHUNK;
+            let reason = match step {
+                true => cpu.step(&mut cycles),
+                false => cpu.run_from(&mut cycles, resuming, |_| false),
+            };
+            resuming = false;
+            self.output.extend(cpu.breakpoints.take_messages());
+            match reason {
+                Some(StopReason::Watchpoint { id, access }) => return self.watchpoint_reply(id, &access),
+                Some(StopReason::IllegalOpcode { .. }) => return format!("S{SIGILL:02x}"),
+                Some(_) => return format!("S{SIGTRAP:02x}"),
+                None if step => return format!("S{SIGTRAP:02x}"),
+                None if cycles <= 0 && interrupted() => return format!("S{SIGINT:02x}"),
+                None => {}
+            }
+        }
+    }
+
+    fn watchpoint_reply(&self, id: usize, access: &Access) -> String {
+        let access_watchpoint = self.breakpoints.iter().any(|(&(kind, _, _), &other)| kind == 4 && other == id);
+        let kind = match access.kind {
+            _ if access_watchpoint => "awatch",
+            AccessKind::Write => "watch",
+            _ => "rwatch",
+        };
+        format!("T{SIGTRAP:02x}{kind}:{:04x};", access.address)
+    }
+
+    /* Handles `bs` and `bc`, replaying the history backwards */
+    fn reverse(&mut self, cpu: &mut CPU, packet: &str) -> String {
+        let stopped = match packet {
+            "s" => cpu.step_back(),
+            "c" => match cpu.reverse_continue() {
+                Some(StopReason::Watchpoint { id, access }) => return self.watchpoint_reply(id, &access),
+                Some(_) => true,
+                None => false,
+            },
+            _ => return String::new(),
+        };
+        match stopped {
+            true => format!("S{SIGTRAP:02x}"),
+            false => format!("T{SIGTRAP:02x}replaylog:begin;"),
+        }
+    }
+
+    /* Handles `type,address,kind`, where kind is the length for watchpoints */
+    fn parse_breakpoint(&self, data: &str) -> Option<(u8, u16, u16)> {
+        let mut parts = data.split(',');
+        let kind: u8 = parts.next()?.parse().ok()?;
+        let address = parse_number(parts.next()?)?;
+        let length = parse_number(parts.next()?.split(';').next()?)?.max(1);
+        Some((kind, address, address.saturating_add(length - 1)))
+    }
+
+    fn insert_breakpoint(&mut self, cpu: &mut CPU, data: &str) -> String {
+        let Some((kind, start, end)) = self.parse_breakpoint(data) else {
+            return "E01".to_string();
+        };
+        let breakpoint = match kind {
+            0 | 1 => BreakpointKind::Execute(start),
+            2 => BreakpointKind::Write(start, end),
+            3 => BreakpointKind::Read(start, end),
+            4 => BreakpointKind::Access(start, end),
+            _ => return String::new(),
+        };
+        self.breakpoints.entry((kind, start, end)).or_insert_with(|| cpu.breakpoints.add(breakpoint));
+        "OK".to_string()
+    }
+
+    fn remove_breakpoint(&mut self, cpu: &mut CPU, data: &str) -> String {
+        let Some((kind, start, end)) = self.parse_breakpoint(data) else {
+            return "E01".to_string();
+        };
+        if let Some(id) = self.breakpoints.remove(&(kind, start, end)) {
+            cpu.breakpoints.remove(id);
+        }
+        "OK".to_string()
+    }
+
+    fn detach(&mut self, cpu: &mut CPU) {
+        for (_, id) in self.breakpoints.drain() {
+            cpu.breakpoints.remove(id);
+        }
+        self.closed = true;
+    }
+
+    /* Serves a client until it detaches or disconnects */
+    pub fn serve(&mut self, cpu: &mut CPU, stream: &mut TcpStream) -> io::Result<()> {
+        self.no_ack = false;
+        self.closed = false;
+        let mut reader = stream.try_clone()?;
+        let mut interrupted = || -> bool {
+            let mut byte = [0];
+            let _ = reader.set_nonblocking(true);
+            let interrupt = matches!(reader.read(&mut byte), Ok(1) if byte[0] == 0x03);
+            let _ = reader.set_nonblocking(false);
+            interrupt
+        };
+
+        while !self.closed {
+            let Some(packet) = self.read_packet(stream)? else {
+                return Ok(());
+            };
+            if let Some(reply) = self.handle_packet(cpu, &packet, &mut interrupted) {
+                for output in self.take_output() {
+                    stream.write_all(encode(&output).as_bytes())?;
+                }
+                stream.write_all(encode(&reply).as_bytes())?;
+                stream.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /* Reads the next packet, acknowledging it unless acks are turned off, or None on disconnect */
+    fn read_packet(&self, stream: &mut TcpStream) -> io::Result<Option<String>> {
+        let mut byte = [0];
+        loop {
+            match stream.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {}
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+            if byte[0] != b'$' {
+                /* Acks and stray interrupts while stopped */
+                continue;
+            }
+
+            let mut data = Vec::new();
+            loop {
+                stream.read_exact(&mut byte)?;
+                if byte[0] == b'#' {
+                    break;
+                }
+                data.push(byte[0]);
+            }
+            let mut sum = [0; 2];
+            stream.read_exact(&mut sum)?;
+
+            let valid = std::str::from_utf8(&sum).ok()
+                .and_then(|sum| u8::from_str_radix(sum, 16).ok())
+                == Some(data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)));
+            let data = unescape(&data);
+            if !self.no_ack {
+                stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'}' => result.extend(bytes.next().map(|byte| byte ^ 0x20)),
+            _ => result.push(*byte),
+        }
+    }
+    result
+}
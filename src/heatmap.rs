@@ -0,0 +1,117 @@
+use std::fmt::Write as _;
+use std::io;
+use std::io::Write;
+use crate::observer::{Access, AccessKind, Observer};
+
+const STACK_PAGE: usize = 0x0100;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HeatKind {
+    Read,
+    Write,
+    /* Opcode and operand fetches */
+    Execute,
+}
+
+/* Counts the reads, writes and instruction fetches the cpu makes to every address */
+#[derive(Clone, Debug)]
+pub struct Heatmap {
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+    executes: Vec<u64>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Heatmap {
+        Heatmap::new()
+    }
+}
+
+impl Heatmap {
+    pub fn new() -> Heatmap {
+        Heatmap { reads: vec![0; 0x10000], writes: vec![0; 0x10000], executes: vec![0; 0x10000] }
+    }
+
+    fn counts(&self, kind: HeatKind) -> &[u64] {
+        match kind {
+            HeatKind::Read => &self.reads,
+            HeatKind::Write => &self.writes,
+            HeatKind::Execute => &self.executes,
+        }
+    }
+
+    pub fn count(&self, kind: HeatKind, address: u16) -> u64 {
+        self.counts(kind)[address as usize]
+    }
+
+    /*
+     * Writes a 256x256 binary PPM with one pixel per address, a row per page. Counts are
+     * scaled logarithmically against the busiest address and run from black over red and
+     * yellow to white.
+     */
+    pub fn write_ppm(&self, kind: HeatKind, output: &mut dyn Write) -> io::Result<()> {
+        let counts = self.counts(kind);
+        let maximum = counts.iter().copied().max().unwrap_or(0);
+        let scale = ((maximum + 1) as f64).ln();
+
+        let mut image = b"P6\n256 256\n255\n".to_vec();
+        for count in counts {
+            let heat = match *count {
+                0 => 0.0,
+                count => ((count + 1) as f64).ln() / scale,
+            };
+            let channel = |offset: f64| ((heat * 3.0 - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+            image.extend([channel(0.0), channel(1.0), channel(2.0)]);
+        }
+        output.write_all(&image)
+    }
+
+    /*
+     * A map of the zero page marking bytes read (R), written (W) or both (B), followed by the
+     * busiest zero page address and how deep the stack went.
+     */
+    pub fn summary(&self) -> String {
+        let mut summary = String::from("Zero page: R read, W written, B both\n    0123456789ABCDEF\n");
+        for row in 0..16 {
+            let cells: String = (row * 16..row * 16 + 16).map(|address| {
+                match (self.reads[address] > 0, self.writes[address] > 0) {
+                    (true, true) => 'B',
+                    (true, false) => 'R',
+                    (false, true) => 'W',
+                    (false, false) => '.',
+                }
+            }).collect();
+            writeln!(summary, "{:02X}: {cells}", row * 16).unwrap();
+        }
+
+        let used = (0..0x100).filter(|address| self.reads[*address] + self.writes[*address] > 0).count();
+        write!(summary, "{used} of 256 zero page bytes used").unwrap();
+        let busiest = (0..0x100).max_by_key(|address| (self.reads[*address] + self.writes[*address], usize::MAX - address));
+        match busiest {
+            Some(address) if used > 0 => writeln!(summary, ", busiest ${address:02X} with {} reads and {} writes",
+                                                  self.reads[address], self.writes[address]).unwrap(),
+            _ => summary.push('\n'),
+        }
+
+        let stack = STACK_PAGE..STACK_PAGE + 0x100;
+        let pushes: u64 = self.writes[stack.clone()].iter().sum();
+        let pulls: u64 = self.reads[stack.clone()].iter().sum();
+        match stack.clone().find(|address| self.reads[*address] + self.writes[*address] > 0) {
+            Some(lowest) => writeln!(summary, "Stack: {} bytes deep at most (${lowest:04X}), {pushes} writes and {pulls} reads",
+                                     stack.end - lowest).unwrap(),
+            None => summary.push_str("Stack: unused\n"),
+        }
+        summary
+    }
+}
+
+impl Observer for Heatmap {
+    fn memory_access(&mut self, access: &Access) {
+        let counts = match access.kind {
+            AccessKind::Opcode | AccessKind::Operand => &mut self.executes,
+            AccessKind::Read => &mut self.reads,
+            AccessKind::Write => &mut self.writes,
+        };
+        counts[access.address as usize] += 1;
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+use crate::call_stack::CallStack;
+use crate::cpu::Registers;
+
+/* A byte written to memory together with the value it replaced */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Overwrite {
+    pub address: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+/* Everything needed to undo a single instruction or interrupt */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UndoRecord {
+    /* The registers and cycle count from before */
+    pub registers: Registers,
+    pub total_cycles: u64,
+    pub call_stack: CallStack,
+    /* The writes in the order they happened */
+    pub writes: Vec<Overwrite>,
+}
+
+/*
+ * A bounded ring of undo records, oldest first. The cpu only records while the capacity
+ * is above zero, which it isn't by default.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    records: VecDeque<UndoRecord>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History { records: VecDeque::new(), capacity }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /* Changes the amount of records kept, dropping the oldest ones that don't fit anymore */
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.records.len() > capacity {
+            self.records.pop_front();
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&UndoRecord> {
+        self.records.iter()
+    }
+
+    /* Starts the record of the next instruction, dropping the oldest one when full */
+    pub fn push(&mut self, record: UndoRecord) {
+        if !self.is_recording() {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /* Adds a write to the record of the current instruction */
+    pub fn record_write(&mut self, write: Overwrite) {
+        if let Some(record) = self.records.back_mut() {
+            record.writes.push(write);
+        }
+    }
+
+    /* Takes the newest record */
+    pub fn pop(&mut self) -> Option<UndoRecord> {
+        self.records.pop_back()
+    }
+}
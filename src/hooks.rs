@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use crate::cpu::CPU;
+
+/* What the cpu does after a hook ran */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HookAction {
+    /* Fetches and executes the instruction at the program counter as usual */
+    Continue,
+    /* Returns like an RTS would, which takes the place of the instruction at the address */
+    Return,
+}
+
+pub type Hook<'a> = Box<dyn FnMut(&mut CPU) -> HookAction + 'a>;
+
+/*
+ * High-level emulation hooks, closures the cpu calls right before fetching an opcode at their
+ * address. They get full access to the registers and memory, so they can replace routines with
+ * native implementations or stub out hardware. Changes they make aren't recorded in the history,
+ * observers only learn about the memory they change through CPU::edit.
+ */
+#[derive(Default)]
+pub struct Hooks<'a> {
+    hooks: HashMap<u16, Hook<'a>>,
+}
+
+impl<'a> Hooks<'a> {
+    pub fn new() -> Hooks<'a> {
+        Hooks::default()
+    }
+
+    /* Registers the hook at the address, replacing the one already there */
+    pub fn add(&mut self, address: u16, hook: impl FnMut(&mut CPU) -> HookAction + 'a) {
+        self.hooks.insert(address, Box::new(hook));
+    }
+
+    pub fn remove(&mut self, address: u16) -> bool {
+        self.hooks.remove(&address).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.hooks.clear();
+    }
+
+    pub fn contains(&self, address: u16) -> bool {
+        self.hooks.contains_key(&address)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /* The hooked addresses in ascending order */
+    pub fn addresses(&self) -> Vec<u16> {
+        let mut addresses: Vec<u16> = self.hooks.keys().copied().collect();
+        addresses.sort();
+        addresses
+    }
+
+    /* Runs the hook at the address if there is one */
+    pub fn call(&mut self, address: u16, cpu: &mut CPU) -> Option<HookAction> {
+        let hook = self.hooks.get_mut(&address)?;
+        Some(hook(cpu))
+    }
+}
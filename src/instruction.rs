@@ -1,7 +1,7 @@
 use std::fmt;
 use std::fmt::Formatter;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AddressingMode {
     Accumulator,
     Absolute,
@@ -18,6 +18,21 @@ pub enum AddressingMode {
     ZeropageYIndexed,
 }
 
+impl AddressingMode {
+    /* The amount of operand bytes following the opcode */
+    pub fn operand_length(&self) -> u8 {
+        match self {
+            AddressingMode::Accumulator | AddressingMode::Implied => 0,
+            AddressingMode::Immediate | AddressingMode::XIndexedIndirect
+            | AddressingMode::IndirectYIndexed | AddressingMode::Relative
+            | AddressingMode::Zeropage | AddressingMode::ZeropageXIndexed
+            | AddressingMode::ZeropageYIndexed => 1,
+            AddressingMode::Absolute | AddressingMode::AbsoluteXIndexed
+            | AddressingMode::AbsoluteYIndexed | AddressingMode::Indirect => 2,
+        }
+    }
+}
+
 impl fmt::Display for AddressingMode {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
@@ -38,7 +53,7 @@ impl fmt::Display for AddressingMode {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Instruction {
     AddWithCarry(AddressingMode),
     And(AddressingMode),
@@ -98,6 +113,130 @@ pub enum Instruction {
     TransferYToAccumulator(AddressingMode),
 }
 
+impl Instruction {
+    pub fn mode(&self) -> AddressingMode {
+        match self {
+            Instruction::AddWithCarry(mode) => *mode,
+            Instruction::And(mode) => *mode,
+            Instruction::ArithmeticShiftLeft(mode) => *mode,
+            Instruction::BranchOnCarryClear(mode) => *mode,
+            Instruction::BranchOnCarrySet(mode) => *mode,
+            Instruction::BranchOnEqual(mode) => *mode,
+            Instruction::BitTest(mode) => *mode,
+            Instruction::BranchOnMinus(mode) => *mode,
+            Instruction::BranchOnNotEqual(mode) => *mode,
+            Instruction::BranchOnPlus(mode) => *mode,
+            Instruction::Break(mode) => *mode,
+            Instruction::BranchOnOverflowClear(mode) => *mode,
+            Instruction::BranchOnOverflowSet(mode) => *mode,
+            Instruction::ClearCarry(mode) => *mode,
+            Instruction::ClearDecimal(mode) => *mode,
+            Instruction::ClearInterruptDisable(mode) => *mode,
+            Instruction::ClearOverflow(mode) => *mode,
+            Instruction::Compare(mode) => *mode,
+            Instruction::CompareWithX(mode) => *mode,
+            Instruction::CompareWithY(mode) => *mode,
+            Instruction::Decrement(mode) => *mode,
+            Instruction::DecrementX(mode) => *mode,
+            Instruction::DecrementY(mode) => *mode,
+            Instruction::ExclusiveOr(mode) => *mode,
+            Instruction::Increment(mode) => *mode,
+            Instruction::IncrementX(mode) => *mode,
+            Instruction::IncrementY(mode) => *mode,
+            Instruction::Jump(mode) => *mode,
+            Instruction::JumpSubroutine(mode) => *mode,
+            Instruction::LoadAccumulator(mode) => *mode,
+            Instruction::LoadX(mode) => *mode,
+            Instruction::LoadY(mode) => *mode,
+            Instruction::LogicalShiftRight(mode) => *mode,
+            Instruction::NoOperation(mode) => *mode,
+            Instruction::OrWithAccumulator(mode) => *mode,
+            Instruction::PushAccumulator(mode) => *mode,
+            Instruction::PushProcessorStatus(mode) => *mode,
+            Instruction::PullAccumulator(mode) => *mode,
+            Instruction::PullProcessorStatus(mode) => *mode,
+            Instruction::RotateLeft(mode) => *mode,
+            Instruction::RotateRight(mode) => *mode,
+            Instruction::ReturnFormInterrupt(mode) => *mode,
+            Instruction::ReturnFromSubroutine(mode) => *mode,
+            Instruction::SubtractWithCarry(mode) => *mode,
+            Instruction::SetCarry(mode) => *mode,
+            Instruction::SetDecimal(mode) => *mode,
+            Instruction::SetInterruptDisable(mode) => *mode,
+            Instruction::StoreAccumulator(mode) => *mode,
+            Instruction::StoreX(mode) => *mode,
+            Instruction::StoreY(mode) => *mode,
+            Instruction::TransferAccumulatorToX(mode) => *mode,
+            Instruction::TransferAccumulatorToY(mode) => *mode,
+            Instruction::TransferStackpointerToX(mode) => *mode,
+            Instruction::TransferXToAccumulator(mode) => *mode,
+            Instruction::TransferXToStackpointer(mode) => *mode,
+            Instruction::TransferYToAccumulator(mode) => *mode,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::AddWithCarry(_) => "ADC",
+            Instruction::And(_) => "AND",
+            Instruction::ArithmeticShiftLeft(_) => "ASL",
+            Instruction::BranchOnCarryClear(_) => "BCC",
+            Instruction::BranchOnCarrySet(_) => "BCS",
+            Instruction::BranchOnEqual(_) => "BEQ",
+            Instruction::BitTest(_) => "BIT",
+            Instruction::BranchOnMinus(_) => "BMI",
+            Instruction::BranchOnNotEqual(_) => "BNE",
+            Instruction::BranchOnPlus(_) => "BPL",
+            Instruction::Break(_) => "BRK",
+            Instruction::BranchOnOverflowClear(_) => "BVC",
+            Instruction::BranchOnOverflowSet(_) => "BVS",
+            Instruction::ClearCarry(_) => "CLC",
+            Instruction::ClearDecimal(_) => "CLD",
+            Instruction::ClearInterruptDisable(_) => "CLI",
+            Instruction::ClearOverflow(_) => "CLV",
+            Instruction::Compare(_) => "CMP",
+            Instruction::CompareWithX(_) => "CPX",
+            Instruction::CompareWithY(_) => "CPY",
+            Instruction::Decrement(_) => "DEC",
+            Instruction::DecrementX(_) => "DEX",
+            Instruction::DecrementY(_) => "DEY",
+            Instruction::ExclusiveOr(_) => "EOR",
+            Instruction::Increment(_) => "INC",
+            Instruction::IncrementX(_) => "INX",
+            Instruction::IncrementY(_) => "INY",
+            Instruction::Jump(_) => "JMP",
+            Instruction::JumpSubroutine(_) => "JSR",
+            Instruction::LoadAccumulator(_) => "LDA",
+            Instruction::LoadX(_) => "LDX",
+            Instruction::LoadY(_) => "LDY",
+            Instruction::LogicalShiftRight(_) => "LSR",
+            Instruction::NoOperation(_) => "NOP",
+            Instruction::OrWithAccumulator(_) => "ORA",
+            Instruction::PushAccumulator(_) => "PHA",
+            Instruction::PushProcessorStatus(_) => "PHP",
+            Instruction::PullAccumulator(_) => "PLA",
+            Instruction::PullProcessorStatus(_) => "PLP",
+            Instruction::RotateLeft(_) => "ROL",
+            Instruction::RotateRight(_) => "ROR",
+            Instruction::ReturnFormInterrupt(_) => "RTI",
+            Instruction::ReturnFromSubroutine(_) => "RTS",
+            Instruction::SubtractWithCarry(_) => "SBC",
+            Instruction::SetCarry(_) => "SEC",
+            Instruction::SetDecimal(_) => "SED",
+            Instruction::SetInterruptDisable(_) => "SEI",
+            Instruction::StoreAccumulator(_) => "STA",
+            Instruction::StoreX(_) => "STX",
+            Instruction::StoreY(_) => "STY",
+            Instruction::TransferAccumulatorToX(_) => "TAX",
+            Instruction::TransferAccumulatorToY(_) => "TAY",
+            Instruction::TransferStackpointerToX(_) => "TSX",
+            Instruction::TransferXToAccumulator(_) => "TXA",
+            Instruction::TransferXToStackpointer(_) => "TXS",
+            Instruction::TransferYToAccumulator(_) => "TYA",
+        }
+    }
+}
+
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
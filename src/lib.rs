@@ -1,2 +1,32 @@
 pub mod instruction;
 pub mod cpu;
+pub mod decoder;
+pub mod observer;
+pub mod trace;
+pub mod binary_trace;
+pub mod breakpoint;
+pub mod call_stack;
+pub mod history;
+pub mod hooks;
+pub mod expression;
+pub mod symbols;
+pub mod ram;
+pub mod cow_ram;
+pub mod machine;
+pub mod state;
+pub mod assembler;
+pub mod monitor;
+pub mod console;
+pub mod cheats;
+pub mod runner;
+pub mod debugger;
+pub mod gdb;
+pub mod json;
+pub mod source_map;
+pub mod debug_info;
+pub mod dap;
+pub mod coverage;
+pub mod analysis;
+pub mod profiler;
+pub mod heatmap;
+pub mod search;
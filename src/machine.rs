@@ -0,0 +1,67 @@
+use std::mem;
+use crate::breakpoint::Breakpoints;
+use crate::call_stack::CallStack;
+use crate::cpu::{CPU, Memory, Registers};
+use crate::history::History;
+
+/*
+ * A machine owning its memory, so it can be forked and kept around while the cpu it was
+ * forked from runs on. Forking is as cheap as copying the memory, which for CowRam only
+ * copies page pointers. Observers and hooks belong to a cpu and aren't part of a machine.
+ * Devices decide what a fork gets through Memory::fork: a Console leaves its streams out,
+ * so wrap the fork's memory in a Console of its own to give it devices, and CheatMemory
+ * copies its cheat list.
+ */
+pub struct Machine {
+    pub memory: Box<dyn Memory>,
+    pub registers: Registers,
+    pub total_cycles: u64,
+    pub call_stack: CallStack,
+    pub breakpoints: Breakpoints,
+    pub history: History,
+}
+
+impl Machine {
+    /* A machine in the state of a newly created cpu */
+    pub fn new(mut memory: Box<dyn Memory>) -> Machine {
+        let registers = CPU::new(&mut *memory).registers();
+        Machine {
+            memory,
+            registers,
+            total_cycles: 0,
+            call_stack: CallStack::new(),
+            breakpoints: Breakpoints::new(),
+            history: History::default(),
+        }
+    }
+
+    pub fn fork(&self) -> Machine {
+        Machine {
+            memory: self.memory.fork(),
+            registers: self.registers,
+            total_cycles: self.total_cycles,
+            call_stack: self.call_stack.clone(),
+            breakpoints: self.breakpoints.clone(),
+            history: self.history.clone(),
+        }
+    }
+
+    /* Runs the function on a cpu in the state of the machine and keeps the state it leaves behind */
+    pub fn with_cpu<R>(&mut self, function: impl FnOnce(&mut CPU) -> R) -> R {
+        let mut cpu = CPU::new(&mut *self.memory);
+        cpu.set_registers(self.registers);
+        cpu.total_cycles = self.total_cycles;
+        cpu.call_stack = mem::take(&mut self.call_stack);
+        cpu.breakpoints = mem::take(&mut self.breakpoints);
+        cpu.history = mem::take(&mut self.history);
+
+        let result = function(&mut cpu);
+
+        self.registers = cpu.registers();
+        self.total_cycles = cpu.total_cycles;
+        self.call_stack = mem::take(&mut cpu.call_stack);
+        self.breakpoints = mem::take(&mut cpu.breakpoints);
+        self.history = mem::take(&mut cpu.history);
+        result
+    }
+}
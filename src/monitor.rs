@@ -0,0 +1,619 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use crate::assembler::assemble;
+use crate::breakpoint::{Breakpoint, BreakpointKind, StopReason};
+use crate::call_stack::FrameKind;
+use crate::cpu::CPU;
+use crate::debug_info::DebugInfo;
+use crate::decoder::decode;
+use crate::expression::Expression;
+use crate::observer::AccessKind;
+use crate::search::{Filter, MemorySearch, Width};
+use crate::source_map::{step_line, SourceMap};
+use crate::state::MachineState;
+use crate::symbols::SymbolTable;
+
+const HELP: &str = "\
+r [REG=value ...]            Show or change the registers (A, X, Y, SP, PC, P)
+m [start [end]]              Hex dump memory
+> address byte [byte ...]    Write bytes to memory
+d [start [end]]              Disassemble
+a address instruction        Assemble an instruction in place
+l file address               Load a binary image
+s file start end             Save memory to a binary image
+savestate file               Save the registers and memory
+loadstate file               Restore the registers and memory
+debuginfo file               Load symbols and source lines from an ld65 debug file
+ll file                      Load labels from a VICE label file or a name = address list
+sl file                      Save the labels as a VICE label file
+shl [address]                Show all labels or the label nearest to an address
+break [address [if cond]]    Set an execution breakpoint or list all breakpoints
+watch r|w|rw start [end]     Set a watchpoint on reads, writes or both
+delete|enable|disable id     Manage breakpoints
+z [count]                    Step into
+n [count]                    Step over
+zl                           Step into until the next source line
+nl                           Step over until the next source line
+ret                          Step out of the current subroutine
+g [address]                  Run until a breakpoint is hit
+history [size]               Show or set how many instructions can be stepped back
+bz [count]                   Step back
+bg [address]                 Run back to the address or to the previous write watchpoint hit
+bt                           Show the call stack
+search b|w [value]           Search bytes or little-endian words, optionally equal to the value
+narrow filter                Keep the matches that are a value, changed, unchanged, inc [n] or dec [n]
+q                            Quit
+Values are hex unless prefixed with % for binary, + for decimal, or given as symbols.";
+
+const HISTORY_START: &str = "Reached the start of the history";
+
+/* A machine language monitor in the spirit of the VICE monitor, driving a cpu command by command */
+pub struct Monitor {
+    pub symbols: SymbolTable,
+    /* Annotates the disassembly with source lines and enables stepping by line */
+    pub source_map: Option<Box<dyn SourceMap>>,
+    /* The maximum amount of cycles a single run command may take */
+    pub cycle_limit: isize,
+    dump_address: u16,
+    disassemble_address: Option<u16>,
+    search: Option<MemorySearch>,
+}
+
+impl Default for Monitor {
+    fn default() -> Monitor {
+        Monitor::new()
+    }
+}
+
+impl Monitor {
+    pub fn new() -> Monitor {
+        Monitor {
+            symbols: SymbolTable::new(),
+            source_map: None,
+            cycle_limit: 10_000_000,
+            dump_address: 0,
+            disassemble_address: None,
+            search: None,
+        }
+    }
+
+    pub fn execute(&mut self, cpu: &mut CPU, line: &str) -> Result<String, String> {
+        let line = line.trim();
+        let (command, arguments) = match line.split_once(char::is_whitespace) {
+            Some((command, arguments)) => (command, arguments.trim()),
+            None => (line, ""),
+        };
+        let words: Vec<&str> = arguments.split_whitespace().collect();
+
+        match command {
+            "" => Ok(String::new()),
+            "help" | "?" => Ok(HELP.to_string()),
+            "r" => self.registers(cpu, &words),
+            "m" => self.dump(cpu, &words),
+            ">" => self.write(cpu, &words),
+            "d" => self.disassemble(cpu, &words),
+            "a" => self.assemble(cpu, arguments),
+            "l" => self.load(cpu, &words),
+            "s" => self.save(cpu, &words),
+            "savestate" => self.save_state(cpu, &words),
+            "loadstate" => self.load_state(cpu, &words),
+            "debuginfo" => self.load_debug_info(&words),
+            "ll" => self.load_labels(&words),
+            "sl" => self.save_labels(&words),
+            "shl" => self.show_labels(&words),
+            "break" => self.set_breakpoint(cpu, arguments),
+            "watch" => self.set_watchpoint(cpu, &words),
+            "delete" | "enable" | "disable" => self.manage_breakpoint(cpu, command, &words),
+            "z" => self.step(cpu, &words, false),
+            "n" => self.step(cpu, &words, true),
+            "zl" => self.step_line(cpu, false),
+            "nl" => self.step_line(cpu, true),
+            "ret" => self.step_out(cpu),
+            "g" => self.go(cpu, &words),
+            "history" => self.history(cpu, &words),
+            "bz" => self.step_back(cpu, &words),
+            "bg" => self.go_back(cpu, &words),
+            "bt" => Ok(self.backtrace(cpu)),
+            "search" => self.start_search(cpu, &words),
+            "narrow" => self.narrow_search(cpu, &words),
+            _ => Err(format!("Unknown command '{command}', try 'help'")),
+        }
+    }
+
+    pub fn parse_value(&self, text: &str) -> Result<u16, String> {
+        let value = if let Some(binary) = text.strip_prefix('%') {
+            u16::from_str_radix(binary, 2).ok()
+        } else if let Some(decimal) = text.strip_prefix('+') {
+            decimal.parse().ok()
+        } else if let Some(hex) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+            u16::from_str_radix(hex, 16).ok()
+        } else {
+            /* VICE writes labels with a leading dot */
+            u16::from_str_radix(text, 16).ok()
+                .or_else(|| self.symbols.address_of(text))
+                .or_else(|| self.symbols.address_of(text.strip_prefix('.')?))
+        };
+        value.ok_or(format!("Invalid value '{text}'"))
+    }
+
+    fn argument(&self, words: &[&str], index: usize) -> Result<Option<u16>, String> {
+        words.get(index).map(|word| self.parse_value(word)).transpose()
+    }
+
+    pub fn format_registers(&self, cpu: &CPU) -> String {
+        format!("  PC   A  X  Y  SP NV-BDIZC  CYC\n.{:04X} {:02X} {:02X} {:02X} {:02X} {:08b}  {}",
+                cpu.program_counter, cpu.accumulator, cpu.x, cpu.y, cpu.stack_pointer,
+                cpu.get_processor_status(), cpu.total_cycles)
+    }
+
+    /* Disassembles the instruction at the address, returning the line and the address of the next one */
+    pub fn format_instruction(&self, cpu: &CPU, address: u16) -> (String, u16) {
+        let mut line = String::new();
+        if let Some(name) = self.symbols.name_of(address) {
+            line.push_str(&format!("{name}:\n"));
+        }
+
+        match decode(&*cpu.memory, address) {
+            Some(instruction) => {
+                let bytes: Vec<String> = instruction.bytes().iter().map(|byte| format!("{byte:02X}")).collect();
+                let text = format!(".{:04X}  {:<8}  {}", address, bytes.join(" "), instruction);
+                match self.source_map.as_ref().and_then(|map| map.location(address)) {
+                    Some(location) => line.push_str(&format!("{text:<32}; {}:{}", location.file, location.line)),
+                    None => line.push_str(&text),
+                }
+                (line, instruction.next_address())
+            }
+            None => {
+                line.push_str(&format!(".{:04X}  {:02X}        ???", address, cpu.memory.get(address)));
+                (line, address.wrapping_add(1))
+            }
+        }
+    }
+
+    fn status(&self, cpu: &CPU) -> String {
+        format!("{}\n{}", self.format_registers(cpu), self.format_instruction(cpu, cpu.program_counter).0)
+    }
+
+    fn registers(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        for word in words {
+            let (register, value) = word.split_once('=')
+                .ok_or(format!("Expected REG=value, got '{word}'"))?;
+            let value = self.parse_value(value)?;
+            match register.to_ascii_uppercase().as_str() {
+                "PC" => cpu.program_counter = value,
+                "A" => cpu.accumulator = value as u8,
+                "X" => cpu.x = value as u8,
+                "Y" => cpu.y = value as u8,
+                "SP" => cpu.stack_pointer = value as u8,
+                "P" => cpu.set_processor_status(value as u8),
+                _ => return Err(format!("Unknown register '{register}'")),
+            }
+        }
+        Ok(self.format_registers(cpu))
+    }
+
+    fn dump(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        let start = self.argument(words, 0)?.unwrap_or(self.dump_address);
+        let end = self.argument(words, 1)?.unwrap_or(start.saturating_add(0x7F));
+        if end < start {
+            return Err("The end lies before the start".to_string());
+        }
+
+        let mut lines = Vec::new();
+        for row in (start as u32..=end as u32).step_by(16) {
+            let bytes: Vec<u8> = (row..=(row + 15).min(end as u32))
+                .map(|address| cpu.memory.get(address as u16))
+                .collect();
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            let text: String = bytes.iter()
+                .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+                .collect();
+            lines.push(format!(">{:04X}  {:<47}  {}", row, hex.join(" "), text));
+        }
+        self.dump_address = end.wrapping_add(1);
+        Ok(lines.join("\n"))
+    }
+
+    fn write(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        let address = self.argument(words, 0)?.ok_or("Expected an address")?;
+        if words.len() < 2 {
+            return Err("Expected bytes to write".to_string());
+        }
+        for (offset, word) in words[1..].iter().enumerate() {
+            let value = self.parse_value(word)?;
+            cpu.edit(address.wrapping_add(offset as u16), value as u8);
+        }
+        Ok(String::new())
+    }
+
+    fn disassemble(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        let mut address = self.argument(words, 0)?
+            .or(self.disassemble_address)
+            .unwrap_or(cpu.program_counter);
+        let end = self.argument(words, 1)?;
+
+        let mut lines = Vec::new();
+        loop {
+            let (line, next) = self.format_instruction(cpu, address);
+            lines.push(line);
+            let done = match end {
+                Some(end) => next > end || next < address,
+                None => lines.len() == 16,
+            };
+            address = next;
+            if done {
+                break;
+            }
+        }
+        self.disassemble_address = Some(address);
+        Ok(lines.join("\n"))
+    }
+
+    fn assemble(&mut self, cpu: &mut CPU, arguments: &str) -> Result<String, String> {
+        let (address, instruction) = arguments.split_once(char::is_whitespace)
+            .ok_or("Expected an address and an instruction")?;
+        let address = self.parse_value(address)?;
+
+        let bytes = assemble(instruction, address, Some(&self.symbols))?;
+        for (offset, byte) in bytes.iter().enumerate() {
+            cpu.edit(address.wrapping_add(offset as u16), *byte);
+        }
+        Ok(self.format_instruction(cpu, address).0)
+    }
+
+    fn load(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        let file = words.first().ok_or("Expected a file name")?;
+        let address = self.argument(words, 1)?.ok_or("Expected a load address")?;
+
+        let bytes = fs::read(file).map_err(|error| format!("Couldn't read {file}: {error}"))?;
+        if bytes.len() > 0x10000 - address as usize {
+            return Err(format!("{file} doesn't fit into memory at ${address:04X}"));
+        }
+        for (offset, byte) in bytes.iter().enumerate() {
+            cpu.edit(address + offset as u16, *byte);
+        }
+        Ok(format!("Loaded {} bytes to ${:04X}-${:04X}", bytes.len(), address,
+                   (address as usize + bytes.len()).saturating_sub(1)))
+    }
+
+    fn save(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        let file = words.first().ok_or("Expected a file name")?;
+        let start = self.argument(words, 1)?.ok_or("Expected a start address")?;
+        let end = self.argument(words, 2)?.ok_or("Expected an end address")?;
+        if end < start {
+            return Err("The end lies before the start".to_string());
+        }
+
+        let bytes: Vec<u8> = (start..=end).map(|address| cpu.memory.get(address)).collect();
+        fs::write(file, &bytes).map_err(|error| format!("Couldn't write {file}: {error}"))?;
+        Ok(format!("Saved {} bytes to {file}", bytes.len()))
+    }
+
+    fn save_state(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        let file = words.first().ok_or("Expected a file name")?;
+        let output = File::create(file).map_err(|error| format!("Couldn't create {file}: {error}"))?;
+        MachineState::capture(cpu).write_to(&mut BufWriter::new(output))
+            .map_err(|error| format!("Couldn't write {file}: {error}"))?;
+        Ok(format!("Saved the state to {file}"))
+    }
+
+    fn load_state(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        let file = words.first().ok_or("Expected a file name")?;
+        let input = File::open(file).map_err(|error| format!("Couldn't open {file}: {error}"))?;
+        let state = MachineState::read_from(&mut BufReader::new(input))
+            .map_err(|error| format!("Couldn't read {file}: {error}"))?;
+        state.restore(cpu);
+        Ok(self.status(cpu))
+    }
+
+    fn load_debug_info(&mut self, words: &[&str]) -> Result<String, String> {
+        let file = words.first().ok_or("Expected a file name")?;
+        let debug_info = DebugInfo::load(file)?;
+        let symbols = debug_info.symbol_table();
+        self.symbols.merge(&symbols);
+        self.source_map = Some(Box::new(debug_info));
+        Ok(format!("Loaded {} symbols from {file}", symbols.len()))
+    }
+
+    fn load_labels(&mut self, words: &[&str]) -> Result<String, String> {
+        let file = words.first().ok_or("Expected a file name")?;
+        let symbols = SymbolTable::load(file)?;
+        self.symbols.merge(&symbols);
+        Ok(format!("Loaded {} labels from {file}", symbols.len()))
+    }
+
+    fn save_labels(&mut self, words: &[&str]) -> Result<String, String> {
+        let file = words.first().ok_or("Expected a file name")?;
+        fs::write(file, self.symbols.to_vice_labels()).map_err(|error| format!("Couldn't write {file}: {error}"))?;
+        Ok(format!("Saved {} labels to {file}", self.symbols.len()))
+    }
+
+    fn show_labels(&mut self, words: &[&str]) -> Result<String, String> {
+        if let Some(address) = self.argument(words, 0)? {
+            return match self.symbols.lookup(address) {
+                Some((name, 0)) => Ok(format!("${address:04X} {name}")),
+                Some((name, offset)) => Ok(format!("${address:04X} {name}+{offset}")),
+                None => Err(format!("No label at or below ${address:04X}")),
+            };
+        }
+
+        match self.symbols.is_empty() {
+            true => Ok("No labels".to_string()),
+            false => Ok(self.symbols.iter().map(|(name, address)| format!("${address:04X} {name}"))
+                .collect::<Vec<_>>().join("\n")),
+        }
+    }
+
+    fn set_breakpoint(&mut self, cpu: &mut CPU, arguments: &str) -> Result<String, String> {
+        if arguments.is_empty() {
+            return Ok(self.list_breakpoints(cpu));
+        }
+
+        let (address, condition) = match arguments.split_once(" if ") {
+            Some((address, condition)) => (address.trim(), Some(condition)),
+            None => (arguments, None),
+        };
+        let address = self.parse_value(address)?;
+        let condition = condition
+            .map(|condition| Expression::parse(condition, Some(&self.symbols)))
+            .transpose()
+            .map_err(|error| error.to_string())?;
+
+        let id = cpu.breakpoints.add(BreakpointKind::Execute(address));
+        cpu.breakpoints.set_condition(id, condition);
+        Ok(format!("Breakpoint #{id} at ${address:04X}"))
+    }
+
+    fn set_watchpoint(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        let start = self.argument(words, 1)?.ok_or("Expected an address")?;
+        let end = self.argument(words, 2)?.unwrap_or(start);
+        let kind = match words.first() {
+            Some(&"r") => BreakpointKind::Read(start, end),
+            Some(&"w") => BreakpointKind::Write(start, end),
+            Some(&"rw") => BreakpointKind::Access(start, end),
+            _ => return Err("Expected r, w or rw".to_string()),
+        };
+
+        let id = cpu.breakpoints.add(kind);
+        Ok(format!("Watchpoint #{id} at ${start:04X}-${end:04X}"))
+    }
+
+    fn manage_breakpoint(&mut self, cpu: &mut CPU, command: &str, words: &[&str]) -> Result<String, String> {
+        let id: usize = words.first()
+            .and_then(|word| word.parse().ok())
+            .ok_or("Expected a breakpoint number")?;
+        let found = match command {
+            "delete" => cpu.breakpoints.remove(id),
+            "enable" => cpu.breakpoints.set_enabled(id, true),
+            _ => cpu.breakpoints.set_enabled(id, false),
+        };
+        match found {
+            true => Ok(String::new()),
+            false => Err(format!("There is no breakpoint #{id}")),
+        }
+    }
+
+    fn list_breakpoints(&self, cpu: &CPU) -> String {
+        let lines: Vec<String> = cpu.breakpoints.iter().map(describe_breakpoint).collect();
+
+        match lines.is_empty() {
+            true => "No breakpoints".to_string(),
+            false => lines.join("\n"),
+        }
+    }
+
+    fn step(&mut self, cpu: &mut CPU, words: &[&str], over: bool) -> Result<String, String> {
+        let count = self.argument(words, 0)?.unwrap_or(1);
+        let mut cycles = self.cycle_limit;
+        let mut reason = None;
+
+        for _ in 0..count {
+            check_instruction(cpu)?;
+            reason = match over {
+                true => cpu.step_over(&mut cycles),
+                false => cpu.step(&mut cycles),
+            };
+            if reason.is_some() || cycles <= 0 {
+                break;
+            }
+        }
+        Ok(self.report(cpu, reason, cycles))
+    }
+
+    fn step_line(&mut self, cpu: &mut CPU, over: bool) -> Result<String, String> {
+        let source_map = self.source_map.as_ref().ok_or("No debug info loaded, try 'debuginfo'")?;
+        check_instruction(cpu)?;
+        let mut cycles = self.cycle_limit;
+        let reason = step_line(cpu, source_map.as_ref(), over, &mut cycles);
+        Ok(self.report(cpu, reason, cycles))
+    }
+
+    fn step_out(&mut self, cpu: &mut CPU) -> Result<String, String> {
+        check_instruction(cpu)?;
+        let mut cycles = self.cycle_limit;
+        let reason = cpu.step_out(&mut cycles);
+        Ok(self.report(cpu, reason, cycles))
+    }
+
+    fn go(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        if let Some(address) = self.argument(words, 0)? {
+            cpu.program_counter = address;
+        }
+        check_instruction(cpu)?;
+
+        let mut cycles = self.cycle_limit;
+        let reason = cpu.run(&mut cycles);
+        Ok(self.report(cpu, reason, cycles))
+    }
+
+    fn history(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        if let Some(size) = self.argument(words, 0)? {
+            cpu.history.set_capacity(size as usize);
+        }
+        Ok(match cpu.history.capacity() {
+            0 => "History off".to_string(),
+            capacity => format!("History of {} of {capacity} instructions", cpu.history.len()),
+        })
+    }
+
+    fn check_history(&self, cpu: &CPU) -> Result<(), String> {
+        match (cpu.history.is_recording(), cpu.history.is_empty()) {
+            (false, _) => Err("No history recorded, try 'history size'".to_string()),
+            (true, true) => Err(HISTORY_START.to_string()),
+            (true, false) => Ok(()),
+        }
+    }
+
+    fn step_back(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        self.check_history(cpu)?;
+        let count = self.argument(words, 0)?.unwrap_or(1);
+        let mut lines = Vec::new();
+        for _ in 0..count {
+            if !cpu.step_back() {
+                lines.push(HISTORY_START.to_string());
+                break;
+            }
+        }
+        lines.push(self.report(cpu, None, self.cycle_limit));
+        Ok(lines.join("\n"))
+    }
+
+    fn go_back(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        self.check_history(cpu)?;
+        let stop = match self.argument(words, 0)? {
+            Some(address) if cpu.run_back_to(address) => None,
+            Some(_) => Some(HISTORY_START.to_string()),
+            None => Some(cpu.reverse_continue()
+                .map(|reason| describe_stop(&reason))
+                .unwrap_or_else(|| HISTORY_START.to_string())),
+        };
+        let status = self.report(cpu, None, self.cycle_limit);
+        Ok(stop.map(|stop| format!("{stop}\n{status}")).unwrap_or(status))
+    }
+
+    fn start_search(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        let width = match words.first() {
+            Some(&"b") => Width::Byte,
+            Some(&"w") => Width::Word,
+            _ => return Err("Expected b or w".to_string()),
+        };
+        let mut search = MemorySearch::new(&*cpu.memory, width);
+        if let Some(value) = self.argument(words, 1)? {
+            search.filter(&*cpu.memory, Filter::Equal(value))?;
+        }
+        self.search = Some(search);
+        Ok(self.search_results(cpu))
+    }
+
+    fn narrow_search(&mut self, cpu: &mut CPU, words: &[&str]) -> Result<String, String> {
+        let amount = self.argument(words, 1)?;
+        let filter = match words.first() {
+            None => return Err("Expected a value, changed, unchanged, inc or dec".to_string()),
+            Some(&"changed") => Filter::Changed,
+            Some(&"unchanged") => Filter::Unchanged,
+            Some(&"inc") => amount.map(Filter::IncreasedBy).unwrap_or(Filter::Increased),
+            Some(&"dec") => amount.map(Filter::DecreasedBy).unwrap_or(Filter::Decreased),
+            Some(value) => Filter::Equal(self.parse_value(value)?),
+        };
+        let search = self.search.as_mut().ok_or("No search started, try 'search'")?;
+        search.filter(&*cpu.memory, filter)?;
+        Ok(self.search_results(cpu))
+    }
+
+    /* The amount of matches, listing them once there are only a few left */
+    fn search_results(&self, cpu: &CPU) -> String {
+        let Some(search) = &self.search else {
+            return String::new();
+        };
+        let candidates = search.candidates();
+        let mut lines = vec![match candidates.len() {
+            1 => "1 match".to_string(),
+            count => format!("{count} matches"),
+        }];
+        if candidates.len() <= 16 {
+            for address in candidates {
+                let value = search.value(&*cpu.memory, *address);
+                lines.push(match search.width {
+                    Width::Byte => format!("${address:04X}: ${value:02X}"),
+                    Width::Word => format!("${address:04X}: ${value:04X}"),
+                });
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn report(&mut self, cpu: &mut CPU, reason: Option<StopReason>, cycles: isize) -> String {
+        let mut lines = cpu.breakpoints.take_messages();
+        match reason {
+            Some(reason) => lines.push(describe_stop(&reason)),
+            None if cycles <= 0 => lines.push(format!("Stopped after {} cycles", self.cycle_limit - cycles)),
+            None => {}
+        }
+        lines.push(self.status(cpu));
+        self.disassemble_address = None;
+        lines.join("\n")
+    }
+
+    fn backtrace(&self, cpu: &CPU) -> String {
+        let frames = cpu.call_stack.frames();
+        if frames.is_empty() {
+            return "No frames".to_string();
+        }
+
+        frames.iter().rev().enumerate().map(|(index, frame)| {
+            let kind = match frame.kind {
+                FrameKind::Subroutine => "",
+                FrameKind::Break => " [brk]",
+                FrameKind::Interrupt => " [irq]",
+                FrameKind::NonMaskableInterrupt => " [nmi]",
+            };
+            let symbol = match frame.symbol(&self.symbols) {
+                Some((name, 0)) => format!(" {name}"),
+                Some((name, offset)) => format!(" {name}+{offset}"),
+                None => String::new(),
+            };
+            format!("#{index} ${:04X}{symbol}{kind} called from ${:04X}", frame.target, frame.caller)
+        }).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/* Fails on an illegal opcode at the program counter, which the cpu can't execute */
+fn check_instruction(cpu: &CPU) -> Result<(), String> {
+    match cpu.at_illegal_opcode() {
+        false => Ok(()),
+        true => Err(format!("Illegal opcode at ${:04X}", cpu.program_counter)),
+    }
+}
+
+pub fn describe_stop(reason: &StopReason) -> String {
+    match reason {
+        StopReason::Breakpoint { id, address } => format!("Breakpoint #{id} hit at ${address:04X}"),
+        StopReason::Watchpoint { id, access } => {
+            let kind = match access.kind {
+                AccessKind::Opcode => "opcode fetch",
+                AccessKind::Operand => "operand fetch",
+                AccessKind::Read => "read",
+                AccessKind::Write => "write",
+            };
+            format!("Watchpoint #{id} hit by a {kind} of ${:02X} at ${:04X}", access.value, access.address)
+        }
+        StopReason::Interrupt { id, vector } => format!("Interrupt breakpoint #{id} hit, vector ${vector:04X}"),
+        StopReason::IllegalOpcode { address } => format!("Illegal opcode at ${address:04X}"),
+    }
+}
+
+pub fn describe_breakpoint(breakpoint: &Breakpoint) -> String {
+    let kind = match breakpoint.kind {
+        BreakpointKind::Execute(address) => format!("exec ${address:04X}"),
+        BreakpointKind::Read(start, end) => format!("read ${start:04X}-${end:04X}"),
+        BreakpointKind::Write(start, end) => format!("write ${start:04X}-${end:04X}"),
+        BreakpointKind::Access(start, end) => format!("access ${start:04X}-${end:04X}"),
+        BreakpointKind::Opcode(opcode) => format!("opcode ${opcode:02X}"),
+        BreakpointKind::Break => "brk".to_string(),
+        BreakpointKind::Interrupt => "interrupt".to_string(),
+    };
+    let state = if breakpoint.enabled { "" } else { " (disabled)" };
+    let condition = if breakpoint.condition.is_some() { " (conditional)" } else { "" };
+    format!("#{} {}{}{} hits: {}", breakpoint.id, kind, condition, state, breakpoint.hits)
+}
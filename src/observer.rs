@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::call_stack::FrameKind;
+use crate::cpu::{CPU, Registers};
+use crate::decoder::DecodedInstruction;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Opcode,
+    Operand,
+    Read,
+    Write,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub kind: AccessKind,
+    pub address: u16,
+    pub value: u8,
+}
+
+/* An IRQ or NMI the cpu took between two instructions */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InterruptEntry {
+    pub kind: FrameKind,
+    /* The registers right before the interrupt */
+    pub before: Registers,
+    pub cycles: u64,
+}
+
+/*
+ * Gets notified by the cpu while it executes instructions. Observers are owned by the cpu,
+ * so wrap them in an Rc<RefCell<_>> to keep access to their state.
+ */
+pub trait Observer {
+    fn before_instruction(&mut self, _cpu: &CPU, _instruction: &DecodedInstruction) {}
+
+    fn memory_access(&mut self, _access: &Access) {}
+
+    fn after_instruction(&mut self, _cpu: &CPU) {}
+
+    /* Called once the cpu entered the handler, after the accesses pushing the return state */
+    fn interrupt(&mut self, _cpu: &CPU, _entry: &InterruptEntry) {}
+
+    /* Called after CPU::edit changed a byte from outside of the program */
+    fn memory_edited(&mut self, _cpu: &CPU, _address: u16, _value: u8) {}
+}
+
+impl<T: Observer> Observer for Rc<RefCell<T>> {
+    fn before_instruction(&mut self, cpu: &CPU, instruction: &DecodedInstruction) {
+        self.borrow_mut().before_instruction(cpu, instruction)
+    }
+
+    fn memory_access(&mut self, access: &Access) {
+        self.borrow_mut().memory_access(access)
+    }
+
+    fn after_instruction(&mut self, cpu: &CPU) {
+        self.borrow_mut().after_instruction(cpu)
+    }
+
+    fn interrupt(&mut self, cpu: &CPU, entry: &InterruptEntry) {
+        self.borrow_mut().interrupt(cpu, entry)
+    }
+
+    fn memory_edited(&mut self, cpu: &CPU, address: u16, value: u8) {
+        self.borrow_mut().memory_edited(cpu, address, value)
+    }
+}
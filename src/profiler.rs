@@ -0,0 +1,206 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use crate::call_stack::FrameKind;
+use crate::cpu::CPU;
+use crate::decoder::DecodedInstruction;
+use crate::instruction::OPCODES;
+use crate::observer::{InterruptEntry, Observer};
+use crate::symbols::SymbolTable;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Counter {
+    pub count: u64,
+    pub cycles: u64,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubroutineProfile {
+    pub calls: u64,
+    /* Cycles spent in the subroutine and everything it called */
+    pub inclusive: u64,
+    /* Cycles spent in the subroutine's own instructions */
+    pub exclusive: u64,
+}
+
+struct Pending {
+    address: u16,
+    opcode: u8,
+    start: u64,
+    /*
+     * The subroutines on the call stack innermost first, recursion counted once, up to the
+     * nearest interrupt handler, which the interrupted code doesn't get charged for. Then the top level.
+     */
+    subroutines: Vec<Option<u16>>,
+    stack: Vec<Option<u16>>,
+}
+
+/*
+ * Attributes every cycle to the instruction that consumed it and, following the call stack,
+ * to the subroutines and interrupt handlers it ran in. The top level, keyed as None, includes
+ * everything and exclusively holds the code running outside of any subroutine. The cycles
+ * entering an IRQ or NMI belong to its handler but to no instruction.
+ */
+#[derive(Default)]
+pub struct Profiler {
+    pub addresses: BTreeMap<u16, Counter>,
+    pub opcodes: BTreeMap<u8, Counter>,
+    pub subroutines: BTreeMap<Option<u16>, SubroutineProfile>,
+    /* Cycles by call stack from the root, which is the top level (None) or an interrupt handler */
+    pub stacks: HashMap<Vec<Option<u16>>, u64>,
+    pub total_cycles: u64,
+    pub instructions: u64,
+    depth: usize,
+    pending: Option<Pending>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /* The subroutines charged for the cycles at the current point and the call stack from its root */
+    fn attribution(cpu: &CPU) -> (Vec<Option<u16>>, Vec<Option<u16>>) {
+        let frames = cpu.call_stack.frames();
+        let root = frames.iter().rposition(|frame| frame.kind != FrameKind::Subroutine);
+
+        let mut subroutines: Vec<Option<u16>> = Vec::new();
+        for frame in frames[root.unwrap_or(0)..].iter().rev() {
+            if !subroutines.contains(&Some(frame.target)) {
+                subroutines.push(Some(frame.target));
+            }
+        }
+        subroutines.push(None);
+
+        let mut stack = match root {
+            Some(_) => Vec::new(),
+            None => vec![None],
+        };
+        stack.extend(frames[root.unwrap_or(0)..].iter().map(|frame| Some(frame.target)));
+        (subroutines, stack)
+    }
+
+    fn charge(&mut self, cycles: u64, subroutines: &[Option<u16>], stack: Vec<Option<u16>>) {
+        self.total_cycles += cycles;
+        for (index, subroutine) in subroutines.iter().enumerate() {
+            let profile = self.subroutines.entry(*subroutine).or_default();
+            profile.inclusive += cycles;
+            if index == 0 {
+                profile.exclusive += cycles;
+            }
+        }
+        *self.stacks.entry(stack).or_default() += cycles;
+    }
+
+    fn count_entered_frames(&mut self, cpu: &CPU) {
+        let frames = cpu.call_stack.frames();
+        if frames.len() > self.depth {
+            for frame in &frames[self.depth..] {
+                self.subroutines.entry(Some(frame.target)).or_default().calls += 1;
+            }
+        }
+        self.depth = frames.len();
+    }
+
+    /*
+     * The stacks in the folded format of flamegraph.pl and inferno, one `root;caller;callee cycles`
+     * line per stack in alphabetical order. Interrupt handlers are roots of their own.
+     */
+    pub fn folded_stacks(&self, symbols: Option<&SymbolTable>) -> String {
+        let name = |frame: &Option<u16>| match frame {
+            None => "(top level)".to_string(),
+            Some(address) => match symbols.and_then(|symbols| symbols.lookup(*address)) {
+                Some((name, 0)) => name.to_string(),
+                Some((name, offset)) => format!("{name}+{offset}"),
+                None => format!("${address:04X}"),
+            },
+        };
+
+        let mut lines: Vec<String> = self.stacks.iter()
+            .map(|(stack, cycles)| format!("{} {cycles}", stack.iter().map(name).collect::<Vec<_>>().join(";")))
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    /* The report of the hot spots, subroutines and opcodes, each limited to the given amount of lines */
+    pub fn report(&self, symbols: Option<&SymbolTable>, limit: usize) -> String {
+        let name = |address: u16| match symbols.and_then(|symbols| symbols.lookup(address)) {
+            Some((name, 0)) => format!("${address:04X} {name}"),
+            Some((name, offset)) => format!("${address:04X} {name}+{offset}"),
+            None => format!("${address:04X}"),
+        };
+        let percent = |cycles: u64| match self.total_cycles {
+            0 => 0.0,
+            total => cycles as f64 * 100.0 / total as f64,
+        };
+
+        let mut report = String::new();
+        writeln!(report, "{} cycles in {} instructions", self.total_cycles, self.instructions).unwrap();
+
+        writeln!(report, "\nHot spots\n    Cycles       %     Count  Address").unwrap();
+        let mut addresses: Vec<(&u16, &Counter)> = self.addresses.iter().collect();
+        addresses.sort_by_key(|(address, counter)| (u64::MAX - counter.cycles, **address));
+        for (address, counter) in addresses.into_iter().take(limit) {
+            writeln!(report, "{:>10} {:>6.2}% {:>9}  {}", counter.cycles, percent(counter.cycles), counter.count,
+                     name(*address)).unwrap();
+        }
+
+        writeln!(report, "\nSubroutines\n Inclusive       %  Exclusive       %     Calls  Subroutine").unwrap();
+        let mut subroutines: Vec<(&Option<u16>, &SubroutineProfile)> = self.subroutines.iter().collect();
+        subroutines.sort_by_key(|(address, profile)| (u64::MAX - profile.inclusive, **address));
+        for (address, profile) in subroutines.into_iter().take(limit) {
+            let subroutine = address.map(name).unwrap_or_else(|| "(top level)".to_string());
+            writeln!(report, "{:>10} {:>6.2}% {:>10} {:>6.2}% {:>9}  {}", profile.inclusive, percent(profile.inclusive),
+                     profile.exclusive, percent(profile.exclusive), profile.calls, subroutine).unwrap();
+        }
+
+        writeln!(report, "\nOpcodes\n    Cycles       %     Count  Opcode").unwrap();
+        let mut opcodes: Vec<(&u8, &Counter)> = self.opcodes.iter().collect();
+        opcodes.sort_by_key(|(opcode, counter)| (u64::MAX - counter.count, **opcode));
+        for (opcode, counter) in opcodes.into_iter().take(limit) {
+            let description = match OPCODES[*opcode as usize] {
+                Some(instruction) => format!("{} {}", instruction.mnemonic(), instruction.mode()),
+                None => "???".to_string(),
+            };
+            writeln!(report, "{:>10} {:>6.2}% {:>9}  ${opcode:02X} {description}", counter.cycles,
+                     percent(counter.cycles), counter.count).unwrap();
+        }
+        report
+    }
+}
+
+impl Observer for Profiler {
+    fn before_instruction(&mut self, cpu: &CPU, instruction: &DecodedInstruction) {
+        /* Interrupts are taken between instructions */
+        self.count_entered_frames(cpu);
+        let (subroutines, stack) = Profiler::attribution(cpu);
+        self.pending = Some(Pending {
+            address: instruction.address,
+            opcode: instruction.opcode,
+            start: cpu.total_cycles,
+            subroutines,
+            stack,
+        });
+    }
+
+    fn after_instruction(&mut self, cpu: &CPU) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        let cycles = cpu.total_cycles - pending.start;
+        self.instructions += 1;
+
+        for counter in [self.addresses.entry(pending.address).or_default(), self.opcodes.entry(pending.opcode).or_default()] {
+            counter.count += 1;
+            counter.cycles += cycles;
+        }
+        self.charge(cycles, &pending.subroutines, pending.stack);
+        self.count_entered_frames(cpu);
+    }
+
+    fn interrupt(&mut self, cpu: &CPU, entry: &InterruptEntry) {
+        self.count_entered_frames(cpu);
+        let (subroutines, stack) = Profiler::attribution(cpu);
+        self.charge(entry.cycles, &subroutines, stack);
+    }
+}
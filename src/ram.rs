@@ -0,0 +1,46 @@
+use crate::cpu::Memory;
+
+/* Plain 64K of memory where every access takes one cycle */
+#[derive(Clone)]
+pub struct Ram {
+    data: Vec<u8>,
+}
+
+impl Ram {
+    pub fn new() -> Ram {
+        Ram { data: vec![0; 0x10000] }
+    }
+
+    /* Copies the bytes to the address, wrapping around at the end of memory */
+    pub fn load(&mut self, address: u16, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.data[address.wrapping_add(offset as u16) as usize] = *byte;
+        }
+    }
+}
+
+impl Default for Ram {
+    fn default() -> Ram {
+        Ram::new()
+    }
+}
+
+impl Memory for Ram {
+    fn read(&self, cycles: &mut isize, address: u16) -> u8 {
+        *cycles -= 1;
+        self.get(address)
+    }
+
+    fn get(&self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+
+    fn write(&mut self, cycles: &mut isize, address: u16, value: u8) {
+        *cycles -= 1;
+        self.set(address, value);
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        self.data[address as usize] = value;
+    }
+}
@@ -0,0 +1,132 @@
+use std::cell::Cell;
+use std::fmt;
+use std::fmt::Formatter;
+use crate::breakpoint::StopReason;
+use crate::cpu::CPU;
+use crate::decoder::Operand;
+use crate::instruction::{AddressingMode, Instruction};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Halt {
+    /* An instruction jumping or branching to itself, the usual way test programs report their result */
+    Trap(u16),
+    /* One of the configured stop addresses was reached */
+    Address(u16),
+    Break(u16),
+    IllegalOpcode(u16),
+    /* Writing to an output device like the Console failed, so nobody sees what the program reports */
+    OutputClosed,
+    Breakpoint(StopReason),
+    CycleLimit,
+    InstructionLimit,
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Halt::Trap(address) => write!(f, "Trapped at ${address:04X}"),
+            Halt::Address(address) => write!(f, "Reached ${address:04X}"),
+            Halt::Break(address) => write!(f, "BRK at ${address:04X}"),
+            Halt::IllegalOpcode(address) => write!(f, "Illegal opcode at ${address:04X}"),
+            Halt::OutputClosed => write!(f, "Output closed"),
+            Halt::Breakpoint(reason) => write!(f, "Stopped by {reason:?}"),
+            Halt::CycleLimit => write!(f, "Cycle limit reached"),
+            Halt::InstructionLimit => write!(f, "Instruction limit reached"),
+        }
+    }
+}
+
+/* Runs a program headless until it halts, with every condition checked before the next instruction */
+#[derive(Clone, Debug, Default)]
+pub struct Runner {
+    pub cycle_limit: Option<u64>,
+    pub instruction_limit: Option<u64>,
+    pub stop_on_trap: bool,
+    pub stop_on_break: bool,
+    pub stop_addresses: Vec<u16>,
+}
+
+impl Runner {
+    pub fn new() -> Runner {
+        Runner::default()
+    }
+
+    pub fn run(&self, cpu: &mut CPU) -> Halt {
+        let start = cpu.total_cycles;
+        let instructions = Cell::new(0);
+        loop {
+            if let Some(halt) = self.check(cpu, instructions.get()) {
+                return halt;
+            }
+
+            let remaining = match self.cycle_limit {
+                Some(limit) => limit.saturating_sub(cpu.total_cycles - start).min(isize::MAX as u64) as isize,
+                None => isize::MAX,
+            };
+            let mut cycles = remaining;
+            let reason = cpu.run_until(&mut cycles, |cpu| {
+                instructions.set(instructions.get() + 1);
+                self.check(cpu, instructions.get()).is_some()
+            });
+            match reason {
+                Some(StopReason::IllegalOpcode { address }) => return Halt::IllegalOpcode(address),
+                Some(reason) => return Halt::Breakpoint(reason),
+                None => {}
+            }
+            if cycles <= 0 && self.cycle_limit.is_some() {
+                return Halt::CycleLimit;
+            }
+        }
+    }
+
+    fn check(&self, cpu: &CPU, instructions: u64) -> Option<Halt> {
+        if cpu.memory.output_error().is_some() {
+            return Some(Halt::OutputClosed);
+        }
+        let address = cpu.program_counter;
+        if self.stop_addresses.contains(&address) {
+            return Some(Halt::Address(address));
+        }
+        if self.instruction_limit.is_some_and(|limit| instructions >= limit) {
+            return Some(Halt::InstructionLimit);
+        }
+
+        /* A hook can take the place of an illegal opcode, like a stub for a routine in rom */
+        let Some(next) = cpu.next_instruction() else {
+            return match cpu.hooks.contains(address) {
+                true => None,
+                false => Some(Halt::IllegalOpcode(address)),
+            };
+        };
+        if self.stop_on_break && matches!(next.instruction, Instruction::Break(_)) {
+            return Some(Halt::Break(address));
+        }
+
+        let trapped = match (next.mode(), next.operand) {
+            (AddressingMode::Absolute, Operand::Word(target)) => {
+                matches!(next.instruction, Instruction::Jump(_)) && target == address
+            }
+            (AddressingMode::Relative, Operand::Byte(offset)) => offset == 0xFE && branch_taken(cpu, next.instruction),
+            _ => false,
+        };
+        if self.stop_on_trap && trapped {
+            return Some(Halt::Trap(address));
+        }
+        None
+    }
+}
+
+/* Whether the branch instruction would branch with the current flags */
+fn branch_taken(cpu: &CPU, instruction: Instruction) -> bool {
+    match instruction {
+        Instruction::BranchOnCarryClear(_) => !cpu.carry,
+        Instruction::BranchOnCarrySet(_) => cpu.carry,
+        Instruction::BranchOnEqual(_) => cpu.zero,
+        Instruction::BranchOnNotEqual(_) => !cpu.zero,
+        Instruction::BranchOnMinus(_) => cpu.negative,
+        Instruction::BranchOnPlus(_) => !cpu.negative,
+        Instruction::BranchOnOverflowClear(_) => !cpu.overflow,
+        Instruction::BranchOnOverflowSet(_) => cpu.overflow,
+        _ => false,
+    }
+}
@@ -0,0 +1,127 @@
+use crate::cpu::Memory;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Width {
+    Byte,
+    /* Little-endian words starting at every address */
+    Word,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Equal(u16),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    /* Differences wrap around like the values do */
+    IncreasedBy(u16),
+    DecreasedBy(u16),
+}
+
+impl Width {
+    /* The largest value of the width */
+    pub fn max(&self) -> u16 {
+        match self {
+            Width::Byte => 0xFF,
+            Width::Word => 0xFFFF,
+        }
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, old: u16, new: u16, width: Width) -> bool {
+        let mask = width.max();
+        match *self {
+            Filter::Equal(value) => new == value,
+            Filter::Changed => new != old,
+            Filter::Unchanged => new == old,
+            Filter::Increased => new > old,
+            Filter::Decreased => new < old,
+            Filter::IncreasedBy(amount) => new == old.wrapping_add(amount) & mask,
+            Filter::DecreasedBy(amount) => new == old.wrapping_sub(amount) & mask,
+        }
+    }
+}
+
+/*
+ * A cheat-finder style search for variables. It starts out with every address as a candidate
+ * and a snapshot of memory, every filter compares the current values to the snapshot taken by
+ * the previous one and keeps the candidates that match.
+ */
+#[derive(Clone, Debug)]
+pub struct MemorySearch {
+    pub width: Width,
+    /* Whether it compares the stored bytes instead of the ones the cpu reads */
+    raw: bool,
+    candidates: Vec<u16>,
+    snapshot: Vec<u8>,
+}
+
+impl MemorySearch {
+    pub fn new(memory: &dyn Memory, width: Width) -> MemorySearch {
+        MemorySearch::with_reads(memory, width, false)
+    }
+
+    /* A search through the stored bytes, so that cheats don't hide the changes to frozen addresses */
+    pub fn raw(memory: &dyn Memory, width: Width) -> MemorySearch {
+        MemorySearch::with_reads(memory, width, true)
+    }
+
+    fn with_reads(memory: &dyn Memory, width: Width, raw: bool) -> MemorySearch {
+        let candidates = match width {
+            Width::Byte => (0..=0xFFFF).collect(),
+            Width::Word => (0..0xFFFF).collect(),
+        };
+        let mut search = MemorySearch { width, raw, candidates, snapshot: Vec::new() };
+        search.snapshot = search.take_snapshot(memory);
+        search
+    }
+
+    fn read(&self, memory: &dyn Memory, address: u16) -> u8 {
+        match self.raw {
+            true => memory.get_raw(address),
+            false => memory.get(address),
+        }
+    }
+
+    fn take_snapshot(&self, memory: &dyn Memory) -> Vec<u8> {
+        (0..=0xFFFF).map(|address| self.read(memory, address)).collect()
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    /* The value at the address when the last snapshot was taken */
+    pub fn previous(&self, address: u16) -> u16 {
+        match self.width {
+            Width::Byte => self.snapshot[address as usize] as u16,
+            Width::Word => u16::from_le_bytes([self.snapshot[address as usize], self.snapshot[address as usize + 1]]),
+        }
+    }
+
+    pub fn value(&self, memory: &dyn Memory, address: u16) -> u16 {
+        match self.width {
+            Width::Byte => self.read(memory, address) as u16,
+            Width::Word => u16::from_le_bytes([self.read(memory, address), self.read(memory, address + 1)]),
+        }
+    }
+
+    /*
+     * Keeps the candidates matching the filter, takes a new snapshot and returns how many are
+     * left. Values and amounts wider than the search fail, rather than matching truncated.
+     */
+    pub fn filter(&mut self, memory: &dyn Memory, filter: Filter) -> Result<usize, String> {
+        if let Filter::Equal(value) | Filter::IncreasedBy(value) | Filter::DecreasedBy(value) = filter {
+            if value > self.width.max() {
+                return Err(format!("${value:X} doesn't fit the search width"));
+            }
+        }
+        let mut candidates = std::mem::take(&mut self.candidates);
+        candidates.retain(|address| filter.matches(self.previous(*address), self.value(memory, *address), self.width));
+        self.candidates = candidates;
+        self.snapshot = self.take_snapshot(memory);
+        Ok(self.candidates.len())
+    }
+}
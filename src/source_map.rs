@@ -0,0 +1,39 @@
+use crate::breakpoint::StopReason;
+use crate::cpu::CPU;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+}
+
+/* Maps between addresses and source lines, implemented by debug info formats */
+pub trait SourceMap {
+    /* The source line that generated the code at the address */
+    fn location(&self, address: u16) -> Option<SourceLocation>;
+
+    /* The addresses of the code generated by the line, the file may be given as a suffix of the path */
+    fn addresses(&self, file: &str, line: u32) -> Vec<u16>;
+}
+
+/*
+ * Steps until the program counter reaches code of another source line, stepping over
+ * subroutine calls when asked to. Without a source line at the start a single instruction is
+ * stepped, running out of cycles stops without a reason.
+ */
+pub fn step_line(cpu: &mut CPU, source_map: &dyn SourceMap, over: bool, cycles: &mut isize) -> Option<StopReason> {
+    let start = source_map.location(cpu.program_counter);
+    loop {
+        let stop = match over {
+            true => cpu.step_over(cycles),
+            false => cpu.step(cycles),
+        };
+        if stop.is_some() || *cycles <= 0 || start.is_none() {
+            return stop;
+        }
+        match source_map.location(cpu.program_counter) {
+            Some(location) if Some(&location) != start.as_ref() => return None,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use crate::cow_ram::PAGE_SIZE;
+use crate::cpu::{CPU, Registers};
+
+const MAGIC: &[u8; 8] = b"M6502STA";
+const DELTA_MAGIC: &[u8; 8] = b"M6502DLT";
+const VERSION: u8 = 1;
+
+fn read_registers(input: &mut impl Read) -> io::Result<(Registers, u64)> {
+    let mut registers = [0; 7];
+    input.read_exact(&mut registers)?;
+    let mut total_cycles = [0; 8];
+    input.read_exact(&mut total_cycles)?;
+    let registers = Registers {
+        program_counter: u16::from_le_bytes([registers[0], registers[1]]),
+        accumulator: registers[2],
+        x: registers[3],
+        y: registers[4],
+        stack_pointer: registers[5],
+        status: registers[6],
+    };
+    Ok((registers, u64::from_le_bytes(total_cycles)))
+}
+
+fn write_registers(output: &mut impl Write, registers: &Registers, total_cycles: u64) -> io::Result<()> {
+    output.write_all(&registers.program_counter.to_le_bytes())?;
+    output.write_all(&[registers.accumulator, registers.x, registers.y,
+        registers.stack_pointer, registers.status])?;
+    output.write_all(&total_cycles.to_le_bytes())
+}
+
+fn read_header(input: &mut impl Read, magic: &[u8; 8], message: &str) -> io::Result<()> {
+    let mut header = [0; 9];
+    input.read_exact(&mut header)?;
+    if &header[..8] != magic || header[8] != VERSION {
+        return Err(io::Error::new(ErrorKind::InvalidData, message));
+    }
+    Ok(())
+}
+
+/* A complete copy of the cpu registers and memory */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachineState {
+    pub registers: Registers,
+    pub total_cycles: u64,
+    pub memory: Vec<u8>,
+}
+
+impl MachineState {
+    pub fn capture(cpu: &CPU) -> MachineState {
+        MachineState {
+            registers: cpu.registers(),
+            total_cycles: cpu.total_cycles,
+            memory: (0..=0xFFFF).map(|address| cpu.memory.get_raw(address)).collect(),
+        }
+    }
+
+    /* Also starts a new checkpoint, so deltas captured afterwards build on the restored state */
+    pub fn restore(&self, cpu: &mut CPU) {
+        cpu.set_registers(self.registers);
+        cpu.total_cycles = self.total_cycles;
+        for (address, value) in self.memory.iter().enumerate() {
+            cpu.memory.set(address as u16, *value);
+        }
+        cpu.memory.checkpoint();
+        cpu.call_stack.clear();
+        cpu.history.clear();
+    }
+
+    pub fn write_to(&self, output: &mut impl Write) -> io::Result<()> {
+        output.write_all(MAGIC)?;
+        output.write_all(&[VERSION])?;
+        write_registers(output, &self.registers, self.total_cycles)?;
+        output.write_all(&self.memory)
+    }
+
+    pub fn read_from(input: &mut impl Read) -> io::Result<MachineState> {
+        read_header(input, MAGIC, "Not a machine state.")?;
+        let (registers, total_cycles) = read_registers(input)?;
+        let mut memory = vec![0; 0x10000];
+        input.read_exact(&mut memory)?;
+        Ok(MachineState { registers, total_cycles, memory })
+    }
+}
+
+/*
+ * The registers and the memory pages written since the last checkpoint. Capturing starts the
+ * next checkpoint, so a chain of deltas restores the machine when applied in order on top of
+ * the state captured at the first checkpoint. Memory that doesn't track written pages ends up
+ * in the delta as a whole.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateDelta {
+    pub registers: Registers,
+    pub total_cycles: u64,
+    pub pages: BTreeMap<u8, Vec<u8>>,
+}
+
+impl StateDelta {
+    pub fn capture(cpu: &mut CPU) -> StateDelta {
+        let pages = cpu.memory.checkpoint().unwrap_or_else(|| (0..=0xFF).collect());
+        let pages = pages.into_iter().map(|page| {
+            let start = page as u16 * PAGE_SIZE as u16;
+            (page, (0..PAGE_SIZE as u16).map(|offset| cpu.memory.get_raw(start + offset)).collect())
+        }).collect();
+
+        StateDelta { registers: cpu.registers(), total_cycles: cpu.total_cycles, pages }
+    }
+
+    /* Like MachineState::restore, this starts a new checkpoint so the applied pages aren't captured again */
+    pub fn apply(&self, cpu: &mut CPU) {
+        cpu.set_registers(self.registers);
+        cpu.total_cycles = self.total_cycles;
+        for (page, bytes) in &self.pages {
+            let start = *page as u16 * PAGE_SIZE as u16;
+            for (offset, value) in bytes.iter().enumerate() {
+                cpu.memory.set(start + offset as u16, *value);
+            }
+        }
+        cpu.memory.checkpoint();
+        cpu.call_stack.clear();
+        cpu.history.clear();
+    }
+
+    pub fn write_to(&self, output: &mut impl Write) -> io::Result<()> {
+        output.write_all(DELTA_MAGIC)?;
+        output.write_all(&[VERSION])?;
+        write_registers(output, &self.registers, self.total_cycles)?;
+        output.write_all(&(self.pages.len() as u16).to_le_bytes())?;
+        for (page, bytes) in &self.pages {
+            output.write_all(&[*page])?;
+            output.write_all(bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(input: &mut impl Read) -> io::Result<StateDelta> {
+        read_header(input, DELTA_MAGIC, "Not a machine state delta.")?;
+        let (registers, total_cycles) = read_registers(input)?;
+        let mut count = [0; 2];
+        input.read_exact(&mut count)?;
+
+        let mut pages = BTreeMap::new();
+        for _ in 0..u16::from_le_bytes(count) {
+            let mut page = [0; 1];
+            input.read_exact(&mut page)?;
+            let mut bytes = vec![0; PAGE_SIZE];
+            input.read_exact(&mut bytes)?;
+            pages.insert(page[0], bytes);
+        }
+        Ok(StateDelta { registers, total_cycles, pages })
+    }
+}
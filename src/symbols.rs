@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use crate::assembler::parse_address;
+
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    addresses: HashMap<String, u16>,
+    names: BTreeMap<u16, Vec<String>>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    pub fn load(path: &str) -> Result<SymbolTable, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("Couldn't read {path}: {error}"))?;
+        SymbolTable::parse(&text)
+    }
+
+    /*
+     * Reads VICE label files (`al C:0810 .main`) as well as the `name = $0810` lists written by
+     * ACME, 64tass and by hand. Assignments of values that aren't addresses, like the strings
+     * and floats in 64tass dumps, are skipped.
+     */
+    pub fn parse(text: &str) -> Result<SymbolTable, String> {
+        let mut table = SymbolTable::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+            let error = || format!("Line {}: Expected 'al address .name' or 'name = address'", index + 1);
+
+            if let Some(label) = line.strip_prefix("al ") {
+                let (address, name) = label.trim().split_once(char::is_whitespace).ok_or_else(error)?;
+                let address = address.split_once(':').map_or(address, |(_, address)| address);
+                let address = u16::from_str_radix(address, 16).map_err(|_| error())?;
+                table.insert(name.trim().trim_start_matches('.'), address);
+            } else if let Some((name, value)) = line.split_once('=') {
+                /* ACME marks address symbols with `!addr`, 64tass uses `:=` for variables */
+                let name = name.trim().trim_start_matches("!addr").trim_end_matches(':').trim();
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    return Err(error());
+                }
+                if let Some(address) = parse_address(value.trim()) {
+                    table.insert(name, address);
+                }
+            } else {
+                return Err(error());
+            }
+        }
+        Ok(table)
+    }
+
+    /* Adds all symbols of the other table, replacing existing ones with the same name */
+    pub fn merge(&mut self, other: &SymbolTable) {
+        for (name, address) in other.iter() {
+            self.insert(name, address);
+        }
+    }
+
+    /* The symbols as a VICE label file ordered by address, ready for the `ll` monitor command */
+    pub fn to_vice_labels(&self) -> String {
+        let mut text = String::new();
+        for (name, address) in self.iter() {
+            writeln!(text, "al C:{address:04x} .{name}").unwrap();
+        }
+        text
+    }
+
+    pub fn insert(&mut self, name: &str, address: u16) {
+        if let Some(previous) = self.addresses.insert(name.to_string(), address) {
+            if let Some(names) = self.names.get_mut(&previous) {
+                names.retain(|other| other != name);
+                if names.is_empty() {
+                    self.names.remove(&previous);
+                }
+            }
+        }
+        self.names.entry(address).or_default().push(name.to_string());
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    /* The first name defined for exactly this address */
+    pub fn name_of(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).and_then(|names| names.first()).map(String::as_str)
+    }
+
+    /* The nearest symbol at or below the address, along with the offset from it */
+    pub fn lookup(&self, address: u16) -> Option<(&str, u16)> {
+        let (symbol, names) = self.names.range(..=address).next_back()?;
+        Some((names.first()?.as_str(), address - symbol))
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    /* All symbols ordered by address */
+    pub fn iter(&self) -> impl Iterator<Item=(&str, u16)> {
+        self.names.iter()
+            .flat_map(|(address, names)| names.iter().map(move |name| (name.as_str(), *address)))
+    }
+}
@@ -0,0 +1,126 @@
+use std::io;
+use std::io::{BufRead, Write};
+use crate::cpu::{CPU, Memory};
+use crate::decoder::{DecodedInstruction, Operand};
+use crate::instruction::{AddressingMode, Instruction};
+use crate::observer::Observer;
+
+/*
+ * Writes one line per instruction in the format of the well-known nestest.log. The first write
+ * error stops the trace without interrupting the emulation, check error() once it ran.
+ */
+pub struct Tracer<W: Write> {
+    output: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(output: W) -> Tracer<W> {
+        Tracer { output, error: None }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.output
+    }
+
+    /* The error that stopped the trace, if any */
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+impl<W: Write> Observer for Tracer<W> {
+    fn before_instruction(&mut self, cpu: &CPU, instruction: &DecodedInstruction) {
+        if self.error.is_none() {
+            self.error = writeln!(self.output, "{}", format_line(cpu, instruction)).err();
+        }
+    }
+}
+
+pub fn format_line(cpu: &CPU, instruction: &DecodedInstruction) -> String {
+    let bytes = instruction.bytes().iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    /* The ppu runs three dots per cpu cycle with 341 dots per scanline */
+    let dots = cpu.total_cycles * 3;
+
+    format!("{:04X}  {:<8}  {:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+            instruction.address, bytes, disassemble(cpu, instruction),
+            cpu.accumulator, cpu.x, cpu.y, cpu.get_processor_status(), cpu.stack_pointer,
+            (dots / 341) % 262, dots % 341, cpu.total_cycles)
+}
+
+/* Disassembles the instruction and annotates the memory it accesses like nestest does */
+pub fn disassemble(cpu: &CPU, instruction: &DecodedInstruction) -> String {
+    let memory: &dyn Memory = &*cpu.memory;
+    let mnemonic = instruction.instruction.mnemonic();
+    let address = instruction.effective_address(cpu).unwrap_or(0);
+    let value = memory.get(address);
+
+    match (instruction.mode(), instruction.operand) {
+        (AddressingMode::Zeropage, Operand::Byte(operand)) => {
+            format!("{mnemonic} ${operand:02X} = {value:02X}")
+        }
+        (AddressingMode::ZeropageXIndexed, Operand::Byte(operand)) => {
+            format!("{mnemonic} ${operand:02X},X @ {address:02X} = {value:02X}")
+        }
+        (AddressingMode::ZeropageYIndexed, Operand::Byte(operand)) => {
+            format!("{mnemonic} ${operand:02X},Y @ {address:02X} = {value:02X}")
+        }
+        (AddressingMode::Absolute, Operand::Word(operand)) => match instruction.instruction {
+            Instruction::Jump(_) | Instruction::JumpSubroutine(_) => format!("{mnemonic} ${operand:04X}"),
+            _ => format!("{mnemonic} ${operand:04X} = {value:02X}"),
+        },
+        (AddressingMode::AbsoluteXIndexed, Operand::Word(operand)) => {
+            format!("{mnemonic} ${operand:04X},X @ {address:04X} = {value:02X}")
+        }
+        (AddressingMode::AbsoluteYIndexed, Operand::Word(operand)) => {
+            format!("{mnemonic} ${operand:04X},Y @ {address:04X} = {value:02X}")
+        }
+        (AddressingMode::Indirect, Operand::Word(operand)) => {
+            format!("{mnemonic} (${operand:04X}) = {address:04X}")
+        }
+        (AddressingMode::XIndexedIndirect, Operand::Byte(operand)) => {
+            let pointer = operand.wrapping_add(cpu.x);
+            format!("{mnemonic} (${operand:02X},X) @ {pointer:02X} = {address:04X} = {value:02X}")
+        }
+        (AddressingMode::IndirectYIndexed, Operand::Byte(operand)) => {
+            let base = address.wrapping_sub(cpu.y as u16);
+            format!("{mnemonic} (${operand:02X}),Y = {base:04X} @ {address:04X} = {value:02X}")
+        }
+        _ => instruction.to_string(),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /* One-based line number of the first line that differs */
+    pub line: usize,
+    pub produced: Option<String>,
+    pub reference: Option<String>,
+}
+
+/* Compares two traces line by line, ignoring line endings and trailing whitespace */
+pub fn diff(produced: impl BufRead, reference: impl BufRead) -> io::Result<Option<Divergence>> {
+    let mut produced = produced.lines();
+    let mut reference = reference.lines();
+    let mut line = 0;
+
+    loop {
+        line += 1;
+        let left = produced.next().transpose()?;
+        let right = reference.next().transpose()?;
+
+        match (&left, &right) {
+            (None, None) => return Ok(None),
+            (Some(left), Some(right)) if left.trim_end() == right.trim_end() => continue,
+            _ => return Ok(Some(Divergence { line, produced: left, reference: right })),
+        }
+    }
+}
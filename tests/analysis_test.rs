@@ -0,0 +1,125 @@
+use m6052_emulator::analysis::{Analyzer, ByteKind, Edge, EdgeKind};
+use m6052_emulator::ram::Ram;
+use m6052_emulator::symbols::SymbolTable;
+
+/*
+ * 0200  LDX #$00        reset
+ * 0202  LDA $0210,X
+ * 0205  BEQ $020D
+ * 0207  JSR $0214
+ * 020A  INX
+ * 020B  BNE $0202
+ * 020D  JMP $020D
+ * 0210  .byte 1, 2, 3, 0
+ * 0214  STA $D000
+ * 0217  RTS
+ * 0218  RTI             nmi and irq
+ */
+fn program() -> Ram {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[
+        0xA2, 0x00, 0xBD, 0x10, 0x02, 0xF0, 0x06, 0x20, 0x14, 0x02, 0xE8, 0xD0, 0xF5, 0x4C, 0x0D, 0x02,
+        0x01, 0x02, 0x03, 0x00, 0x8D, 0x00, 0xD0, 0x60, 0x40,
+    ]);
+    ram.load(0xFFFA, &[0x18, 0x02, 0x00, 0x02, 0x18, 0x02]);
+    ram
+}
+
+fn edge(target: u16, kind: EdgeKind) -> Edge {
+    Edge { target, kind }
+}
+
+#[test]
+fn separate_code_from_data() {
+    let ram = program();
+    let analysis = Analyzer::new().analyze(&ram);
+
+    assert_eq!(analysis.kind(0x0200), ByteKind::Opcode);
+    assert_eq!(analysis.kind(0x0201), ByteKind::Operand);
+    assert_eq!(analysis.kind(0x0210), ByteKind::Data);
+    assert_eq!(analysis.kind(0x0211), ByteKind::Unknown);
+    assert_eq!(analysis.kind(0x0218), ByteKind::Opcode);
+    assert!(analysis.is_code(0x0216));
+    assert!(!analysis.is_code(0x0212));
+    assert!(analysis.instruction(0x0211).is_none());
+}
+
+#[test]
+fn blocks_and_subroutines() {
+    let ram = program();
+    let analysis = Analyzer::new().analyze(&ram);
+
+    assert_eq!(analysis.blocks.keys().copied().collect::<Vec<_>>(), vec![0x0200, 0x0202, 0x0207, 0x020D, 0x0214, 0x0218]);
+    assert_eq!(analysis.blocks[&0x0200].successors, vec![edge(0x0202, EdgeKind::Fallthrough)]);
+    assert_eq!(analysis.blocks[&0x0202].successors,
+               vec![edge(0x020D, EdgeKind::Branch), edge(0x0207, EdgeKind::Fallthrough)]);
+    assert_eq!(analysis.blocks[&0x0207].instructions, vec![0x0207, 0x020A, 0x020B]);
+    assert_eq!(analysis.blocks[&0x0207].calls, vec![0x0214]);
+    assert_eq!(analysis.blocks[&0x020D].successors, vec![edge(0x020D, EdgeKind::Jump)]);
+    assert!(analysis.blocks[&0x0214].successors.is_empty());
+    assert_eq!(analysis.block_of(0x020A).map(|block| block.start), Some(0x0207));
+
+    assert_eq!(analysis.subroutines.keys().copied().collect::<Vec<_>>(), vec![0x0200, 0x0214, 0x0218]);
+    assert_eq!(analysis.subroutines[&0x0200].blocks, vec![0x0200, 0x0202, 0x0207, 0x020D]);
+    assert_eq!(analysis.subroutines[&0x0214].callers, vec![0x0207]);
+}
+
+#[test]
+fn entry_points_without_vectors() {
+    let mut ram = program();
+    ram.load(0x0300, &[0xA9, 0x01, 0x02, 0xEA]);
+    let mut analyzer = Analyzer::new();
+    analyzer.use_vectors = false;
+    analyzer.entry_points.push(0x0300);
+    let analysis = analyzer.analyze(&ram);
+
+    assert_eq!(analysis.blocks.keys().copied().collect::<Vec<_>>(), vec![0x0300]);
+    /* Decoding stops at the illegal opcode */
+    assert_eq!(analysis.kind(0x0302), ByteKind::Unknown);
+    assert_eq!(analysis.kind(0x0303), ByteKind::Unknown);
+}
+
+#[test]
+fn annotated_listing() {
+    let ram = program();
+    let analysis = Analyzer::new().analyze(&ram);
+    let mut symbols = SymbolTable::new();
+    symbols.insert("main", 0x0200);
+
+    assert_eq!(analysis.listing(&ram, 0x0200, 0x0218, Some(&symbols)), "\
+; entry point
+main:
+.0200  A2 00     LDX #$00
+L_0202:
+.0202  BD 10 02  LDA $0210,X
+.0205  F0 06     BEQ $020D
+L_0207:
+.0207  20 14 02  JSR $0214
+.020A  E8        INX
+.020B  D0 F5     BNE $0202
+L_020D:
+.020D  4C 0D 02  JMP $020D
+.0210  01 02 03  .byte $01,$02,$03
+.0213  00        .byte $00
+; called from $0207
+sub_0214:
+.0214  8D 00 D0  STA $D000
+.0217  60        RTS
+; entry point
+sub_0218:
+.0218  40        RTI");
+}
+
+#[test]
+fn control_flow_graph() {
+    let ram = program();
+    let dot = Analyzer::new().analyze(&ram).to_dot(None);
+
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert!(dot.contains("    b0214 [label=\"sub_0214:\\l0214  STA $D000\\l0217  RTS\\l\"];\n"));
+    assert!(dot.contains("    b0202 -> b020D [label=\"taken\"];\n"));
+    assert!(dot.contains("    b0202 -> b0207;\n"));
+    assert!(dot.contains("    b020D -> b020D [style=bold];\n"));
+    assert!(dot.contains("    b0207 -> b0214 [style=dashed, label=\"call\"];\n"));
+    assert!(dot.ends_with("}\n"));
+}
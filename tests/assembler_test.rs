@@ -0,0 +1,72 @@
+use m6052_emulator::assembler::{assemble, parse_address, parse_hex_address, parse_number};
+use m6052_emulator::symbols::SymbolTable;
+
+#[test]
+fn assemble_addressing_modes() {
+    assert_eq!(assemble("NOP", 0x0200, None), Ok(vec![0xEA]));
+    assert_eq!(assemble("asl", 0x0200, None), Ok(vec![0x0A]));
+    assert_eq!(assemble("ASL A", 0x0200, None), Ok(vec![0x0A]));
+    assert_eq!(assemble("LDA #$42", 0x0200, None), Ok(vec![0xA9, 0x42]));
+    assert_eq!(assemble("LDA #%101", 0x0200, None), Ok(vec![0xA9, 0x05]));
+    assert_eq!(assemble("LDA #10", 0x0200, None), Ok(vec![0xA9, 0x0A]));
+    assert_eq!(assemble("LDA $FB", 0x0200, None), Ok(vec![0xA5, 0xFB]));
+    assert_eq!(assemble("LDA $FB,X", 0x0200, None), Ok(vec![0xB5, 0xFB]));
+    assert_eq!(assemble("LDX $FB,Y", 0x0200, None), Ok(vec![0xB6, 0xFB]));
+    assert_eq!(assemble("LDA $1234", 0x0200, None), Ok(vec![0xAD, 0x34, 0x12]));
+    assert_eq!(assemble("LDA $1234,X", 0x0200, None), Ok(vec![0xBD, 0x34, 0x12]));
+    assert_eq!(assemble("LDA $1234,Y", 0x0200, None), Ok(vec![0xB9, 0x34, 0x12]));
+    assert_eq!(assemble("LDA ($FB,X)", 0x0200, None), Ok(vec![0xA1, 0xFB]));
+    assert_eq!(assemble("LDA ($FB),Y", 0x0200, None), Ok(vec![0xB1, 0xFB]));
+    assert_eq!(assemble("JMP ($1234)", 0x0200, None), Ok(vec![0x6C, 0x34, 0x12]));
+}
+
+#[test]
+fn assemble_prefers_absolute_when_there_is_no_zeropage_mode() {
+    assert_eq!(assemble("JMP $0010", 0x0200, None), Ok(vec![0x4C, 0x10, 0x00]));
+    assert_eq!(assemble("LDA $10,Y", 0x0200, None), Ok(vec![0xB9, 0x10, 0x00]));
+}
+
+#[test]
+fn assemble_branches() {
+    assert_eq!(assemble("BNE $0210", 0x0200, None), Ok(vec![0xD0, 0x0E]));
+    assert_eq!(assemble("BEQ $0200", 0x0200, None), Ok(vec![0xF0, 0xFE]));
+    assert!(assemble("BCC $0300", 0x0200, None).is_err());
+}
+
+#[test]
+fn assemble_symbols() {
+    let mut symbols = SymbolTable::new();
+    symbols.insert("pointer", 0x00FB);
+    symbols.insert("Draw", 0x0400);
+
+    assert_eq!(assemble("LDA (pointer),Y", 0x0200, Some(&symbols)), Ok(vec![0xB1, 0xFB]));
+    assert_eq!(assemble("JSR Draw", 0x0200, Some(&symbols)), Ok(vec![0x20, 0x00, 0x04]));
+}
+
+#[test]
+fn assemble_errors() {
+    assert!(assemble("FOO", 0x0200, None).is_err());
+    assert!(assemble("LDA", 0x0200, None).is_err());
+    assert!(assemble("STA #$10", 0x0200, None).is_err());
+    assert!(assemble("LDA #$100", 0x0200, None).is_err());
+    assert!(assemble("JSR missing", 0x0200, None).is_err());
+}
+
+#[test]
+fn parse_numbers() {
+    assert_eq!(parse_number("$FF"), Some(0xFF));
+    assert_eq!(parse_number("0x1_0"), None);
+    assert_eq!(parse_number("0x10000"), Some(0x10000));
+    assert_eq!(parse_number("%101"), Some(5));
+    assert_eq!(parse_number("42"), Some(42));
+    assert_eq!(parse_address("$C000"), Some(0xC000));
+    assert_eq!(parse_address("65536"), None);
+    assert_eq!(parse_address("start"), None);
+    assert_eq!(parse_hex_address("0200"), Some(0x0200));
+    assert_eq!(parse_hex_address("$F001"), Some(0xF001));
+    assert_eq!(parse_hex_address("0xF001"), Some(0xF001));
+    assert_eq!(parse_hex_address("+512"), Some(0x0200));
+    assert_eq!(parse_hex_address("%11"), Some(3));
+    assert_eq!(parse_hex_address("10000"), None);
+    assert_eq!(parse_hex_address("+-1"), None);
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use std::borrow::BorrowMut;
+use std::cell::RefCell;
+use std::io::ErrorKind;
+use std::rc::Rc;
+use common::TestMemory;
+use m6052_emulator::binary_trace::{find_executions, find_last_write, seek, BinaryTraceWriter, TraceReader};
+use m6052_emulator::call_stack::FrameKind;
+use m6052_emulator::cheats::{CheatCode, CheatMemory};
+use m6052_emulator::cpu::{CPU, Registers};
+use m6052_emulator::hooks::HookAction;
+use m6052_emulator::observer::{Access, AccessKind};
+use m6052_emulator::ram::Ram;
+
+/* LDA #$42; STA $10; TAX; LDA #$43; STA $10 */
+fn record_trace() -> Vec<u8> {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xA9, 0x42, 0x85, 0x10, 0xAA, 0xA9, 0x43, 0x85, 0x10]);
+
+    let writer = Rc::new(RefCell::new(BinaryTraceWriter::new(Vec::new())));
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.observers.push(Box::new(writer.clone()));
+
+    let mut cycles = 12;
+    cpu.run(&mut cycles);
+    assert_eq!(cycles, 0);
+    drop(cpu);
+
+    let trace = writer.borrow().get_ref().clone();
+    trace
+}
+
+#[test]
+fn trace_is_compact() {
+    let trace = record_trace();
+
+    /* Header: magic, version, registers, cycle count and the memory image */
+    let header = 8 + 1 + 7 + 8 + 0x10000;
+    /* LDA and TAX: mask, opcode, cycles, one register; STA: mask, opcode, cycles, one access */
+    assert_eq!(trace.len(), header + 4 + 8 + 4 + 4 + 8);
+}
+
+#[test]
+fn trace_replays_every_step() {
+    let trace = record_trace();
+    let mut reader = TraceReader::new(trace.as_slice()).unwrap();
+
+    let first = reader.next_step().unwrap().unwrap();
+    assert_eq!(first.index, 0);
+    assert_eq!(first.opcode, 0xA9);
+    assert_eq!(first.cycles, 2);
+    assert_eq!(first.after.accumulator, 0x42);
+    assert_eq!(first.after.program_counter, 0x0202);
+
+    let second = reader.next_step().unwrap().unwrap();
+    assert_eq!(second.total_cycles, 2);
+    assert_eq!(second.accesses, vec![Access { kind: AccessKind::Write, address: 0x0010, value: 0x42 }]);
+    assert_eq!(reader.memory()[0x0010], 0x42);
+
+    let third = reader.next_step().unwrap().unwrap();
+    assert_eq!(third.after.x, 0x42);
+
+    assert!(reader.next_step().unwrap().is_some());
+    assert!(reader.next_step().unwrap().is_some());
+    assert!(reader.next_step().unwrap().is_none());
+    assert_eq!(reader.total_cycles(), 12);
+}
+
+#[test]
+fn query_last_write() {
+    let trace = record_trace();
+    let mut reader = TraceReader::new(trace.as_slice()).unwrap();
+
+    assert_eq!(find_last_write(&mut reader, 0x0010).unwrap(), Some((4, 0x43)));
+}
+
+#[test]
+fn query_executions() {
+    let trace = record_trace();
+    let mut reader = TraceReader::new(trace.as_slice()).unwrap();
+
+    assert_eq!(find_executions(&mut reader, 0x0204).unwrap(), vec![2]);
+}
+
+#[test]
+fn query_state() {
+    let trace = record_trace();
+    let mut reader = TraceReader::new(trace.as_slice()).unwrap();
+
+    assert!(seek(&mut reader, 3).unwrap());
+    assert_eq!(reader.registers(), Registers {
+        program_counter: 0x0205,
+        accumulator: 0x42,
+        x: 0x42,
+        y: 0x00,
+        stack_pointer: 0xFF,
+        status: 0x20,
+    });
+    assert_eq!(reader.total_cycles(), 7);
+    assert_eq!(reader.memory()[0x0010], 0x42);
+
+    assert!(!seek(&mut reader, 10).unwrap());
+}
+
+#[test]
+fn rejects_other_files() {
+    assert!(TraceReader::new(&b"not a trace at all"[..]).is_err());
+}
+
+#[test]
+fn replays_interrupts_and_register_edits() {
+    /* LDA #$42; STA $10; NOP; NOP with an IRQ handler at $0300 doing LDA #$99; STA $11; RTI */
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xA9, 0x42, 0x85, 0x10, 0xEA, 0xEA]);
+    memory.load(0x0300, &[0xA9, 0x99, 0x85, 0x11, 0x40]);
+    memory.load(0xFFFE, &[0x00, 0x03]);
+
+    let writer = Rc::new(RefCell::new(BinaryTraceWriter::new(Vec::new())));
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.observers.push(Box::new(writer.clone()));
+
+    let mut cycles = 5;
+    cpu.run(&mut cycles);
+    /* An edit from a debugger between two instructions */
+    cpu.x = 0x07;
+    let mut cycles = 0;
+    cpu.interrupt_request(&mut cycles);
+    assert_eq!(cpu.program_counter, 0x0300);
+    let mut cycles = 13;
+    cpu.run(&mut cycles);
+
+    let registers = cpu.registers();
+    let total_cycles = cpu.total_cycles;
+    drop(cpu);
+
+    let trace = writer.borrow().get_ref().clone();
+    let mut reader = TraceReader::new(trace.as_slice()).unwrap();
+    let mut steps = Vec::new();
+    while let Some(step) = reader.next_step().unwrap() {
+        steps.push(step);
+    }
+
+    let interrupt = &steps[2];
+    assert_eq!(interrupt.interrupt, Some(FrameKind::Interrupt));
+    assert_eq!(interrupt.before.program_counter, 0x0204);
+    assert_eq!(interrupt.before.x, 0x07);
+    assert_eq!(interrupt.after.program_counter, 0x0300);
+    assert_eq!(interrupt.total_cycles, 5);
+    assert_eq!(interrupt.cycles, 7);
+    let pushed: Vec<u8> = interrupt.accesses.iter()
+        .filter(|access| access.kind == AccessKind::Write)
+        .map(|access| access.value)
+        .collect();
+    assert_eq!(pushed, vec![0x02, 0x04, 0x20]);
+
+    assert_eq!(steps[5].opcode, 0x40);
+    assert_eq!(steps[5].after.program_counter, 0x0204);
+    assert_eq!(steps.iter().filter(|step| step.interrupt.is_none()).count(), steps.len() - 1);
+
+    assert_eq!(reader.registers(), registers);
+    assert_eq!(reader.total_cycles(), total_cycles);
+    assert_eq!(reader.memory()[0x0010], 0x42);
+    assert_eq!(reader.memory()[0x0011], 0x99);
+    assert_eq!(find_executions(&mut TraceReader::new(trace.as_slice()).unwrap(), 0x0204).unwrap(), vec![6]);
+}
+
+#[test]
+fn header_holds_stored_bytes() {
+    let mut memory = CheatMemory::new(Ram::new());
+    /* LDA #$01 */
+    memory.inner.load(0x0200, &[0xA9, 0x01]);
+    memory.inner.load(0x0010, &[0x05]);
+    RefCell::borrow_mut(&memory.cheats).add(CheatCode::parse("0010:42").unwrap());
+
+    let writer = Rc::new(RefCell::new(BinaryTraceWriter::new(Vec::new())));
+    let mut cpu = CPU::new(&mut memory);
+    cpu.observers.push(Box::new(writer.clone()));
+    cpu.step(&mut 100);
+    drop(cpu);
+
+    let trace = writer.borrow().get_ref().clone();
+    let reader = TraceReader::new(trace.as_slice()).unwrap();
+    assert_eq!(reader.memory()[0x0010], 0x05);
+}
+
+#[test]
+fn replays_edits_between_instructions() {
+    /* $0200: JSR $0300; LDA $10; LDX $11 with a hook at $0300 in place of the subroutine */
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0x20, 0x00, 0x03, 0xA5, 0x10, 0xA6, 0x11]);
+
+    let writer = Rc::new(RefCell::new(BinaryTraceWriter::new(Vec::new())));
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.hooks.add(0x0300, |cpu: &mut CPU| {
+        cpu.memory.set(0x0010, 0x55);
+        HookAction::Return
+    });
+    cpu.observers.push(Box::new(writer.clone()));
+    cpu.run_until(&mut 100, |cpu| cpu.program_counter == 0x0205);
+    /* An edit from a debugger between two instructions */
+    cpu.edit(0x0011, 0x66);
+    cpu.step(&mut 100);
+    assert_eq!((cpu.accumulator, cpu.x), (0x55, 0x66));
+    drop(cpu);
+
+    let trace = writer.borrow().get_ref().clone();
+    let mut reader = TraceReader::new(trace.as_slice()).unwrap();
+    while reader.next_step().unwrap().is_some() {}
+    assert_eq!(reader.index(), 4);
+    assert_eq!(reader.registers().x, 0x66);
+    assert_eq!(reader.memory()[0x0010], 0x55);
+    assert_eq!(reader.memory()[0x0011], 0x66);
+}
+
+#[test]
+fn writer_stops_at_write_errors() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xA9, 0x42, 0x85, 0x10]);
+
+    /* Room for half of the memory image */
+    let mut buffer = vec![0; 0x8000];
+    let writer = Rc::new(RefCell::new(BinaryTraceWriter::new(&mut buffer[..])));
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.observers.push(Box::new(writer.clone()));
+    cpu.run(&mut 5);
+    assert_eq!(cpu.memory.get(0x0010), 0x42);
+    drop(cpu);
+
+    assert_eq!(writer.borrow().error().map(|error| error.kind()), Some(ErrorKind::WriteZero));
+}
@@ -0,0 +1,265 @@
+mod common;
+
+use std::borrow::BorrowMut;
+use common::TestMemory;
+use m6052_emulator::breakpoint::{BreakpointKind, HitCondition, LogMessage, StopReason, MESSAGE_LIMIT};
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::expression::Expression;
+use m6052_emulator::observer::{Access, AccessKind};
+
+/* LDA #$01; STA $10; LDA $10; TAX; BRK */
+fn program() -> TestMemory {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xA9, 0x01, 0x85, 0x10, 0xA5, 0x10, 0xAA, 0x00]);
+    memory.load(0xFFFE, &[0x00, 0x30]);
+    memory
+}
+
+#[test]
+fn execute_breakpoint() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Execute(0x0204));
+
+    let mut cycles = 100;
+    assert_eq!(cpu.run(&mut cycles), Some(StopReason::Breakpoint { id, address: 0x0204 }));
+    assert_eq!(cpu.program_counter, 0x0204);
+    assert_eq!(cycles, 95);
+    assert_eq!(cpu.breakpoints.get(id).unwrap().hits, 1);
+}
+
+#[test]
+fn resume_from_breakpoint() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.breakpoints.add(BreakpointKind::Execute(0x0204));
+    let brk = cpu.breakpoints.add(BreakpointKind::Break);
+
+    let mut cycles = 100;
+    cpu.run(&mut cycles);
+    assert_eq!(cpu.run(&mut cycles), Some(StopReason::Breakpoint { id: brk, address: 0x0207 }));
+    assert_eq!(cpu.x, 0x01);
+}
+
+#[test]
+fn disabled_breakpoint() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Execute(0x0204));
+    cpu.breakpoints.set_enabled(id, false);
+
+    let mut cycles = 10;
+    assert_eq!(cpu.run(&mut cycles), None);
+    assert_eq!(cpu.breakpoints.get(id).unwrap().hits, 0);
+}
+
+#[test]
+fn opcode_breakpoint() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Opcode(0xAA));
+
+    let mut cycles = 100;
+    assert_eq!(cpu.run(&mut cycles), Some(StopReason::Breakpoint { id, address: 0x0206 }));
+}
+
+#[test]
+fn write_watchpoint() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Write(0x0000, 0x00FF));
+
+    let mut cycles = 100;
+    assert_eq!(cpu.run(&mut cycles), Some(StopReason::Watchpoint {
+        id,
+        access: Access { kind: AccessKind::Write, address: 0x0010, value: 0x01 },
+    }));
+    /* The instruction that wrote completes before the cpu stops */
+    assert_eq!(cpu.program_counter, 0x0204);
+}
+
+#[test]
+fn read_watchpoint_ignores_writes() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Read(0x0010, 0x0010));
+
+    let mut cycles = 100;
+    assert_eq!(cpu.run(&mut cycles), Some(StopReason::Watchpoint {
+        id,
+        access: Access { kind: AccessKind::Read, address: 0x0010, value: 0x01 },
+    }));
+    assert_eq!(cpu.program_counter, 0x0206);
+}
+
+#[test]
+fn access_watchpoint_counts_hits() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Access(0x0010, 0x0010));
+
+    let mut cycles = 100;
+    cpu.run(&mut cycles);
+    cpu.run(&mut cycles);
+    assert_eq!(cpu.program_counter, 0x0206);
+    assert_eq!(cpu.breakpoints.get(id).unwrap().hits, 2);
+}
+
+#[test]
+fn step_reports_watchpoint() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.breakpoints.add(BreakpointKind::Write(0x0010, 0x0010));
+
+    let mut cycles = 100;
+    assert_eq!(cpu.step(&mut cycles), None);
+    assert!(matches!(cpu.step(&mut cycles), Some(StopReason::Watchpoint { .. })));
+}
+
+#[test]
+fn interrupt_breakpoint() {
+    let mut memory = program();
+    memory.load(0xFFFA, &[0x00, 0x40]);
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Interrupt);
+
+    let mut cycles = 100;
+    cpu.non_maskable_interrupt(&mut cycles);
+    assert_eq!(cycles, 93);
+    assert_eq!(cpu.run(&mut cycles), Some(StopReason::Interrupt { id, vector: 0xFFFA }));
+    assert_eq!(cpu.program_counter, 0x4000);
+    assert_eq!(cpu.memory.get(0x01FD) & 0x10, 0);
+}
+
+#[test]
+fn masked_interrupt_request() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.interrupt = true;
+
+    let mut cycles = 100;
+    cpu.interrupt_request(&mut cycles);
+    assert_eq!(cycles, 100);
+    assert_eq!(cpu.program_counter, 0x0200);
+}
+
+#[test]
+fn remove_breakpoint() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Break);
+
+    assert!(cpu.breakpoints.remove(id));
+    assert!(!cpu.breakpoints.remove(id));
+    assert!(cpu.breakpoints.is_empty());
+}
+
+/* LDA #$01; STA $10; LDA #$02; STA $10; LDA #$03; STA $10 */
+fn stores() -> TestMemory {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xA9, 0x01, 0x85, 0x10, 0xA9, 0x02, 0x85, 0x10, 0xA9, 0x03, 0x85, 0x10]);
+    memory
+}
+
+#[test]
+fn conditional_breakpoint() {
+    let mut memory = stores();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Opcode(0x85));
+    cpu.breakpoints.set_condition(id, Some(Expression::parse("A == 2 && [$10] == 1", None).unwrap()));
+
+    let mut cycles = 100;
+    assert_eq!(cpu.run(&mut cycles), Some(StopReason::Breakpoint { id, address: 0x0206 }));
+    assert_eq!(cpu.breakpoints.get(id).unwrap().hits, 1);
+}
+
+#[test]
+fn conditional_watchpoint() {
+    let mut memory = stores();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Write(0x0010, 0x0010));
+    cpu.breakpoints.set_condition(id, Some(Expression::parse("A > 2", None).unwrap()));
+
+    let mut cycles = 100;
+    assert!(matches!(cpu.run(&mut cycles), Some(StopReason::Watchpoint { .. })));
+    assert_eq!(cpu.program_counter, 0x020C);
+}
+
+#[test]
+fn hit_condition() {
+    let mut memory = stores();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Opcode(0x85));
+    cpu.breakpoints.set_hit_condition(id, Some(HitCondition::Equal(3)));
+
+    let mut cycles = 100;
+    assert_eq!(cpu.run(&mut cycles), Some(StopReason::Breakpoint { id, address: 0x020A }));
+    assert_eq!(cpu.breakpoints.get(id).unwrap().hits, 3);
+}
+
+#[test]
+fn tracepoint_logs_without_stopping() {
+    let mut memory = stores();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Opcode(0x85));
+    cpu.breakpoints.set_log_message(id, Some(LogMessage::parse("store {A} at {PC:x}", None).unwrap()));
+
+    let mut cycles = 15;
+    assert_eq!(cpu.run(&mut cycles), None);
+    assert_eq!(cpu.breakpoints.take_messages(), vec![
+        "store 1 at $202", "store 2 at $206", "store 3 at $20A",
+    ]);
+    assert!(cpu.breakpoints.take_messages().is_empty());
+}
+
+#[test]
+fn tracepoint_logs_on_steps_and_resume() {
+    let mut memory = stores();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Opcode(0x85));
+    cpu.breakpoints.set_log_message(id, Some(LogMessage::parse("store {A}", None).unwrap()));
+    let stop = cpu.breakpoints.add(BreakpointKind::Execute(0x0206));
+
+    let mut cycles = 100;
+    assert_eq!(cpu.run(&mut cycles), Some(StopReason::Breakpoint { id: stop, address: 0x0206 }));
+    assert_eq!(cpu.breakpoints.take_messages(), vec!["store 1"]);
+
+    assert_eq!(cpu.step(&mut cycles), None);
+    assert_eq!(cpu.breakpoints.take_messages(), vec!["store 2"]);
+
+    let mut cycles = 5;
+    assert_eq!(cpu.run(&mut cycles), None);
+    assert_eq!(cpu.breakpoints.take_messages(), vec!["store 3"]);
+}
+
+#[test]
+fn tracepoint_messages_are_capped() {
+    /* JMP $0200 */
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0x4C, 0x00, 0x02]);
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Execute(0x0200));
+    cpu.breakpoints.set_log_message(id, Some(LogMessage::parse("loop", None).unwrap()));
+
+    let mut cycles = 3 * (MESSAGE_LIMIT as isize + 10);
+    cpu.run(&mut cycles);
+    assert_eq!(cpu.breakpoints.get(id).unwrap().hits, MESSAGE_LIMIT as u64 + 10);
+    assert_eq!(cpu.breakpoints.take_messages().len(), MESSAGE_LIMIT);
+}
+
+#[test]
+fn stop_in_front_of_illegal_opcodes() {
+    /* LDA #$01 followed by an illegal opcode */
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xA9, 0x01, 0x02]);
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    let mut cycles = 100;
+    assert_eq!(cpu.run(&mut cycles), Some(StopReason::IllegalOpcode { address: 0x0202 }));
+    assert_eq!(cpu.accumulator, 0x01);
+    assert_eq!(cycles, 98);
+
+    assert_eq!(cpu.step(&mut cycles), Some(StopReason::IllegalOpcode { address: 0x0202 }));
+    assert_eq!(cpu.step_over(&mut cycles), Some(StopReason::IllegalOpcode { address: 0x0202 }));
+    assert_eq!((cpu.program_counter, cpu.total_cycles, cycles), (0x0202, 2, 98));
+}
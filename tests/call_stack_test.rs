@@ -0,0 +1,165 @@
+mod common;
+
+use std::borrow::BorrowMut;
+use common::TestMemory;
+use m6052_emulator::breakpoint::{BreakpointKind, StopReason};
+use m6052_emulator::call_stack::{Frame, FrameKind};
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::symbols::SymbolTable;
+
+/*
+ * $0200: JSR $0300; LDX #$05; BRK
+ * $0300: JSR $0400; LDA #$01; RTS
+ * $0400: LDY #$02; RTS
+ */
+fn program() -> TestMemory {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0x20, 0x00, 0x03, 0xA2, 0x05, 0x00]);
+    memory.load(0x0300, &[0x20, 0x00, 0x04, 0xA9, 0x01, 0x60]);
+    memory.load(0x0400, &[0xA0, 0x02, 0x60]);
+    memory
+}
+
+#[test]
+fn frames_follow_calls() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    let mut cycles = 100;
+    cpu.step(&mut cycles);
+    cpu.step(&mut cycles);
+
+    assert_eq!(cpu.call_stack.frames(), &[
+        Frame { kind: FrameKind::Subroutine, caller: 0x0200, target: 0x0300, return_address: 0x0203, stack_pointer: 0xFD },
+        Frame { kind: FrameKind::Subroutine, caller: 0x0300, target: 0x0400, return_address: 0x0303, stack_pointer: 0xFB },
+    ]);
+
+    cpu.step(&mut cycles);
+    cpu.step(&mut cycles);
+    assert_eq!(cpu.program_counter, 0x0303);
+    assert_eq!(cpu.call_stack.depth(), 1);
+}
+
+#[test]
+fn step_over_subroutine() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    let mut cycles = 100;
+    assert_eq!(cpu.step_over(&mut cycles), None);
+    assert_eq!(cpu.program_counter, 0x0203);
+    assert_eq!(cpu.accumulator, 0x01);
+    assert_eq!(cpu.y, 0x02);
+    assert_eq!(cpu.call_stack.depth(), 0);
+
+    cpu.step_over(&mut cycles);
+    assert_eq!(cpu.program_counter, 0x0205);
+    assert_eq!(cpu.x, 0x05);
+}
+
+#[test]
+fn step_over_stops_at_breakpoint() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Execute(0x0400));
+
+    let mut cycles = 100;
+    assert_eq!(cpu.step_over(&mut cycles), Some(StopReason::Breakpoint { id, address: 0x0400 }));
+    assert_eq!(cpu.call_stack.depth(), 2);
+}
+
+#[test]
+fn step_over_stops_at_subroutine_entry() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+    let id = cpu.breakpoints.add(BreakpointKind::Execute(0x0300));
+
+    let mut cycles = 100;
+    assert_eq!(cpu.step_over(&mut cycles), Some(StopReason::Breakpoint { id, address: 0x0300 }));
+    assert_eq!(cpu.program_counter, 0x0300);
+    assert_eq!(cpu.call_stack.depth(), 1);
+}
+
+#[test]
+fn step_out_of_subroutine() {
+    let mut memory = program();
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    let mut cycles = 100;
+    cpu.step(&mut cycles);
+    cpu.step(&mut cycles);
+    assert_eq!(cpu.program_counter, 0x0400);
+
+    assert_eq!(cpu.step_out(&mut cycles), None);
+    assert_eq!(cpu.program_counter, 0x0303);
+    assert_eq!(cpu.y, 0x02);
+    assert_eq!(cpu.accumulator, 0x00);
+
+    cpu.step_out(&mut cycles);
+    assert_eq!(cpu.program_counter, 0x0203);
+    assert_eq!(cpu.accumulator, 0x01);
+}
+
+#[test]
+fn step_out_of_interrupt() {
+    let mut memory = program();
+    memory.load(0xFFFA, &[0x00, 0x05]);
+    /* $0500: LDX #$07; RTI */
+    memory.load(0x0500, &[0xA2, 0x07, 0x40]);
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    let mut cycles = 100;
+    cpu.step(&mut cycles);
+    cpu.non_maskable_interrupt(&mut cycles);
+    assert_eq!(cpu.call_stack.frames().last().unwrap().kind, FrameKind::NonMaskableInterrupt);
+
+    cpu.step_out(&mut cycles);
+    assert_eq!(cpu.program_counter, 0x0300);
+    assert_eq!(cpu.x, 0x07);
+    assert_eq!(cpu.call_stack.depth(), 1);
+}
+
+#[test]
+fn return_through_pushed_address() {
+    let mut memory = TestMemory::new();
+    /* $0200: JSR $0300 */
+    memory.load(0x0200, &[0x20, 0x00, 0x03]);
+    /* $0300: LDA #$12; PHA; LDA #$33; PHA; RTS */
+    memory.load(0x0300, &[0xA9, 0x12, 0x48, 0xA9, 0x33, 0x48, 0x60]);
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    let mut cycles = 100;
+    for _ in 0..6 {
+        cpu.step(&mut cycles);
+    }
+    assert_eq!(cpu.program_counter, 0x1234);
+    assert_eq!(cpu.call_stack.depth(), 1);
+    assert_eq!(cpu.call_stack.frames()[0].target, 0x0300);
+}
+
+#[test]
+fn discarded_return_address() {
+    let mut memory = TestMemory::new();
+    /* $0200: JSR $0300 */
+    memory.load(0x0200, &[0x20, 0x00, 0x03]);
+    /* $0300: PLA; PLA */
+    memory.load(0x0300, &[0x68, 0x68]);
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    let mut cycles = 100;
+    cpu.step(&mut cycles);
+    assert_eq!(cpu.call_stack.depth(), 1);
+    cpu.step(&mut cycles);
+    cpu.step(&mut cycles);
+    assert_eq!(cpu.call_stack.depth(), 0);
+    assert_eq!(cpu.stack_pointer, 0xFF);
+}
+
+#[test]
+fn frame_symbol() {
+    let mut symbols = SymbolTable::new();
+    symbols.insert("draw", 0x0300);
+
+    let frame = Frame { kind: FrameKind::Subroutine, caller: 0x0200, target: 0x0302, return_address: 0x0203, stack_pointer: 0xFD };
+    assert_eq!(frame.symbol(&symbols), Some(("draw", 2)));
+}
@@ -0,0 +1,95 @@
+use m6052_emulator::cheats::{CheatCode, CheatMemory};
+use m6052_emulator::cpu::{CPU, Memory};
+use m6052_emulator::ram::Ram;
+use m6052_emulator::search::{Filter, MemorySearch, Width};
+use m6052_emulator::state::MachineState;
+
+#[test]
+fn parse_codes() {
+    assert_eq!(CheatCode::parse("0010:42"), Ok(CheatCode { address: 0x0010, value: 0x42, compare: None }));
+    assert_eq!(CheatCode::parse("$C000?A9:EA"), Ok(CheatCode { address: 0xC000, value: 0xEA, compare: Some(0xA9) }));
+    assert_eq!(CheatCode::parse("10000:42"), Err("Invalid address '10000'".to_string()));
+    assert_eq!(CheatCode::parse("0010:142"), Err("Invalid byte '142'".to_string()));
+    assert_eq!(CheatCode::parse("SXIOP"), Err("Expected six or eight letters, got 'SXIOP'".to_string()));
+    assert_eq!(CheatCode::parse("SXIOPB"), Err("Invalid Game Genie code 'SXIOPB'".to_string()));
+}
+
+#[test]
+fn decode_game_genie_codes() {
+    assert_eq!(CheatCode::parse("SXIOPO"), Ok(CheatCode { address: 0x91D9, value: 0xAD, compare: None }));
+    assert_eq!(CheatCode::parse("yeuzugaa"), Ok(CheatCode { address: 0xACB3, value: 0x07, compare: Some(0x00) }));
+}
+
+#[test]
+fn freeze_addresses() {
+    let mut memory = CheatMemory::new(Ram::new());
+    /* LDA $10; STA $10; LDX $10 */
+    memory.inner.load(0x0200, &[0xA5, 0x10, 0x85, 0x10, 0xA6, 0x10]);
+    memory.inner.load(0x0010, &[0x01]);
+    let cheats = memory.cheats.clone();
+    let id = cheats.borrow_mut().add(CheatCode::parse("0010:42").unwrap());
+
+    let mut cpu = CPU::new(&mut memory);
+    cpu.step(&mut 100);
+    assert_eq!(cpu.accumulator, 0x42);
+
+    cpu.accumulator = 0x07;
+    cpu.step(&mut 100);
+    assert_eq!(cpu.memory.get(0x0010), 0x42);
+    cheats.borrow_mut().set_enabled(id, false);
+    assert_eq!(cpu.memory.get(0x0010), 0x07);
+    cpu.step(&mut 100);
+    assert_eq!(cpu.x, 0x07);
+}
+
+#[test]
+fn substitute_matching_bytes_only() {
+    let mut memory = CheatMemory::new(Ram::new());
+    memory.inner.load(0xC000, &[0xA9]);
+    memory.cheats.borrow_mut().add(CheatCode::parse("C000?A9:EA").unwrap());
+
+    assert_eq!(memory.read(&mut 1, 0xC000), 0xEA);
+    memory.set(0xC000, 0xA2);
+    assert_eq!(memory.get(0xC000), 0xA2);
+    assert_eq!(memory.inner.get(0xC000), 0xA2);
+
+    memory.cheats.borrow_mut().clear();
+    memory.set(0xC000, 0xA9);
+    assert_eq!(memory.get(0xC000), 0xA9);
+}
+
+#[test]
+fn history_and_snapshots_see_stored_bytes() {
+    let mut memory = CheatMemory::new(Ram::new());
+    /* LDA #$07; STA $10 */
+    memory.inner.load(0x0200, &[0xA9, 0x07, 0x85, 0x10]);
+    memory.inner.load(0x0010, &[0x01]);
+    memory.cheats.borrow_mut().add(CheatCode::parse("0010:42").unwrap());
+
+    let mut cpu = CPU::new(&mut memory);
+    cpu.history.set_capacity(10);
+    let state = MachineState::capture(&cpu);
+    assert_eq!(state.memory[0x0010], 0x01);
+
+    cpu.step(&mut 100);
+    cpu.step(&mut 100);
+    assert_eq!(cpu.memory.get_raw(0x0010), 0x07);
+    assert!(cpu.step_back());
+    assert_eq!(cpu.memory.get_raw(0x0010), 0x01);
+    assert_eq!(cpu.memory.get(0x0010), 0x42);
+}
+
+#[test]
+fn search_stored_bytes() {
+    let mut memory = CheatMemory::new(Ram::new());
+    memory.inner.load(0x0010, &[0x03]);
+    memory.cheats.borrow_mut().add(CheatCode::parse("0010:42").unwrap());
+
+    let mut cheated = MemorySearch::new(&memory, Width::Byte);
+    let mut raw = MemorySearch::raw(&memory, Width::Byte);
+    memory.set(0x0010, 0x02);
+
+    assert_eq!(cheated.filter(&memory, Filter::Changed), Ok(0));
+    assert_eq!(raw.filter(&memory, Filter::DecreasedBy(1)), Ok(1));
+    assert_eq!(raw.value(&memory, 0x0010), 0x02);
+}
@@ -0,0 +1,40 @@
+#![allow(dead_code)]
+
+use m6052_emulator::cpu::Memory;
+
+#[derive(Clone)]
+pub struct TestMemory {
+    data: Vec<u8>,
+}
+
+impl TestMemory {
+    pub fn new() -> TestMemory {
+        TestMemory { data: vec![0; 0x10000] }
+    }
+
+    pub fn load(&mut self, address: u16, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.set(address.wrapping_add(offset as u16), *byte);
+        }
+    }
+}
+
+impl Memory for TestMemory {
+    fn read(&self, cycles: &mut isize, address: u16) -> u8 {
+        *cycles -= 1;
+        self.get(address)
+    }
+
+    fn get(&self, address: u16) -> u8 {
+        self.data[address as usize]
+    }
+
+    fn write(&mut self, cycles: &mut isize, address: u16, value: u8) {
+        *cycles -= 1;
+        self.set(address, value);
+    }
+
+    fn set(&mut self, address: u16, value: u8) {
+        self.data[address as usize] = value;
+    }
+}
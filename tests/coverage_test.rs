@@ -0,0 +1,195 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use m6052_emulator::analysis::{Analyzer, ByteKind, Edge, EdgeKind};
+use m6052_emulator::coverage::{BranchCount, Coverage, CoverageReport};
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::decoder::decode;
+use m6052_emulator::observer::Observer;
+use m6052_emulator::ram::Ram;
+use m6052_emulator::source_map::{SourceLocation, SourceMap};
+
+/*
+ * 0200  LDA #$02
+ * 0202  STA $0301
+ * 0205  JMP ($0300)     through $0210
+ * 0210  LDA $0220
+ * 0213  LDA #$02        return to $0230 with an RTS
+ * 0215  PHA
+ * 0216  LDA #$2F
+ * 0218  PHA
+ * 0219  RTS
+ * 0230  BRK             into $0240
+ * 0240  JMP $0240
+ */
+fn program() -> Ram {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x02, 0x8D, 0x01, 0x03, 0x6C, 0x00, 0x03]);
+    ram.load(0x0210, &[0xAD, 0x20, 0x02, 0xA9, 0x02, 0x48, 0xA9, 0x2F, 0x48, 0x60]);
+    ram.load(0x0220, &[0x42]);
+    ram.load(0x0230, &[0x00]);
+    ram.load(0x0240, &[0x4C, 0x40, 0x02]);
+    ram.load(0x0300, &[0x10, 0x00]);
+    ram.load(0xFFFE, &[0x40, 0x02]);
+    ram
+}
+
+fn record(ram: &mut Ram) -> Coverage {
+    let coverage = Rc::new(RefCell::new(Coverage::new()));
+    let mut cpu = CPU::new(ram);
+    cpu.observers.push(Box::new(coverage.clone()));
+    let mut cycles = 100;
+    cpu.run(&mut cycles);
+    drop(cpu);
+    Rc::try_unwrap(coverage).unwrap().into_inner()
+}
+
+#[test]
+fn record_accesses() {
+    let mut ram = program();
+    let coverage = record(&mut ram);
+
+    assert!(coverage.is_executed(0x0200));
+    assert!(coverage.is_operand(0x0201));
+    assert!(!coverage.is_executed(0x0201));
+    assert!(coverage.is_written(0x0301));
+    assert!(coverage.is_read(0x0300));
+    assert!(coverage.is_read(0x0220));
+    assert!(coverage.is_written(0x01FF));
+    assert!(!coverage.is_executed(0x0208));
+    assert_eq!(coverage.executed().collect::<Vec<_>>(),
+               vec![0x0200, 0x0202, 0x0205, 0x0210, 0x0213, 0x0215, 0x0216, 0x0218, 0x0219, 0x0230, 0x0240]);
+}
+
+#[test]
+fn record_indirect_control_flow() {
+    let mut ram = program();
+    let coverage = record(&mut ram);
+
+    assert_eq!(coverage.jumps.get(&0x0205).map(|targets| targets.iter().copied().collect::<Vec<_>>()),
+               Some(vec![0x0210]));
+    assert_eq!(coverage.jumps.get(&0x0219).map(|targets| targets.iter().copied().collect::<Vec<_>>()),
+               Some(vec![0x0230]));
+    assert_eq!(coverage.interrupts.iter().copied().collect::<Vec<_>>(), vec![0x0240]);
+}
+
+#[test]
+fn record_interrupts_but_not_edits() {
+    let mut ram = program();
+    let coverage = Rc::new(RefCell::new(Coverage::new()));
+    let mut cpu = CPU::new(&mut ram);
+    cpu.observers.push(Box::new(coverage.clone()));
+
+    let mut cycles = 2;
+    cpu.run(&mut cycles);
+    cpu.program_counter = 0x0213;
+    let mut cycles = 2;
+    cpu.run(&mut cycles);
+    cpu.interrupt_request(&mut cycles);
+    let mut cycles = 3;
+    cpu.run(&mut cycles);
+    drop(cpu);
+
+    let coverage = coverage.borrow();
+    assert_eq!(coverage.interrupts.iter().copied().collect::<Vec<_>>(), vec![0x0240]);
+    assert!(coverage.jumps.is_empty());
+    assert_eq!(coverage.executions(0x0240), 1);
+}
+
+#[test]
+fn assist_the_analysis() {
+    let mut ram = program();
+    let coverage = record(&mut ram);
+    let mut analyzer = Analyzer::new();
+    analyzer.use_vectors = false;
+    analyzer.entry_points.push(0x0200);
+
+    let plain = analyzer.analyze(&ram);
+    assert_eq!(plain.kind(0x0210), ByteKind::Unknown);
+    assert_eq!(plain.kind(0x0300), ByteKind::Unknown);
+
+    let analysis = analyzer.analyze_with_coverage(&ram, &coverage);
+    assert_eq!(analysis.kind(0x0210), ByteKind::Opcode);
+    assert_eq!(analysis.kind(0x0230), ByteKind::Opcode);
+    assert_eq!(analysis.kind(0x0300), ByteKind::Data);
+    assert_eq!(analysis.kind(0x0220), ByteKind::Data);
+    assert_eq!(analysis.kind(0x0208), ByteKind::Unknown);
+
+    assert_eq!(analysis.blocks[&0x0200].successors, vec![Edge { target: 0x0210, kind: EdgeKind::Jump }]);
+    assert_eq!(analysis.blocks[&0x0210].successors, vec![Edge { target: 0x0230, kind: EdgeKind::Jump }]);
+    assert!(analysis.subroutines.contains_key(&0x0240));
+}
+
+#[test]
+fn count_branch_outcomes() {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xD0, 0xFE]);
+    let mut coverage = Coverage::new();
+    let mut cpu = CPU::new(&mut ram);
+    let branch = decode(&*cpu.memory, 0x0200).unwrap();
+
+    for target in [0x0200, 0x0202, 0x0200] {
+        coverage.before_instruction(&cpu, &branch);
+        cpu.program_counter = target;
+        coverage.after_instruction(&cpu);
+    }
+
+    assert_eq!(coverage.branches[&0x0200], BranchCount { taken: 2, not_taken: 1 });
+    assert_eq!(coverage.executions(0x0200), 3);
+}
+
+/* One line per entry, covering the addresses from the start up to the end */
+struct Lines(Vec<(u16, u16, u32)>);
+
+impl SourceMap for Lines {
+    fn location(&self, address: u16) -> Option<SourceLocation> {
+        let (_, _, line) = self.0.iter().find(|(start, end, _)| (*start..*end).contains(&address))?;
+        Some(SourceLocation { file: "src/main.s".to_string(), line: *line })
+    }
+
+    fn addresses(&self, _file: &str, line: u32) -> Vec<u16> {
+        self.0.iter().filter(|entry| entry.2 == line).map(|entry| entry.0).collect()
+    }
+}
+
+#[test]
+fn report_by_source_line() {
+    /*
+     * 1  LDA table
+     * 2  INX
+     * 3  BNE *
+     * 4  JMP *
+     * 5  table: .byte $AA
+     * 6  LDY #$03
+     */
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xAD, 0x09, 0x02, 0xE8, 0xD0, 0xFE, 0x4C, 0x06, 0x02, 0xAA, 0xA0, 0x03]);
+    let lines = Lines(vec![(0x0200, 0x0203, 1), (0x0203, 0x0204, 2), (0x0204, 0x0206, 3), (0x0206, 0x0209, 4),
+                           (0x0209, 0x020A, 5), (0x020A, 0x020C, 6)]);
+
+    let coverage = Rc::new(RefCell::new(Coverage::new()));
+    let mut cpu = CPU::new(&mut ram);
+    cpu.observers.push(Box::new(coverage.clone()));
+    let mut cycles = 100;
+    cpu.run_until(&mut cycles, |cpu| cpu.program_counter == 0x0204);
+    drop(cpu);
+
+    let report = CoverageReport::new(&coverage.borrow(), &lines, &ram);
+    assert_eq!(report.files["src/main.s"].keys().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4, 6]);
+    assert_eq!(report.summary(), "Lines: 2/5 (40.0%), branches: 0/2 (0.0%)");
+    assert_eq!(report.to_lcov("unit"), "\
+TN:unit
+SF:src/main.s
+BRDA:3,516,0,-
+BRDA:3,516,1,-
+BRF:2
+BRH:0
+DA:1,1
+DA:2,1
+DA:3,0
+DA:4,0
+DA:6,0
+LF:5
+LH:2
+end_of_record
+");
+}
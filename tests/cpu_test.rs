@@ -1,14 +1,16 @@
+#![allow(clippy::bool_assert_comparison)]
+
 use std::borrow::BorrowMut;
 use m6052_emulator::cpu::{CPU, Memory};
 
 #[derive(Clone)]
 struct TestMemory {
-    data: [u8; 0xFFFF],
+    data: [u8; 0x10000],
 }
 
 impl TestMemory {
     pub fn new() -> TestMemory {
-        TestMemory { data: [0; 0xFFFF] }
+        TestMemory { data: [0; 0x10000] }
     }
 }
 
@@ -75,7 +77,21 @@ fn absolute_4_cycles() {
 
 #[test]
 fn implied_7_cycles() {
-    // TODO: Implement this test
+    let mut memory = TestMemory::new();
+    memory.set(0x0200, 0x00);
+    memory.set(0xFFFE, 0x34);
+    memory.set(0xFFFF, 0x12);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    instruction_test!(memory, cpu, 7, {
+        assert_eq!(cpu.program_counter, 0x1234);
+        assert_eq!(cpu.stack_pointer, 0xFC);
+        assert_eq!(cpu.memory.get(0x01FF), 0x02);
+        assert_eq!(cpu.memory.get(0x01FE), 0x02);
+        assert_eq!(cpu.memory.get(0x01FD), 0b0011_0000);
+        assert_eq!(cpu.interrupt, true);
+    }, [x, y, accumulator, carry, decimal, _break, overflow, zero, negative]);
 }
 
 #[test]
@@ -95,7 +111,23 @@ fn implied_4_cycles() {
 
 #[test]
 fn implied_6_cycles() {
-    // TODO: Implement this test
+    let mut memory = TestMemory::new();
+    memory.set(0x0200, 0x40);
+    memory.set(0x01FD, 0b1100_0011);
+    memory.set(0x01FE, 0x34);
+    memory.set(0x01FF, 0x12);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.stack_pointer = 0xFC;
+
+    instruction_test!(memory, cpu, 6, {
+        assert_eq!(cpu.program_counter, 0x1234);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+        assert_eq!(cpu.negative, true);
+        assert_eq!(cpu.overflow, true);
+        assert_eq!(cpu.zero, true);
+        assert_eq!(cpu.carry, true);
+    }, [x, y, accumulator, interrupt, decimal, _break]);
 }
 
 /* ~~~~~~~~ Address Mode: Accumulator ~~~~~~~~ */
@@ -205,7 +237,19 @@ fn relative_5_cycles_branch_new_page() {
 
 #[test]
 fn absolute_6_cycles() {
-    // TODO: Implement this test
+    let mut memory = TestMemory::new();
+    memory.set(0x0200, 0x20);
+    memory.set(0x0201, 0x34);
+    memory.set(0x0202, 0x12);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    instruction_test!(memory, cpu, 6, {
+        assert_eq!(cpu.program_counter, 0x1234);
+        assert_eq!(cpu.stack_pointer, 0xFD);
+        assert_eq!(cpu.memory.get(0x01FF), 0x02);
+        assert_eq!(cpu.memory.get(0x01FE), 0x02);
+    }, [x, y, accumulator, carry, interrupt, decimal, _break, overflow, zero, negative]);
 }
 
 /* ~~~~~~~~ Address Mode: Absolute x ~~~~~~~~ */
@@ -251,7 +295,18 @@ fn absolute_y_5_cycles() {
 
 #[test]
 fn indirect_5_cycles() {
-    // TODO: Implement this test
+    let mut memory = TestMemory::new();
+    memory.set(0x0200, 0x6C);
+    memory.set(0x0201, 0x42);
+    memory.set(0x0202, 0x42);
+    memory.set(0x4242, 0x34);
+    memory.set(0x4243, 0x12);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    instruction_test!(memory, cpu, 5, {
+        assert_eq!(cpu.program_counter, 0x1234);
+    }, [x, y, accumulator, stack_pointer, carry, interrupt, decimal, _break, overflow, zero, negative]);
 }
 
 /* ~~~~~~~~ Address Mode: Indirect x ~~~~~~~~ */
@@ -1114,3 +1169,54 @@ fn bittest_all_flags() {
         assert_eq!(cpu.overflow, true);
     }, [x, y, accumulator, stack_pointer, carry, interrupt, decimal, _break]);
 }
+
+/* ~~~~~~~~ Instruction: Jump ~~~~~~~~ */
+
+#[test]
+fn jump_absolute() {
+    let mut memory = TestMemory::new();
+    memory.set(0x0200, 0x4C);
+    memory.set(0x0201, 0x34);
+    memory.set(0x0202, 0x12);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    instruction_test!(memory, cpu, 3, {
+        assert_eq!(cpu.program_counter, 0x1234);
+    }, [x, y, accumulator, stack_pointer, carry, interrupt, decimal, _break, overflow, zero, negative]);
+}
+
+#[test]
+fn jump_indirect_page_boundary() {
+    let mut memory = TestMemory::new();
+    memory.set(0x0200, 0x6C);
+    memory.set(0x0201, 0xFF);
+    memory.set(0x0202, 0x42);
+    memory.set(0x42FF, 0x34);
+    memory.set(0x4200, 0x12);
+    memory.set(0x4300, 0x56);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+
+    instruction_test!(memory, cpu, 5, {
+        assert_eq!(cpu.program_counter, 0x1234);
+    }, [x, y, accumulator, stack_pointer, carry, interrupt, decimal, _break, overflow, zero, negative]);
+}
+
+/* ~~~~~~~~ Instruction: Return from subroutine ~~~~~~~~ */
+
+#[test]
+fn return_from_subroutine() {
+    let mut memory = TestMemory::new();
+    memory.set(0x0200, 0x60);
+    memory.set(0x01FE, 0x33);
+    memory.set(0x01FF, 0x12);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.stack_pointer = 0xFD;
+
+    instruction_test!(memory, cpu, 6, {
+        assert_eq!(cpu.program_counter, 0x1234);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+    }, [x, y, accumulator, carry, interrupt, decimal, _break, overflow, zero, negative]);
+}
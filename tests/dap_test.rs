@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::dap::{decode_base64, encode_base64, read_message, write_message, DapServer};
+use m6052_emulator::json::Json;
+use m6052_emulator::ram::Ram;
+use m6052_emulator::source_map::{SourceLocation, SourceMap};
+
+/*
+ * main.s line 1 $0200: LDA #$01
+ *         line 2 $0202: JSR $0300
+ *         line 3 $0205: LDX #$02
+ *         line 4 $0207: BRK
+ *        line 10 $0300: LDY #$03
+ *        line 10 $0302: LDY #$04
+ *        line 11 $0304: RTS
+ */
+fn program() -> Ram {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0x20, 0x00, 0x03, 0xA2, 0x02, 0x00]);
+    ram.load(0x0300, &[0xA0, 0x03, 0xA0, 0x04, 0x60]);
+    ram
+}
+
+struct TestSourceMap {
+    lines: HashMap<u16, u32>,
+}
+
+impl TestSourceMap {
+    fn new() -> TestSourceMap {
+        let lines = [(0x0200, 1), (0x0202, 2), (0x0205, 3), (0x0207, 4), (0x0300, 10), (0x0302, 10), (0x0304, 11)];
+        TestSourceMap { lines: lines.into_iter().collect() }
+    }
+}
+
+impl SourceMap for TestSourceMap {
+    fn location(&self, address: u16) -> Option<SourceLocation> {
+        self.lines.get(&address).map(|line| SourceLocation { file: "/src/main.s".to_string(), line: *line })
+    }
+
+    fn addresses(&self, file: &str, line: u32) -> Vec<u16> {
+        let mut addresses: Vec<u16> = match "/src/main.s".ends_with(file) {
+            true => self.lines.iter().filter(|(_, other)| **other == line).map(|(address, _)| *address).collect(),
+            false => Vec::new(),
+        };
+        addresses.sort();
+        addresses
+    }
+}
+
+fn request(seq: i64, command: &str, arguments: Json) -> Json {
+    Json::object([
+        ("seq", seq.into()),
+        ("type", "request".into()),
+        ("command", command.into()),
+        ("arguments", arguments),
+    ])
+}
+
+fn path<'j>(value: &'j Json, keys: &[&str]) -> &'j Json {
+    keys.iter().fold(value, |value, key| match key.parse::<usize>() {
+        Ok(index) => &value.as_array().unwrap()[index],
+        Err(_) => value.get(key).unwrap_or_else(|| panic!("No {key} in {value}")),
+    })
+}
+
+fn body<'j>(messages: &'j [Json], command: &str) -> &'j Json {
+    let response = messages.iter()
+        .find(|message| message.get("command").and_then(Json::as_str) == Some(command))
+        .unwrap();
+    assert_eq!(response.get("success"), Some(&Json::Bool(true)), "{response}");
+    path(response, &["body"])
+}
+
+fn events<'j>(messages: &'j [Json], event: &str) -> Vec<&'j Json> {
+    messages.iter()
+        .filter(|message| message.get("event").and_then(Json::as_str) == Some(event))
+        .map(|message| path(message, &["body"]))
+        .collect()
+}
+
+#[test]
+fn base64() {
+    assert_eq!(encode_base64(b"Man"), "TWFu");
+    assert_eq!(encode_base64(b"Ma"), "TWE=");
+    assert_eq!(encode_base64(b"M"), "TQ==");
+    assert_eq!(decode_base64("TWE="), Some(b"Ma".to_vec()));
+    assert_eq!(decode_base64("T!=="), None);
+}
+
+#[test]
+fn scripted_session() {
+    let mut input = Vec::new();
+    let requests = [
+        request(1, "initialize", Json::object([("adapterID", "m6502".into())])),
+        request(2, "launch", Json::object([("address", "0x0200".into()), ("stopOnEntry", true.into())])),
+        request(3, "setBreakpoints", Json::object([
+            ("source", Json::object([("path", "main.s".into())])),
+            ("breakpoints", vec![Json::object([("line", 10i64.into())]), Json::object([("line", 5i64.into())])].into()),
+        ])),
+        request(4, "configurationDone", Json::object([])),
+        request(5, "continue", Json::object([("threadId", 1i64.into())])),
+        request(6, "stackTrace", Json::object([("threadId", 1i64.into())])),
+        request(7, "next", Json::object([("threadId", 1i64.into())])),
+        request(8, "variables", Json::object([("variablesReference", 1i64.into())])),
+        request(9, "disconnect", Json::object([])),
+        request(10, "threads", Json::object([])),
+    ];
+    for request in &requests {
+        write_message(&mut input, request).unwrap();
+    }
+
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut server = DapServer::new();
+    server.monitor.source_map = Some(Box::new(TestSourceMap::new()));
+    let mut output = Vec::new();
+    server.serve(&mut cpu, Cursor::new(input), &mut output).unwrap();
+
+    let mut messages = Vec::new();
+    let mut output = Cursor::new(output);
+    while let Some(message) = read_message(&mut output).unwrap() {
+        messages.push(message);
+    }
+
+    assert!(server.is_disconnected());
+    assert!(messages.iter().all(|message| message.get("command").and_then(Json::as_str) != Some("threads")));
+    let seqs: Vec<i64> = messages.iter().map(|message| message.get("seq").and_then(Json::as_i64).unwrap()).collect();
+    assert!(seqs.windows(2).all(|pair| pair[0] < pair[1]));
+
+    assert_eq!(path(body(&messages, "initialize"), &["supportsConfigurationDoneRequest"]), &Json::Bool(true));
+    let breakpoints = path(body(&messages, "setBreakpoints"), &["breakpoints"]);
+    assert_eq!(path(breakpoints, &["0", "verified"]), &Json::Bool(true));
+    assert_eq!(path(breakpoints, &["1", "verified"]), &Json::Bool(false));
+    let id = path(breakpoints, &["0", "id"]).clone();
+
+    let stopped = events(&messages, "stopped");
+    assert_eq!(stopped.len(), 3);
+    assert_eq!(path(stopped[0], &["reason"]).as_str(), Some("entry"));
+    assert_eq!(path(stopped[1], &["reason"]).as_str(), Some("breakpoint"));
+    assert_eq!(path(stopped[1], &["hitBreakpointIds", "0"]), &id);
+    assert_eq!(path(stopped[2], &["reason"]).as_str(), Some("step"));
+
+    let frames = path(body(&messages, "stackTrace"), &["stackFrames"]);
+    assert_eq!(path(frames, &["0", "instructionPointerReference"]).as_str(), Some("0x0300"));
+    assert_eq!(path(frames, &["0", "line"]).as_i64(), Some(10));
+    assert_eq!(path(frames, &["0", "source", "name"]).as_str(), Some("main.s"));
+    assert_eq!(path(frames, &["1", "instructionPointerReference"]).as_str(), Some("0x0202"));
+    assert_eq!(path(frames, &["1", "line"]).as_i64(), Some(2));
+
+    /* Stepping a line runs both instructions of line 10 */
+    assert_eq!(cpu.program_counter, 0x0304);
+    let variables = path(body(&messages, "variables"), &["variables"]);
+    assert_eq!(path(variables, &["2", "name"]).as_str(), Some("Y"));
+    assert_eq!(path(variables, &["2", "value"]).as_str(), Some("$04"));
+}
+
+fn handle(server: &mut DapServer, cpu: &mut CPU, command: &str, arguments: Json) -> Vec<Json> {
+    server.handle(cpu, &request(1, command, arguments))
+}
+
+#[test]
+fn launch_program() {
+    let file = env::temp_dir().join(format!("m6502-dap-{}.bin", std::process::id()));
+    fs::write(&file, [0xA9, 0x42, 0x00]).unwrap();
+
+    let mut ram = Ram::new();
+    let mut cpu = CPU::new(&mut ram);
+    let mut server = DapServer::new();
+    let messages = handle(&mut server, &mut cpu, "launch", Json::object([
+        ("program", file.to_string_lossy().as_ref().into()),
+        ("address", 0x1000i64.into()),
+    ]));
+    fs::remove_file(&file).unwrap();
+
+    assert_eq!(messages[0].get("success"), Some(&Json::Bool(true)));
+    assert_eq!(cpu.program_counter, 0x1000);
+    assert_eq!(cpu.memory.get(0x1001), 0x42);
+
+    let messages = handle(&mut server, &mut cpu, "launch", Json::object([("program", "/missing".into())]));
+    assert_eq!(messages[0].get("success"), Some(&Json::Bool(false)));
+}
+
+#[test]
+fn instruction_breakpoints_and_stepping() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut server = DapServer::new();
+
+    let messages = handle(&mut server, &mut cpu, "setInstructionBreakpoints", Json::object([
+        ("breakpoints", vec![Json::object([("instructionReference", "0x0302".into()), ("condition", "Y == 3".into())])].into()),
+    ]));
+    assert_eq!(path(body(&messages, "setInstructionBreakpoints"), &["breakpoints", "0", "verified"]), &Json::Bool(true));
+
+    let messages = handle(&mut server, &mut cpu, "continue", Json::object([]));
+    assert_eq!(path(events(&messages, "stopped")[0], &["reason"]).as_str(), Some("breakpoint"));
+    assert_eq!(cpu.program_counter, 0x0302);
+
+    /* Without a source map steps are single instructions */
+    handle(&mut server, &mut cpu, "stepIn", Json::object([]));
+    assert_eq!(cpu.program_counter, 0x0304);
+    let messages = handle(&mut server, &mut cpu, "stepOut", Json::object([]));
+    assert_eq!(path(events(&messages, "stopped")[0], &["reason"]).as_str(), Some("step"));
+    assert_eq!(cpu.program_counter, 0x0205);
+
+    handle(&mut server, &mut cpu, "setInstructionBreakpoints", Json::object([("breakpoints", Json::Array(Vec::new()))]));
+    assert!(cpu.breakpoints.is_empty());
+
+    cpu.memory.set(0x0207, 0x02);
+    let messages = handle(&mut server, &mut cpu, "continue", Json::object([]));
+    let stopped = events(&messages, "stopped");
+    assert_eq!(path(stopped[0], &["reason"]).as_str(), Some("exception"));
+    assert_eq!(path(stopped[0], &["description"]).as_str(), Some("Illegal opcode at $0207"));
+}
+
+#[test]
+fn memory_and_evaluation() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut server = DapServer::new();
+    server.monitor.symbols.insert("subroutine", 0x0300);
+
+    let messages = handle(&mut server, &mut cpu, "readMemory", Json::object([
+        ("memoryReference", "0x0200".into()), ("offset", 2i64.into()), ("count", 3i64.into()),
+    ]));
+    assert_eq!(path(body(&messages, "readMemory"), &["data"]).as_str(), Some(encode_base64(&[0x20, 0x00, 0x03]).as_str()));
+
+    handle(&mut server, &mut cpu, "writeMemory", Json::object([
+        ("memoryReference", "$1000".into()), ("data", encode_base64(&[1, 2, 3]).into()),
+    ]));
+    assert_eq!(cpu.memory.get(0x1002), 3);
+
+    let messages = handle(&mut server, &mut cpu, "disassemble", Json::object([
+        ("memoryReference", "0x0205".into()), ("instructionOffset", (-2i64).into()), ("instructionCount", 3i64.into()),
+    ]));
+    let instructions = path(body(&messages, "disassemble"), &["instructions"]);
+    assert_eq!(path(instructions, &["0", "address"]).as_str(), Some("0x0200"));
+    assert_eq!(path(instructions, &["1", "instruction"]).as_str(), Some("JSR $0300"));
+    assert_eq!(path(instructions, &["2", "instructionBytes"]).as_str(), Some("A2 02"));
+
+    let messages = handle(&mut server, &mut cpu, "evaluate", Json::object([("expression", "subroutine + 1".into())]));
+    assert_eq!(path(body(&messages, "evaluate"), &["result"]).as_str(), Some("769 ($301)"));
+    let messages = handle(&mut server, &mut cpu, "evaluate", Json::object([
+        ("expression", "m 0200 0201".into()), ("context", "repl".into()),
+    ]));
+    assert!(path(body(&messages, "evaluate"), &["result"]).as_str().unwrap().starts_with(">0200  A9 01"));
+
+    let messages = handle(&mut server, &mut cpu, "setVariable", Json::object([
+        ("variablesReference", 1i64.into()), ("name", "X".into()), ("value", "$10".into()),
+    ]));
+    assert_eq!(path(body(&messages, "setVariable"), &["value"]).as_str(), Some("$10"));
+    assert_eq!(cpu.x, 0x10);
+
+    let messages = handle(&mut server, &mut cpu, "bogus", Json::object([]));
+    assert_eq!(messages[0].get("success"), Some(&Json::Bool(false)));
+}
+
+#[test]
+fn pause_a_running_program() {
+    let mut input = Vec::new();
+    for request in [
+        request(1, "continue", Json::object([("threadId", 1i64.into())])),
+        request(2, "pause", Json::object([("threadId", 1i64.into())])),
+        request(3, "disconnect", Json::object([])),
+    ] {
+        write_message(&mut input, &request).unwrap();
+    }
+
+    /* $0200: JMP $0200 */
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0x4C, 0x00, 0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut server = DapServer::new();
+    let mut output = Vec::new();
+    server.serve(&mut cpu, Cursor::new(input), &mut output).unwrap();
+
+    let mut messages = Vec::new();
+    let mut output = Cursor::new(output);
+    while let Some(message) = read_message(&mut output).unwrap() {
+        messages.push(message);
+    }
+
+    let stopped = events(&messages, "stopped");
+    assert_eq!(stopped.len(), 1);
+    assert_eq!(path(stopped[0], &["reason"]).as_str(), Some("pause"));
+    assert!(cpu.total_cycles < server.monitor.cycle_limit as u64);
+    let pause = messages.iter().find(|message| message.get("command").and_then(Json::as_str) == Some("pause")).unwrap();
+    assert_eq!(pause.get("success"), Some(&Json::Bool(true)));
+    assert!(server.is_disconnected());
+}
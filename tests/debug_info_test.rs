@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::rc::Rc;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::debug_info::{DebugInfo, SourceTracer};
+use m6052_emulator::monitor::Monitor;
+use m6052_emulator::ram::Ram;
+use m6052_emulator::source_map::{step_line, SourceLocation, SourceMap};
+
+/*
+ * main.s:                              macros.inc:
+ *  5  .proc main                        3  ldx #COUNT
+ *  6      lda #$01
+ *  7  loop: jsr sub
+ *  8      sta $10
+ *  9      .byte $02
+ * 11  .proc sub
+ * 12      ldx #COUNT   (from a macro)
+ * 13      rts
+ */
+const DEBUG_INFO: &str = "\
+version\tmajor=2,minor=0
+info\tcsym=0,file=2,lib=0,line=7,mod=1,scope=3,seg=1,span=8,sym=5,type=0
+file\tid=0,name=\"src/main.s\",size=200,mtime=0x5A4F7B1C,mod=0
+file\tid=1,name=\"src/macros.inc\",size=50,mtime=0x5A4F7B1C,mod=0
+mod\tid=0,name=\"main.o\",file=0
+seg\tid=0,name=\"CODE\",start=0x000200,size=0x000B,addrsize=absolute,type=ro,oname=\"main.bin\",ooffs=0
+span\tid=0,seg=0,start=0,size=2
+span\tid=1,seg=0,start=2,size=3
+span\tid=2,seg=0,start=5,size=2
+span\tid=3,seg=0,start=7,size=1
+span\tid=4,seg=0,start=8,size=2
+span\tid=5,seg=0,start=10,size=1
+span\tid=6,seg=0,start=0,size=8
+span\tid=7,seg=0,start=8,size=3
+line\tid=0,file=0,line=6,span=0
+line\tid=1,file=0,line=7,span=1
+line\tid=2,file=0,line=8,span=2
+line\tid=3,file=0,line=9,span=3
+line\tid=4,file=1,line=3,type=2,span=4
+line\tid=5,file=0,line=12,span=4
+line\tid=6,file=0,line=13,span=5
+scope\tid=0,name=\"\",mod=0,size=11,span=6+7
+scope\tid=1,name=\"main\",mod=0,type=scope,size=8,parent=0,span=6,sym=0
+scope\tid=2,name=\"sub\",mod=0,type=scope,size=3,parent=0,span=7,sym=1
+sym\tid=0,name=\"main\",addrsize=absolute,scope=0,def=0,val=0x200,seg=0,type=lab
+sym\tid=1,name=\"sub\",addrsize=absolute,scope=0,def=4,val=0x208,seg=0,type=lab
+sym\tid=2,name=\"loop\",addrsize=absolute,scope=1,def=1,val=0x202,seg=0,type=lab
+sym\tid=3,name=\"COUNT\",addrsize=zeropage,scope=0,def=1,val=0x5,type=equ
+sym\tid=4,name=\"_exit\",addrsize=absolute,scope=0,type=imp
+";
+
+fn program() -> Ram {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0x20, 0x08, 0x02, 0x85, 0x10, 0x02, 0xA2, 0x05, 0x60]);
+    ram
+}
+
+fn location(file: &str, line: u32) -> Option<SourceLocation> {
+    Some(SourceLocation { file: file.to_string(), line })
+}
+
+#[test]
+fn parse_records() {
+    let info = DebugInfo::parse(DEBUG_INFO).unwrap();
+
+    assert_eq!(info.files.len(), 2);
+    assert_eq!(info.lines.len(), 7);
+    assert_eq!(info.spans.len(), 8);
+    assert_eq!(info.segments[&0].name, "CODE");
+    assert_eq!(info.segments[&0].output_name.as_deref(), Some("main.bin"));
+    assert_eq!(info.scopes[&0].spans, vec![6, 7]);
+    assert_eq!(info.span_range(4), Some((0x0208, 0x020A)));
+    assert_eq!(info.symbols[&3].kind, "equ");
+}
+
+#[test]
+fn reject_invalid_input() {
+    assert_eq!(DebugInfo::parse("version\tmajor=3,minor=0").unwrap_err(),
+               "Line 1: Only version 2 debug info is supported");
+    assert_eq!(DebugInfo::parse("version\tmajor=2,minor=0\nspan\tseg=0,start=0,size=1").unwrap_err(),
+               "Line 2: Missing id");
+    assert!(DebugInfo::parse("file\tid=0,name=\"open").is_err());
+    assert!(DebugInfo::parse("seg\tid=x,name=\"CODE\",start=0,size=0").is_err());
+}
+
+#[test]
+fn map_addresses_to_lines() {
+    let info = DebugInfo::parse(DEBUG_INFO).unwrap();
+
+    assert_eq!(info.location(0x0200), location("src/main.s", 6));
+    assert_eq!(info.location(0x0204), location("src/main.s", 7));
+    /* Assembler lines win over the macro line covering the same bytes */
+    assert_eq!(info.location(0x0209), location("src/main.s", 12));
+    assert_eq!(info.location(0x020B), None);
+
+    assert_eq!(info.addresses("src/main.s", 7), vec![0x0202]);
+    assert_eq!(info.addresses("main.s", 13), vec![0x020A]);
+    assert_eq!(info.addresses("/work/project/src/main.s", 12), vec![0x0208]);
+    assert_eq!(info.addresses("macros.inc", 3), vec![0x0208]);
+    assert!(info.addresses("other.s", 7).is_empty());
+}
+
+#[test]
+fn symbols_and_scopes() {
+    let info = DebugInfo::parse(DEBUG_INFO).unwrap();
+    let symbols = info.symbol_table();
+
+    assert_eq!(symbols.address_of("main"), Some(0x0200));
+    assert_eq!(symbols.address_of("main::loop"), Some(0x0202));
+    assert_eq!(symbols.address_of("COUNT"), Some(0x0005));
+    assert_eq!(symbols.address_of("_exit"), None);
+    assert_eq!(symbols.lookup(0x0209), Some(("sub", 1)));
+
+    assert_eq!(info.scope_at(0x0203).map(|scope| scope.name.as_str()), Some("main"));
+    assert_eq!(info.scope_at(0x0209).map(|scope| scope.name.as_str()), Some("sub"));
+    assert_eq!(info.scope_name(2), "sub");
+}
+
+#[test]
+fn step_by_source_line() {
+    let info = DebugInfo::parse(DEBUG_INFO).unwrap();
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut cycles = 1000;
+
+    step_line(&mut cpu, &info, false, &mut cycles);
+    assert_eq!(cpu.program_counter, 0x0202);
+    step_line(&mut cpu, &info, false, &mut cycles);
+    assert_eq!(cpu.program_counter, 0x0208);
+    step_line(&mut cpu, &info, false, &mut cycles);
+    step_line(&mut cpu, &info, false, &mut cycles);
+    assert_eq!(cpu.program_counter, 0x0205);
+
+    cpu.program_counter = 0x0202;
+    step_line(&mut cpu, &info, true, &mut cycles);
+    assert_eq!(cpu.program_counter, 0x0205);
+    assert_eq!(cpu.x, 0x05);
+}
+
+#[test]
+fn monitor_loads_debug_info() {
+    let path = env::temp_dir().join(format!("m6502-debug-info-{}.dbg", std::process::id()));
+    fs::write(&path, DEBUG_INFO).unwrap();
+
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+    assert!(monitor.execute(&mut cpu, "nl").is_err());
+
+    let output = monitor.execute(&mut cpu, &format!("debuginfo {}", path.display()));
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output.unwrap(), format!("Loaded 4 symbols from {}", path.display()));
+
+    let output = monitor.execute(&mut cpu, "d main::loop main::loop").unwrap();
+    assert_eq!(output, "main::loop:\n.0202  20 08 02  JSR $0208      ; src/main.s:7");
+
+    monitor.execute(&mut cpu, "zl").unwrap();
+    let output = monitor.execute(&mut cpu, "nl").unwrap();
+    assert!(output.ends_with(".0205  85 10     STA $10        ; src/main.s:8"));
+}
+
+#[test]
+fn trace_with_symbols_and_lines() {
+    let info = Rc::new(DebugInfo::parse(DEBUG_INFO).unwrap());
+    let tracer = Rc::new(RefCell::new(SourceTracer::new(Vec::new(), info)));
+
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    cpu.observers.push(Box::new(tracer.clone()));
+    let mut cycles = 1000;
+    cpu.run_until(&mut cycles, |cpu| cpu.program_counter == 0x020A);
+    drop(cpu);
+
+    let output = String::from_utf8(tracer.borrow().get_ref().clone()).unwrap();
+    assert_eq!(output,
+               "0200  main                  LDA #$01          src/main.s:6\n\
+                0202  main::loop            JSR $0208         src/main.s:7\n\
+                0208  sub                   LDX #$05          src/main.s:12\n");
+}
+
+#[test]
+fn trace_stops_at_write_errors() {
+    let info = Rc::new(DebugInfo::parse(DEBUG_INFO).unwrap());
+    /* Room for the first line only */
+    let mut buffer = [0; 50];
+    let tracer = Rc::new(RefCell::new(SourceTracer::new(&mut buffer[..], info)));
+
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    cpu.observers.push(Box::new(tracer.clone()));
+    cpu.run_until(&mut 1000, |cpu| cpu.program_counter == 0x020A);
+    assert_eq!(cpu.program_counter, 0x020A);
+    drop(cpu);
+
+    assert_eq!(tracer.borrow().error().map(|error| error.kind()), Some(ErrorKind::WriteZero));
+}
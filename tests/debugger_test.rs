@@ -0,0 +1,134 @@
+use m6052_emulator::breakpoint::BreakpointKind;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::debugger::{Debugger, Key};
+use m6052_emulator::ram::Ram;
+
+/* LDA #$01; LDX #$02; LDY #$03; STA $1000; BRK */
+fn program() -> Ram {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0xA2, 0x02, 0xA0, 0x03, 0x8D, 0x00, 0x10, 0x00]);
+    ram
+}
+
+#[test]
+fn step_and_move_the_cursor() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut debugger = Debugger::new();
+    debugger.attach(&mut cpu);
+
+    debugger.handle_key(&mut cpu, Key::Char('s'));
+    assert_eq!(cpu.program_counter, 0x0202);
+    assert_eq!(debugger.cursor(), 0x0202);
+
+    debugger.handle_key(&mut cpu, Key::Down);
+    debugger.handle_key(&mut cpu, Key::Down);
+    assert_eq!(debugger.cursor(), 0x0206);
+    debugger.handle_key(&mut cpu, Key::Up);
+    assert_eq!(debugger.cursor(), 0x0204);
+}
+
+#[test]
+fn run_to_cursor_and_toggle_breakpoints() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut debugger = Debugger::new();
+    debugger.attach(&mut cpu);
+
+    debugger.handle_key(&mut cpu, Key::Down);
+    debugger.handle_key(&mut cpu, Key::Down);
+    debugger.handle_key(&mut cpu, Key::Char('c'));
+    assert_eq!(cpu.program_counter, 0x0204);
+
+    debugger.handle_key(&mut cpu, Key::Down);
+    debugger.handle_key(&mut cpu, Key::Char('b'));
+    assert_eq!(cpu.breakpoints.iter().next().map(|breakpoint| breakpoint.kind), Some(BreakpointKind::Execute(0x0206)));
+
+    debugger.handle_key(&mut cpu, Key::Char('g'));
+    assert_eq!(cpu.program_counter, 0x0206);
+    assert_eq!(debugger.message(), "Breakpoint #0 hit at $0206");
+
+    debugger.handle_key(&mut cpu, Key::Char('b'));
+    assert!(cpu.breakpoints.is_empty());
+}
+
+#[test]
+fn monitor_commands() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut debugger = Debugger::new();
+    debugger.attach(&mut cpu);
+
+    for key in ":r A=42".chars() {
+        debugger.handle_key(&mut cpu, Key::Char(key));
+    }
+    debugger.handle_key(&mut cpu, Key::Enter);
+    assert_eq!(cpu.accumulator, 0x42);
+
+    assert!(debugger.handle_key(&mut cpu, Key::Char(':')));
+    assert!(debugger.handle_key(&mut cpu, Key::Char('q')));
+    debugger.handle_key(&mut cpu, Key::Escape);
+    assert!(!debugger.handle_key(&mut cpu, Key::Char('q')));
+}
+
+#[test]
+fn render_panes() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut debugger = Debugger::new();
+    debugger.attach(&mut cpu);
+    debugger.handle_key(&mut cpu, Key::Char('s'));
+
+    let frame = debugger.render(&cpu, 80, 24);
+    let lines: Vec<&str> = frame.split("\r\n").collect();
+
+    assert_eq!(lines.len(), 24);
+    assert!(frame.contains("-- Disassembly"));
+    assert!(frame.contains(" >.0202  A2 02     LDX #$02"));
+    assert!(frame.contains("PC 0202  A 01  X 00  Y 00  SP FF"));
+    assert!(frame.contains("P nv-bdizc  CYC 2"));
+    assert!(frame.contains("0100  00 00"));
+    assert!(frame.contains("0200  LDA #$01"));
+    assert!(frame.contains("0000  00 00 00"));
+
+    assert!(debugger.render(&cpu, 40, 10).contains("at least 60x24"));
+}
+
+#[test]
+fn step_over_and_out_stop_at_illegal_opcodes() {
+    /* $0200: JSR $0300; $0300: LDA #$01 followed by an illegal opcode */
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0x20, 0x00, 0x03]);
+    ram.load(0x0300, &[0xA9, 0x01, 0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut debugger = Debugger::new();
+    debugger.attach(&mut cpu);
+
+    debugger.handle_key(&mut cpu, Key::Char('n'));
+    assert_eq!(cpu.program_counter, 0x0302);
+    assert_eq!(debugger.message(), "Illegal opcode at $0302");
+
+    cpu.program_counter = 0x0300;
+    debugger.handle_key(&mut cpu, Key::Char('o'));
+    assert_eq!(cpu.program_counter, 0x0302);
+    assert_eq!(debugger.message(), "Illegal opcode at $0302");
+}
+
+#[test]
+fn follow_the_program_counter_with_context() {
+    /* $0200: JMP $0400 */
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0x4C, 0x00, 0x04]);
+    ram.load(0x03F0, &[0xEA; 0x20]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut debugger = Debugger::new();
+    debugger.attach(&mut cpu);
+    debugger.render(&cpu, 80, 24);
+
+    debugger.handle_key(&mut cpu, Key::Char('s'));
+    let frame = debugger.render(&cpu, 80, 24);
+    let rows: Vec<&str> = frame.split("\r\n").collect();
+    let cursor = rows.iter().position(|row| row.contains(">.0400")).unwrap();
+    assert!(rows[cursor - 1].contains(".03FF  EA"));
+    assert!(rows[2].contains(".03FD  EA"));
+}
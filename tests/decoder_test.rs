@@ -0,0 +1,122 @@
+mod common;
+
+use std::borrow::BorrowMut;
+use common::TestMemory;
+use m6052_emulator::cpu::{CPU, Memory};
+use m6052_emulator::decoder::{decode, Operand};
+use m6052_emulator::instruction::{AddressingMode, Instruction};
+
+#[test]
+fn decode_absolute() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xAD, 0x34, 0x12]);
+
+    let decoded = decode(&memory, 0x0200).unwrap();
+    assert_eq!(decoded.opcode, 0xAD);
+    assert_eq!(decoded.instruction, Instruction::LoadAccumulator(AddressingMode::Absolute));
+    assert_eq!(decoded.operand, Operand::Word(0x1234));
+    assert_eq!(decoded.length, 3);
+    assert_eq!(decoded.bytes(), vec![0xAD, 0x34, 0x12]);
+    assert_eq!(decoded.next_address(), 0x0203);
+}
+
+#[test]
+fn decode_illegal_opcode() {
+    let mut memory = TestMemory::new();
+    memory.set(0x0200, 0x02);
+    assert!(decode(&memory, 0x0200).is_none());
+}
+
+#[test]
+fn decode_has_no_side_effects() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xB5, 0x21]);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.x = 0x21;
+
+    let decoded = cpu.next_instruction().unwrap();
+    assert_eq!(decoded.effective_address(&cpu), Some(0x0042));
+    assert_eq!(cpu.program_counter, 0x0200);
+}
+
+#[test]
+fn effective_address_indexed_wraps() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xB5, 0xF0]);
+    memory.load(0x0300, &[0xBD, 0xF0, 0xFF]);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.x = 0x20;
+
+    let zeropage = decode(&*cpu.memory, 0x0200).unwrap();
+    assert_eq!(zeropage.effective_address(&cpu), Some(0x0010));
+
+    let absolute = decode(&*cpu.memory, 0x0300).unwrap();
+    assert_eq!(absolute.effective_address(&cpu), Some(0x0010));
+}
+
+#[test]
+fn effective_address_indirect_page_bug() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0x6C, 0xFF, 0x30]);
+    memory.set(0x30FF, 0x80);
+    memory.set(0x3000, 0x40);
+    memory.set(0x3100, 0x50);
+
+    let cpu = CPU::new(memory.borrow_mut());
+
+    let decoded = cpu.next_instruction().unwrap();
+    assert_eq!(decoded.effective_address(&cpu), Some(0x4080));
+}
+
+#[test]
+fn effective_address_x_indexed_indirect() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xA1, 0x20]);
+    memory.set(0x0024, 0x74);
+    memory.set(0x0025, 0x20);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.x = 0x04;
+
+    let decoded = cpu.next_instruction().unwrap();
+    assert_eq!(decoded.effective_address(&cpu), Some(0x2074));
+}
+
+#[test]
+fn effective_address_indirect_y_indexed() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xB1, 0xFF]);
+    memory.set(0x00FF, 0x28);
+    memory.set(0x0000, 0x40);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.y = 0x10;
+
+    let decoded = cpu.next_instruction().unwrap();
+    assert_eq!(decoded.effective_address(&cpu), Some(0x4038));
+}
+
+#[test]
+fn effective_address_relative() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xD0, 0xFC]);
+
+    let cpu = CPU::new(memory.borrow_mut());
+
+    let decoded = cpu.next_instruction().unwrap();
+    assert_eq!(decoded.effective_address(&cpu), Some(0x01FE));
+}
+
+#[test]
+fn effective_address_immediate() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xA9, 0x42]);
+
+    let cpu = CPU::new(memory.borrow_mut());
+
+    let decoded = cpu.next_instruction().unwrap();
+    assert_eq!(decoded.operand, Operand::Byte(0x42));
+    assert_eq!(decoded.effective_address(&cpu), None);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use std::borrow::BorrowMut;
+use common::TestMemory;
+use m6052_emulator::cpu::{CPU, Memory};
+use m6052_emulator::expression::Expression;
+use m6052_emulator::symbols::SymbolTable;
+
+fn evaluate(text: &str, cpu: &CPU) -> i64 {
+    Expression::parse(text, None).unwrap().evaluate(cpu)
+}
+
+#[test]
+fn numbers() {
+    let mut memory = TestMemory::new();
+    let cpu = CPU::new(memory.borrow_mut());
+
+    assert_eq!(evaluate("42", &cpu), 42);
+    assert_eq!(evaluate("$2A", &cpu), 42);
+    assert_eq!(evaluate("0x2a", &cpu), 42);
+    assert_eq!(evaluate("%101010", &cpu), 42);
+}
+
+#[test]
+fn precedence() {
+    let mut memory = TestMemory::new();
+    let cpu = CPU::new(memory.borrow_mut());
+
+    assert_eq!(evaluate("2 + 3 * 4", &cpu), 14);
+    assert_eq!(evaluate("(2 + 3) * 4", &cpu), 20);
+    assert_eq!(evaluate("1 << 4 | 1", &cpu), 17);
+    assert_eq!(evaluate("1 < 2 && 3 > 2", &cpu), 1);
+    assert_eq!(evaluate("0 || 5 & 4", &cpu), 1);
+    assert_eq!(evaluate("-1 + ~0", &cpu), -2);
+    assert_eq!(evaluate("7 % 4 == 3", &cpu), 1);
+    assert_eq!(evaluate("1 / 0", &cpu), 0);
+}
+
+#[test]
+fn registers_flags_and_memory() {
+    let mut memory = TestMemory::new();
+    memory.set(0x00FB, 4);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.accumulator = 0x10;
+    cpu.carry = false;
+
+    assert_eq!(evaluate("A == $10 && [$00FB] > 3 && !C", &cpu), 1);
+    assert_eq!(evaluate("pc", &cpu), 0x0200);
+    assert_eq!(evaluate("SP", &cpu), 0xFF);
+    assert_eq!(evaluate("[$FA + 1] * 2", &cpu), 8);
+    assert_eq!(evaluate("P & $20", &cpu), 0x20);
+}
+
+#[test]
+fn symbols() {
+    let mut memory = TestMemory::new();
+    memory.set(0x00FB, 7);
+    let cpu = CPU::new(memory.borrow_mut());
+
+    let mut symbols = SymbolTable::new();
+    symbols.insert("lives", 0x00FB);
+
+    let expression = Expression::parse("[lives] == 7", Some(&symbols)).unwrap();
+    assert!(expression.is_true(&cpu));
+
+    let expression = Expression::parse("[.lives] == 7", Some(&symbols)).unwrap();
+    assert!(expression.is_true(&cpu));
+}
+
+#[test]
+fn parse_errors() {
+    let unknown = Expression::parse("[lives]", None).unwrap_err();
+    assert_eq!(unknown.position, 1);
+
+    let unclosed = Expression::parse("(A == 1", None).unwrap_err();
+    assert_eq!(unclosed.position, 7);
+
+    assert!(Expression::parse("A == ", None).is_err());
+    assert!(Expression::parse("A 1", None).is_err());
+    assert!(Expression::parse("$XY", None).is_err());
+}
@@ -0,0 +1,211 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use m6052_emulator::breakpoint::{BreakpointKind, HitCondition, LogMessage};
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::gdb::{checksum, encode, GdbStub};
+use m6052_emulator::ram::Ram;
+
+/* LDA #$01; LDX #$02; STA $1000; LDY #$03; BRK */
+fn program() -> Ram {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0xA2, 0x02, 0x8D, 0x00, 0x10, 0xA0, 0x03, 0x00]);
+    ram
+}
+
+fn send(stub: &mut GdbStub, cpu: &mut CPU, packet: &str) -> Option<String> {
+    stub.handle_packet(cpu, packet, &mut || false)
+}
+
+#[test]
+fn framing() {
+    assert_eq!(checksum("OK"), 0x9A);
+    assert_eq!(encode("OK"), "$OK#9a");
+    assert_eq!(encode("a$b"), "$a}\u{4}b#".to_string() + &format!("{:02x}", checksum("a}\u{4}b")));
+}
+
+#[test]
+fn registers() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut stub = GdbStub::new();
+
+    assert_eq!(send(&mut stub, &mut cpu, "g").unwrap(), "000000ff000220");
+    assert_eq!(send(&mut stub, &mut cpu, "G112233fd3412a1").unwrap(), "OK");
+    assert_eq!((cpu.accumulator, cpu.x, cpu.y, cpu.stack_pointer), (0x11, 0x22, 0x33, 0xFD));
+    assert_eq!(cpu.program_counter, 0x1234);
+    assert!(cpu.negative && cpu.carry);
+
+    assert_eq!(send(&mut stub, &mut cpu, "p4").unwrap(), "3412");
+    assert_eq!(send(&mut stub, &mut cpu, "P4=0002").unwrap(), "OK");
+    assert_eq!(send(&mut stub, &mut cpu, "P0=42").unwrap(), "OK");
+    assert_eq!(cpu.program_counter, 0x0200);
+    assert_eq!(cpu.accumulator, 0x42);
+    assert_eq!(send(&mut stub, &mut cpu, "p9").unwrap(), "E01");
+}
+
+#[test]
+fn memory() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut stub = GdbStub::new();
+
+    assert_eq!(send(&mut stub, &mut cpu, "m200,4").unwrap(), "a901a202");
+    assert_eq!(send(&mut stub, &mut cpu, "M2000,2:beef").unwrap(), "OK");
+    assert_eq!(cpu.memory.get(0x2001), 0xEF);
+    assert_eq!(send(&mut stub, &mut cpu, "M2000,3:beef").unwrap(), "E01");
+}
+
+#[test]
+fn step_continue_and_breakpoints() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut stub = GdbStub::new();
+
+    assert_eq!(send(&mut stub, &mut cpu, "s").unwrap(), "S05");
+    assert_eq!(cpu.program_counter, 0x0202);
+
+    assert_eq!(send(&mut stub, &mut cpu, "Z2,1000,1").unwrap(), "OK");
+    assert_eq!(send(&mut stub, &mut cpu, "c").unwrap(), "T05watch:1000;");
+    assert_eq!(cpu.program_counter, 0x0207);
+    assert_eq!(send(&mut stub, &mut cpu, "z2,1000,1").unwrap(), "OK");
+    assert!(cpu.breakpoints.is_empty());
+
+    assert_eq!(send(&mut stub, &mut cpu, "Z0,209,1").unwrap(), "OK");
+    assert_eq!(send(&mut stub, &mut cpu, "c").unwrap(), "S05");
+    assert_eq!(cpu.program_counter, 0x0209);
+    assert_eq!(cpu.y, 0x03);
+
+    assert_eq!(send(&mut stub, &mut cpu, "D").unwrap(), "OK");
+    assert!(stub.is_closed());
+    assert!(cpu.breakpoints.is_empty());
+}
+
+#[test]
+fn watchpoints_of_different_lengths() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut stub = GdbStub::new();
+
+    assert_eq!(send(&mut stub, &mut cpu, "Z2,1000,4").unwrap(), "OK");
+    assert_eq!(send(&mut stub, &mut cpu, "Z2,1000,1").unwrap(), "OK");
+    assert_eq!(send(&mut stub, &mut cpu, "Z2,1000,1").unwrap(), "OK");
+    let kinds = |cpu: &CPU| cpu.breakpoints.iter().map(|breakpoint| breakpoint.kind).collect::<Vec<_>>();
+    assert_eq!(kinds(&cpu), [BreakpointKind::Write(0x1000, 0x1003), BreakpointKind::Write(0x1000, 0x1000)]);
+
+    assert_eq!(send(&mut stub, &mut cpu, "z2,1000,1").unwrap(), "OK");
+    assert_eq!(kinds(&cpu), [BreakpointKind::Write(0x1000, 0x1003)]);
+}
+
+#[test]
+fn breakpoints_where_chunks_end() {
+    /* LDA #$01; JMP $0200, the first chunk of 100000 cycles ends right at $0200 */
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0x4C, 0x00, 0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    let id = cpu.breakpoints.add(BreakpointKind::Execute(0x0200));
+    cpu.breakpoints.set_hit_condition(id, Some(HitCondition::Equal(20_000)));
+    let mut stub = GdbStub::new();
+
+    assert_eq!(send(&mut stub, &mut cpu, "c").unwrap(), "S05");
+    assert_eq!(cpu.total_cycles, 100_000);
+}
+
+#[test]
+fn tracepoints_become_console_output() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let id = cpu.breakpoints.add(BreakpointKind::Execute(0x0204));
+    cpu.breakpoints.set_log_message(id, Some(LogMessage::parse("X={X}", None).unwrap()));
+    let mut stub = GdbStub::new();
+
+    assert_eq!(send(&mut stub, &mut cpu, "Z0,209,1").unwrap(), "OK");
+    assert_eq!(send(&mut stub, &mut cpu, "c").unwrap(), "S05");
+    assert_eq!(stub.take_output(), vec!["O583d320a"]);
+    assert!(stub.take_output().is_empty());
+}
+
+#[test]
+fn reverse_step_and_continue() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    cpu.history.set_capacity(10);
+    let mut stub = GdbStub::new();
+
+    assert!(send(&mut stub, &mut cpu, "qSupported").unwrap().contains("ReverseStep+;ReverseContinue+"));
+    assert_eq!(send(&mut stub, &mut cpu, "Z0,209,1").unwrap(), "OK");
+    assert_eq!(send(&mut stub, &mut cpu, "c").unwrap(), "S05");
+    assert_eq!(send(&mut stub, &mut cpu, "bs").unwrap(), "S05");
+    assert_eq!(cpu.program_counter, 0x0207);
+
+    assert_eq!(send(&mut stub, &mut cpu, "Z2,1000,1").unwrap(), "OK");
+    assert_eq!(send(&mut stub, &mut cpu, "bc").unwrap(), "T05watch:1000;");
+    assert_eq!(cpu.program_counter, 0x0204);
+    assert_eq!(send(&mut stub, &mut cpu, "bc").unwrap(), "T05replaylog:begin;");
+    assert_eq!(cpu.program_counter, 0x0200);
+}
+
+#[test]
+fn illegal_opcodes_stop_with_sigill() {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut stub = GdbStub::new();
+
+    assert_eq!(send(&mut stub, &mut cpu, "c").unwrap(), "S04");
+    assert_eq!(cpu.program_counter, 0x0202);
+}
+
+#[test]
+fn target_description() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut stub = GdbStub::new();
+
+    assert!(send(&mut stub, &mut cpu, "qSupported:multiprocess+").unwrap().contains("qXfer:features:read+"));
+    let first = send(&mut stub, &mut cpu, "qXfer:features:read:target.xml:0,10").unwrap();
+    assert_eq!(first, "m<?xml version=\"1");
+    let rest = send(&mut stub, &mut cpu, "qXfer:features:read:target.xml:10,1000").unwrap();
+    assert!(rest.starts_with('l') && rest.contains("name=\"pc\" bitsize=\"16\""));
+    assert_eq!(send(&mut stub, &mut cpu, "vMustReplyEmpty").unwrap(), "");
+}
+
+fn read_reply(stream: &mut TcpStream) -> String {
+    let mut reply = Vec::new();
+    let mut byte = [0];
+    loop {
+        stream.read_exact(&mut byte).unwrap();
+        match byte[0] {
+            b'+' if reply.is_empty() => continue,
+            b'#' => break,
+            _ => reply.push(byte[0]),
+        }
+    }
+    let mut sum = [0; 2];
+    stream.read_exact(&mut sum).unwrap();
+    String::from_utf8(reply[1..].to_vec()).unwrap()
+}
+
+#[test]
+fn serve_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        let mut replies = Vec::new();
+        for packet in ["QStartNoAckMode", "m200,2", "s", "g", "D"] {
+            stream.write_all(encode(packet).as_bytes()).unwrap();
+            replies.push(read_reply(&mut stream));
+        }
+        replies
+    });
+
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut stub = GdbStub::new();
+    let (mut stream, _) = listener.accept().unwrap();
+    stub.serve(&mut cpu, &mut stream).unwrap();
+
+    assert_eq!(client.join().unwrap(), ["OK", "a901", "S05", "010000ff020220", "OK"]);
+}
@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::heatmap::{HeatKind, Heatmap};
+use m6052_emulator::ram::Ram;
+
+/*
+ * 0200  LDA $10
+ * 0202  STA $11
+ * 0204  JSR store
+ * 0207  JMP $0207
+ * 0210  store: STA $12
+ * 0212  LDA $12
+ * 0214  RTS
+ */
+fn heatmap() -> Heatmap {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA5, 0x10, 0x85, 0x11, 0x20, 0x10, 0x02, 0x4C, 0x07, 0x02]);
+    ram.load(0x0210, &[0x85, 0x12, 0xA5, 0x12, 0x60]);
+
+    let heatmap = Rc::new(RefCell::new(Heatmap::new()));
+    let mut cpu = CPU::new(&mut ram);
+    cpu.observers.push(Box::new(heatmap.clone()));
+    let mut cycles = 1000;
+    cpu.run_until(&mut cycles, |cpu| cpu.program_counter == 0x0207);
+    drop(cpu);
+    Rc::try_unwrap(heatmap).ok().unwrap().into_inner()
+}
+
+#[test]
+fn count_accesses() {
+    let heatmap = heatmap();
+
+    assert_eq!(heatmap.count(HeatKind::Read, 0x0010), 1);
+    assert_eq!(heatmap.count(HeatKind::Write, 0x0010), 0);
+    assert_eq!(heatmap.count(HeatKind::Write, 0x0011), 1);
+    assert_eq!(heatmap.count(HeatKind::Read, 0x0012), 1);
+    assert_eq!(heatmap.count(HeatKind::Write, 0x0012), 1);
+    assert_eq!(heatmap.count(HeatKind::Execute, 0x0200), 1);
+    assert_eq!(heatmap.count(HeatKind::Execute, 0x0205), 1);
+    assert_eq!(heatmap.count(HeatKind::Execute, 0x0207), 0);
+    assert_eq!(heatmap.count(HeatKind::Write, 0x01FF), 1);
+    assert_eq!(heatmap.count(HeatKind::Read, 0x01FE), 1);
+}
+
+#[test]
+fn write_ppm() {
+    let heatmap = heatmap();
+    let mut image = Vec::new();
+    heatmap.write_ppm(HeatKind::Execute, &mut image).unwrap();
+
+    let header = b"P6\n256 256\n255\n";
+    assert_eq!(&image[..header.len()], header);
+    let pixels = &image[header.len()..];
+    assert_eq!(pixels.len(), 256 * 256 * 3);
+    let pixel = |address: usize| &pixels[address * 3..address * 3 + 3];
+    assert_eq!(pixel(0x0000), [0, 0, 0]);
+    assert_eq!(pixel(0x0207), [0, 0, 0]);
+    /* Every address ran once, so all of them are the busiest */
+    assert_eq!(pixel(0x0200), [255, 255, 255]);
+    assert_eq!(pixel(0x0214), [255, 255, 255]);
+}
+
+#[test]
+fn summarize_zero_page_and_stack() {
+    let summary = heatmap().summary();
+
+    assert_eq!(summary, "Zero page: R read, W written, B both
+    0123456789ABCDEF
+00: ................
+10: RWB.............
+20: ................
+30: ................
+40: ................
+50: ................
+60: ................
+70: ................
+80: ................
+90: ................
+A0: ................
+B0: ................
+C0: ................
+D0: ................
+E0: ................
+F0: ................
+3 of 256 zero page bytes used, busiest $12 with 1 reads and 1 writes
+Stack: 2 bytes deep at most ($01FE), 2 writes and 2 reads
+");
+    assert_eq!(Heatmap::new().summary().lines().skip(18).collect::<Vec<_>>(),
+               ["0 of 256 zero page bytes used", "Stack: unused"]);
+}
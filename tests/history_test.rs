@@ -0,0 +1,112 @@
+use m6052_emulator::breakpoint::{BreakpointKind, StopReason};
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::observer::{Access, AccessKind};
+use m6052_emulator::ram::Ram;
+
+/*
+ * 0200  LDA #$01
+ * 0202  STA $10
+ * 0204  LDA #$02
+ * 0206  STA $10
+ * 0208  JSR store
+ * 020B  STA $11
+ * 020D  JMP $020D
+ * 0220  store: LDA #$03
+ * 0222  STA $10
+ * 0224  RTS
+ */
+fn program() -> Ram {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0x85, 0x10, 0xA9, 0x02, 0x85, 0x10, 0x20, 0x20, 0x02, 0x85, 0x11, 0x4C, 0x0D, 0x02]);
+    ram.load(0x0220, &[0xA9, 0x03, 0x85, 0x10, 0x60]);
+    ram
+}
+
+fn run(cpu: &mut CPU) {
+    let mut cycles = 1000;
+    cpu.run_until(&mut cycles, |cpu| cpu.program_counter == 0x020D);
+}
+
+#[test]
+fn history_is_off_by_default() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    run(&mut cpu);
+
+    assert!(cpu.history.is_empty());
+    assert!(!cpu.step_back());
+    assert_eq!(cpu.program_counter, 0x020D);
+}
+
+#[test]
+fn step_back_restores_registers_and_memory() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    cpu.history.set_capacity(100);
+    run(&mut cpu);
+    assert_eq!(cpu.history.len(), 9);
+    let cycles = cpu.total_cycles;
+
+    assert!(cpu.step_back());
+    assert_eq!(cpu.program_counter, 0x020B);
+    assert_eq!(cpu.memory.get(0x11), 0x00);
+    assert_eq!(cpu.accumulator, 0x03);
+    assert_eq!(cpu.total_cycles, cycles - 3);
+
+    assert!(cpu.step_back());
+    assert_eq!(cpu.program_counter, 0x0224);
+    assert_eq!(cpu.stack_pointer, 0xFD);
+    assert_eq!(cpu.call_stack.depth(), 1);
+
+    assert!(cpu.run_back_to(0x0200));
+    assert_eq!(cpu.memory.get(0x10), 0x00);
+    assert_eq!(cpu.memory.get(0x01FF), 0x00);
+    assert_eq!(cpu.accumulator, 0x00);
+    assert_eq!(cpu.stack_pointer, 0xFF);
+    assert_eq!(cpu.total_cycles, 0);
+    assert!(!cpu.step_back());
+
+    run(&mut cpu);
+    assert_eq!(cpu.total_cycles, cycles);
+    assert_eq!(cpu.memory.get(0x10), 0x03);
+    assert_eq!(cpu.memory.get(0x11), 0x03);
+}
+
+#[test]
+fn history_is_bounded() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    cpu.history.set_capacity(3);
+    run(&mut cpu);
+
+    assert_eq!(cpu.history.len(), 3);
+    assert!(!cpu.run_back_to(0x0200));
+    assert_eq!(cpu.program_counter, 0x0222);
+    assert_eq!(cpu.memory.get(0x10), 0x02);
+}
+
+#[test]
+fn reverse_continue_to_writes_and_breakpoints() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    cpu.history.set_capacity(100);
+    run(&mut cpu);
+    let watchpoint = cpu.breakpoints.add(BreakpointKind::Write(0x10, 0x10));
+
+    let write = |value| StopReason::Watchpoint {
+        id: watchpoint,
+        access: Access { kind: AccessKind::Write, address: 0x10, value },
+    };
+    assert_eq!(cpu.reverse_continue(), Some(write(0x03)));
+    assert_eq!(cpu.program_counter, 0x0222);
+    assert_eq!(cpu.memory.get(0x10), 0x02);
+    assert_eq!(cpu.reverse_continue(), Some(write(0x02)));
+    assert_eq!(cpu.program_counter, 0x0206);
+
+    let breakpoint = cpu.breakpoints.add(BreakpointKind::Execute(0x0204));
+    assert_eq!(cpu.reverse_continue(), Some(StopReason::Breakpoint { id: breakpoint, address: 0x0204 }));
+    assert_eq!(cpu.reverse_continue(), Some(write(0x01)));
+    assert_eq!(cpu.program_counter, 0x0202);
+    assert_eq!(cpu.reverse_continue(), None);
+    assert_eq!(cpu.program_counter, 0x0200);
+}
@@ -0,0 +1,159 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use m6052_emulator::breakpoint::StopReason;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::decoder::DecodedInstruction;
+use m6052_emulator::hooks::HookAction;
+use m6052_emulator::monitor::Monitor;
+use m6052_emulator::observer::{Access, Observer};
+use m6052_emulator::ram::Ram;
+use m6052_emulator::runner::{Halt, Runner};
+
+/*
+ * 0200  LDA #$41
+ * 0202  JSR $FFD2
+ * 0205  LDX #$07
+ * 0207  JMP $0207
+ * FFD2  an illegal opcode, so the routine only works when hooked
+ */
+fn program() -> Ram {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x41, 0x20, 0xD2, 0xFF, 0xA2, 0x07, 0x4C, 0x07, 0x02]);
+    ram.load(0xFFD2, &[0x02]);
+    ram
+}
+
+#[test]
+fn replace_routines() {
+    let mut ram = program();
+    let mut output = Vec::new();
+    {
+        let mut cpu = CPU::new(&mut ram);
+        cpu.hooks.add(0xFFD2, |cpu: &mut CPU| {
+            output.push(cpu.accumulator);
+            cpu.memory.set(0x0300, cpu.accumulator);
+            HookAction::Return
+        });
+        cpu.run_until(&mut 100, |cpu| cpu.program_counter == 0x0207);
+
+        assert_eq!(cpu.x, 0x07);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+        assert_eq!(cpu.call_stack.depth(), 0);
+        assert_eq!(cpu.total_cycles, 16);
+        assert_eq!(cpu.memory.get(0x0300), 0x41);
+    }
+    assert_eq!(output, [0x41]);
+}
+
+#[test]
+fn returning_takes_the_place_of_an_instruction() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    cpu.hooks.add(0xFFD2, |_: &mut CPU| HookAction::Return);
+    cpu.history.set_capacity(10);
+
+    cpu.step(&mut 100);
+    cpu.step(&mut 100);
+    assert_eq!(cpu.program_counter, 0xFFD2);
+    assert_eq!(cpu.call_stack.depth(), 1);
+    cpu.step(&mut 100);
+    assert_eq!(cpu.program_counter, 0x0205);
+    assert_eq!(cpu.call_stack.depth(), 0);
+
+    assert!(cpu.step_back());
+    assert_eq!(cpu.program_counter, 0xFFD2);
+    assert_eq!(cpu.call_stack.depth(), 1);
+}
+
+#[test]
+fn continue_with_the_instruction() {
+    let mut ram = program();
+    let calls = Cell::new(0);
+    let mut cpu = CPU::new(&mut ram);
+    cpu.hooks.add(0xFFD2, |_: &mut CPU| HookAction::Return);
+    cpu.hooks.add(0x0205, |cpu: &mut CPU| {
+        calls.set(calls.get() + 1);
+        cpu.y = 0x09;
+        HookAction::Continue
+    });
+    cpu.run_until(&mut 100, |cpu| cpu.program_counter == 0x0207);
+
+    assert_eq!(calls.get(), 1);
+    assert_eq!((cpu.x, cpu.y), (0x07, 0x09));
+    assert_eq!(cpu.hooks.addresses(), [0x0205, 0xFFD2]);
+    assert!(cpu.hooks.remove(0x0205));
+    assert!(!cpu.hooks.remove(0x0205));
+    assert!(cpu.hooks.contains(0xFFD2));
+}
+
+#[derive(Default)]
+struct Events {
+    events: Vec<String>,
+}
+
+impl Observer for Events {
+    fn before_instruction(&mut self, _cpu: &CPU, instruction: &DecodedInstruction) {
+        self.events.push(format!("before ${:04X}", instruction.address));
+    }
+
+    fn memory_access(&mut self, access: &Access) {
+        self.events.push(format!("{:?} ${:04X}", access.kind, access.address));
+    }
+
+    fn after_instruction(&mut self, cpu: &CPU) {
+        self.events.push(format!("after ${:04X}", cpu.program_counter));
+    }
+}
+
+#[test]
+fn observers_see_returns_as_instructions() {
+    let mut ram = program();
+    let events = Rc::new(RefCell::new(Events::default()));
+    let mut cpu = CPU::new(&mut ram);
+    cpu.hooks.add(0xFFD2, |_: &mut CPU| HookAction::Return);
+    cpu.step(&mut 100);
+    cpu.step(&mut 100);
+    cpu.observers.push(Box::new(events.clone()));
+    cpu.step(&mut 100);
+
+    assert_eq!(events.borrow().events, ["before $FFD2", "Read $01FE", "Read $01FF", "after $0205"]);
+}
+
+#[test]
+fn hooked_illegal_opcodes_run() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    cpu.hooks.add(0xFFD2, |_: &mut CPU| HookAction::Return);
+    cpu.step(&mut 100);
+    assert_eq!(cpu.step_over(&mut 100), None);
+    assert_eq!(cpu.program_counter, 0x0205);
+
+    cpu.program_counter = 0x0200;
+    let mut monitor = Monitor::new();
+    monitor.execute(&mut cpu, "break 0205").unwrap();
+    assert!(monitor.execute(&mut cpu, "g").unwrap().starts_with("Breakpoint #0 hit at $0205"));
+
+    cpu.hooks.clear();
+    cpu.program_counter = 0xFFD2;
+    assert!(cpu.at_illegal_opcode());
+    assert_eq!(monitor.execute(&mut cpu, "g"), Err("Illegal opcode at $FFD2".to_string()));
+}
+
+#[test]
+fn continuing_at_illegal_opcodes_stops() {
+    let mut ram = program();
+    let calls = Cell::new(0);
+    let mut cpu = CPU::new(&mut ram);
+    cpu.hooks.add(0xFFD2, |_: &mut CPU| {
+        calls.set(calls.get() + 1);
+        HookAction::Continue
+    });
+    assert!(!cpu.at_illegal_opcode());
+
+    assert_eq!(Runner::new().run(&mut cpu), Halt::IllegalOpcode(0xFFD2));
+    assert_eq!(cpu.step(&mut 100), Some(StopReason::IllegalOpcode { address: 0xFFD2 }));
+    assert_eq!(cpu.program_counter, 0xFFD2);
+    assert_eq!(cpu.call_stack.depth(), 1);
+    drop(cpu);
+    assert_eq!(calls.get(), 2);
+}
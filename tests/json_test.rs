@@ -0,0 +1,42 @@
+use m6052_emulator::json::Json;
+
+#[test]
+fn parse_values() {
+    let value = Json::parse(r#" {"seq": 1, "ok": true, "none": null, "list": [1, -2.5, "x"], "nested": {"a": "b\"\\n\u0041"}} "#).unwrap();
+
+    assert_eq!(value.get("seq").and_then(Json::as_i64), Some(1));
+    assert_eq!(value.get("ok").and_then(Json::as_bool), Some(true));
+    assert_eq!(value.get("none"), Some(&Json::Null));
+    assert_eq!(value.get("list").and_then(Json::as_array).map(|list| list.len()), Some(3));
+    assert_eq!(value.get("list").unwrap().as_array().unwrap()[1], Json::Number(-2.5));
+    assert_eq!(value.get("nested").and_then(|nested| nested.get("a")).and_then(Json::as_str), Some("b\"\\nA"));
+}
+
+#[test]
+fn parse_surrogate_pairs() {
+    assert_eq!(Json::parse(r#""\ud83d\ude00""#).unwrap().as_str(), Some("\u{1F600}"));
+    assert_eq!(Json::parse(r#""\ud83dx""#).unwrap().as_str(), Some("\u{FFFD}x"));
+    assert_eq!(Json::parse(r#""\ud83d\u0041""#).unwrap().as_str(), Some("\u{FFFD}A"));
+}
+
+#[test]
+fn reject_invalid_input() {
+    assert!(Json::parse("{\"a\" 1}").is_err());
+    assert!(Json::parse("[1, 2").is_err());
+    assert!(Json::parse("\"open").is_err());
+    assert!(Json::parse("1 2").is_err());
+}
+
+#[test]
+fn serialize() {
+    let mut value = Json::object([
+        ("name", "say \"hi\"\n".into()),
+        ("count", 3i64.into()),
+        ("list", vec![Json::Bool(false), Json::Null].into()),
+    ]);
+    value.set("count", 4i64.into());
+    value.set("extra", 0.5.into());
+
+    assert_eq!(value.to_string(), r#"{"name":"say \"hi\"\n","count":4,"list":[false,null],"extra":0.5}"#);
+    assert_eq!(Json::parse(&value.to_string()).unwrap(), value);
+}
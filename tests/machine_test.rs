@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use m6052_emulator::breakpoint::BreakpointKind;
+use m6052_emulator::cheats::{CheatCode, CheatMemory};
+use m6052_emulator::console::Console;
+use m6052_emulator::cow_ram::CowRam;
+use m6052_emulator::cpu::{CPU, Memory};
+use m6052_emulator::machine::Machine;
+use m6052_emulator::ram::Ram;
+
+/* LDX $10; STX $11; JMP $0204 */
+fn program() -> CowRam {
+    let mut ram = CowRam::new();
+    ram.load(0x0200, &[0xA6, 0x10, 0x86, 0x11, 0x4C, 0x04, 0x02]);
+    ram.set(0x10, 0x01);
+    ram
+}
+
+#[test]
+fn clones_share_pages_until_written() {
+    let ram = program();
+    let mut clone = ram.clone();
+    assert_eq!(ram.shared_pages(&clone), 256);
+
+    clone.set(0x0211, 0xFF);
+    clone.set(0x02FF, 0xFF);
+    assert_eq!(ram.shared_pages(&clone), 255);
+    assert_eq!(ram.get(0x0211), 0x00);
+    assert_eq!(clone.get(0x0211), 0xFF);
+
+    /* Writing the value already there doesn't copy the page */
+    clone.set(0x1000, 0x00);
+    assert_eq!(ram.shared_pages(&clone), 255);
+}
+
+#[test]
+fn copy_from_memory() {
+    let mut ram = Ram::new();
+    ram.load(0xFFFC, &[0x00, 0x02]);
+    let copy = CowRam::from_memory(&ram);
+
+    assert_eq!(copy.get(0xFFFD), 0x02);
+    assert_eq!(copy.get(0xFFFB), 0x00);
+}
+
+#[test]
+fn forks_run_independently() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    let mut cycles = 100;
+    cpu.breakpoints.add(BreakpointKind::Execute(0x0204));
+    cpu.step(&mut cycles);
+
+    let mut machine = cpu.fork();
+    assert_eq!(machine.registers, cpu.registers());
+    assert_eq!(machine.total_cycles, 3);
+
+    cpu.step(&mut cycles);
+    assert_eq!(cpu.memory.get(0x11), 0x01);
+    assert_eq!(machine.memory.get(0x11), 0x00);
+
+    let mut other = machine.fork();
+    let reason = machine.with_cpu(|cpu| {
+        cpu.x = 0x05;
+        cpu.run(&mut 100)
+    });
+    assert!(reason.is_some());
+    assert_eq!(machine.memory.get(0x11), 0x05);
+    assert_eq!(machine.registers.program_counter, 0x0204);
+    assert_eq!(machine.total_cycles, 6);
+    assert_eq!(machine.breakpoints.iter().next().unwrap().hits, 1);
+
+    other.with_cpu(|cpu| cpu.step(&mut 100));
+    assert_eq!(other.memory.get(0x11), 0x01);
+    assert_eq!(other.breakpoints.iter().next().unwrap().hits, 0);
+}
+
+#[test]
+fn new_machines_start_like_new_cpus() {
+    let mut machine = Machine::new(Box::new(program()));
+    let mut ram = program();
+
+    assert_eq!(machine.registers, CPU::new(&mut ram).registers());
+    machine.with_cpu(|cpu| cpu.step(&mut 100));
+    assert_eq!(machine.registers.x, 0x01);
+}
+
+#[test]
+fn forks_get_devices_of_their_own() {
+    /* LDX $10; STX $11 with the console output at $11 and a cheat freezing $10 */
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut console = Console::new(CheatMemory::new(program()));
+    console.map_output(0x11, output.clone());
+    let cheats = console.inner.cheats.clone();
+    cheats.borrow_mut().add(CheatCode { address: 0x10, value: 0x07, compare: None });
+    let cpu = CPU::new(&mut console);
+
+    let mut machine = cpu.fork();
+    cheats.borrow_mut().clear();
+    machine.with_cpu(|cpu| cpu.run(&mut 6));
+    assert!(output.borrow().is_empty());
+    assert_eq!(machine.memory.get(0x11), 0x07);
+
+    let fork_output = Rc::new(RefCell::new(Vec::new()));
+    let mut fork = machine.fork();
+    let mut device = Console::new(fork.memory);
+    device.map_output(0x11, fork_output.clone());
+    fork.memory = Box::new(device);
+    fork.registers.program_counter = 0x0200;
+    fork.with_cpu(|cpu| cpu.run(&mut 6));
+    assert_eq!(*fork_output.borrow(), vec![0x07]);
+    assert!(output.borrow().is_empty());
+}
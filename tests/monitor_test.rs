@@ -0,0 +1,197 @@
+use std::env;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::monitor::Monitor;
+use m6052_emulator::ram::Ram;
+use m6052_emulator::state::MachineState;
+
+#[test]
+fn change_registers() {
+    let mut ram = Ram::new();
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+
+    monitor.execute(&mut cpu, "r A=42 X=+10 PC=C000 p=%10000001").unwrap();
+
+    assert_eq!(cpu.accumulator, 0x42);
+    assert_eq!(cpu.x, 10);
+    assert_eq!(cpu.program_counter, 0xC000);
+    assert!(cpu.negative);
+    assert!(cpu.carry);
+    assert!(monitor.execute(&mut cpu, "r Q=1").is_err());
+}
+
+#[test]
+fn edit_and_dump_memory() {
+    let mut ram = Ram::new();
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+
+    monitor.execute(&mut cpu, "> 1000 48 69 21").unwrap();
+
+    assert_eq!(monitor.execute(&mut cpu, "m 1000 1003"),
+               Ok(">1000  48 69 21 00                                      Hi!.".to_string()));
+}
+
+#[test]
+fn assemble_and_disassemble() {
+    let mut ram = Ram::new();
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+    monitor.symbols.insert("start", 0x0200);
+
+    monitor.execute(&mut cpu, "a 0200 LDA #$01").unwrap();
+    monitor.execute(&mut cpu, "a 0202 STA $1000").unwrap();
+    monitor.execute(&mut cpu, "a 0205 BNE start").unwrap();
+
+    assert_eq!(monitor.execute(&mut cpu, "d 0200 0206"), Ok("\
+start:
+.0200  A9 01     LDA #$01
+.0202  8D 00 10  STA $1000
+.0205  D0 F9     BNE $0200".to_string()));
+}
+
+#[test]
+fn step_and_run_to_breakpoint() {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0xA2, 0x02, 0xA0, 0x03, 0x8D, 0x00, 0x10, 0x00]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+
+    let output = monitor.execute(&mut cpu, "z").unwrap();
+    assert!(output.ends_with(".0202  A2 02     LDX #$02"));
+    assert_eq!(cpu.accumulator, 0x01);
+
+    monitor.execute(&mut cpu, "break 0206 if Y == 3").unwrap();
+    let output = monitor.execute(&mut cpu, "g").unwrap();
+    assert!(output.starts_with("Breakpoint #0 hit at $0206"));
+    assert_eq!(cpu.program_counter, 0x0206);
+
+    monitor.execute(&mut cpu, "watch w 1000").unwrap();
+    let output = monitor.execute(&mut cpu, "g").unwrap();
+    assert!(output.starts_with("Watchpoint #1 hit by a write of $01 at $1000"));
+
+    monitor.execute(&mut cpu, "delete 1").unwrap();
+    assert!(monitor.execute(&mut cpu, "delete 1").is_err());
+    assert_eq!(monitor.execute(&mut cpu, "break").unwrap(), "#0 exec $0206 (conditional) hits: 1");
+}
+
+#[test]
+fn run_stops_at_illegal_opcodes() {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+
+    let output = monitor.execute(&mut cpu, "g").unwrap();
+
+    assert!(output.starts_with("Illegal opcode at $0202"));
+    assert!(monitor.execute(&mut cpu, "z").is_err());
+    assert_eq!(monitor.execute(&mut cpu, "g"), Err("Illegal opcode at $0202".to_string()));
+}
+
+#[test]
+fn return_stops_at_illegal_opcodes() {
+    /* $0200: JSR $0300; $0300: LDA #$01 followed by an illegal opcode */
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0x20, 0x00, 0x03]);
+    ram.load(0x0300, &[0xA9, 0x01, 0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+
+    monitor.execute(&mut cpu, "z").unwrap();
+    assert!(monitor.execute(&mut cpu, "ret").unwrap().starts_with("Illegal opcode at $0302"));
+    assert_eq!(cpu.accumulator, 0x01);
+    assert_eq!(monitor.execute(&mut cpu, "ret"), Err("Illegal opcode at $0302".to_string()));
+}
+
+#[test]
+fn save_and_load_state() {
+    let path = env::temp_dir().join(format!("m6502-monitor-{}.state", std::process::id()));
+    let command = |name: &str| format!("{name} {}", path.display());
+
+    let mut ram = Ram::new();
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+    monitor.execute(&mut cpu, "r A=12 PC=0400").unwrap();
+    monitor.execute(&mut cpu, "> 2000 AA").unwrap();
+    let saved = MachineState::capture(&cpu);
+    monitor.execute(&mut cpu, &command("savestate")).unwrap();
+
+    monitor.execute(&mut cpu, "r A=00 PC=0200").unwrap();
+    monitor.execute(&mut cpu, "> 2000 00").unwrap();
+    monitor.execute(&mut cpu, &command("loadstate")).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(MachineState::capture(&cpu), saved);
+    assert_eq!(cpu.memory.get(0x2000), 0xAA);
+}
+
+#[test]
+fn load_and_save_labels() {
+    let path = env::temp_dir().join(format!("m6502-monitor-{}.labels", std::process::id()));
+    std::fs::write(&path, "main = $0200\nal C:0204 .loop\n").unwrap();
+
+    let mut ram = Ram::new();
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+    let output = monitor.execute(&mut cpu, &format!("ll {}", path.display())).unwrap();
+    assert_eq!(output, format!("Loaded 2 labels from {}", path.display()));
+
+    assert_eq!(monitor.execute(&mut cpu, "shl 0206").unwrap(), "$0206 loop+2");
+    assert_eq!(monitor.execute(&mut cpu, "shl").unwrap(), "$0200 main\n$0204 loop");
+    monitor.execute(&mut cpu, "r PC=.loop").unwrap();
+    assert_eq!(cpu.program_counter, 0x0204);
+
+    monitor.execute(&mut cpu, &format!("sl {}", path.display())).unwrap();
+    let saved = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(saved, "al C:0200 .main\nal C:0204 .loop\n");
+}
+
+#[test]
+fn step_and_run_backwards() {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0x8D, 0x00, 0x10, 0xA9, 0x02, 0x8D, 0x00, 0x10, 0xA0, 0x03, 0x00]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+
+    assert!(monitor.execute(&mut cpu, "bz").is_err());
+    assert_eq!(monitor.execute(&mut cpu, "history 100").unwrap(), "History of 0 of 256 instructions");
+    monitor.execute(&mut cpu, "z 5").unwrap();
+    assert_eq!(monitor.execute(&mut cpu, "history").unwrap(), "History of 5 of 256 instructions");
+
+    let output = monitor.execute(&mut cpu, "bz").unwrap();
+    assert!(output.ends_with(".020A  A0 03     LDY #$03"));
+    monitor.execute(&mut cpu, "watch w 1000").unwrap();
+    let output = monitor.execute(&mut cpu, "bg").unwrap();
+    assert!(output.starts_with("Watchpoint #0 hit by a write of $02 at $1000"));
+    assert!(output.ends_with(".0207  8D 00 10  STA $1000"));
+    assert_eq!(cpu.memory.get(0x1000), 0x01);
+
+    let output = monitor.execute(&mut cpu, "bg 0").unwrap();
+    assert!(output.starts_with("Reached the start of the history"));
+    assert_eq!(cpu.program_counter, 0x0200);
+    assert!(monitor.execute(&mut cpu, "bz").is_err());
+}
+
+#[test]
+fn search_memory() {
+    let mut ram = Ram::new();
+    let mut cpu = CPU::new(&mut ram);
+    let mut monitor = Monitor::new();
+
+    assert!(monitor.execute(&mut cpu, "narrow changed").is_err());
+    assert!(monitor.execute(&mut cpu, "search x").is_err());
+    assert_eq!(monitor.execute(&mut cpu, "search b 1FF"), Err("$1FF doesn't fit the search width".to_string()));
+    monitor.execute(&mut cpu, "> 10 03 00").unwrap();
+    monitor.execute(&mut cpu, "> 20 03").unwrap();
+    assert_eq!(monitor.execute(&mut cpu, "search b 3").unwrap(), "2 matches\n$0010: $03\n$0020: $03");
+
+    monitor.execute(&mut cpu, "> 10 02").unwrap();
+    assert_eq!(monitor.execute(&mut cpu, "narrow dec 1").unwrap(), "1 match\n$0010: $02");
+    assert_eq!(monitor.execute(&mut cpu, "narrow unchanged").unwrap(), "1 match\n$0010: $02");
+
+    assert_eq!(monitor.execute(&mut cpu, "search w").unwrap(), "65535 matches");
+    monitor.execute(&mut cpu, "> 10 00 01").unwrap();
+    assert_eq!(monitor.execute(&mut cpu, "narrow inc FE").unwrap(), "1 match\n$0010: $0100");
+}
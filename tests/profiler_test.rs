@@ -0,0 +1,144 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::profiler::{Counter, Profiler, SubroutineProfile};
+use m6052_emulator::ram::Ram;
+use m6052_emulator::symbols::SymbolTable;
+
+/*
+ * 0200  JSR outer
+ * 0203  JSR outer
+ * 0206  JMP $0206
+ * 0210  outer: LDA #$01
+ * 0212  JSR inner
+ * 0215  RTS
+ * 0220  inner: LDX $0300
+ * 0223  RTS
+ */
+fn profile() -> Profiler {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0x20, 0x10, 0x02, 0x20, 0x10, 0x02, 0x4C, 0x06, 0x02]);
+    ram.load(0x0210, &[0xA9, 0x01, 0x20, 0x20, 0x02, 0x60]);
+    ram.load(0x0220, &[0xAE, 0x00, 0x03, 0x60]);
+
+    let profiler = Rc::new(RefCell::new(Profiler::new()));
+    let mut cpu = CPU::new(&mut ram);
+    cpu.observers.push(Box::new(profiler.clone()));
+    let mut cycles = 1000;
+    cpu.run_until(&mut cycles, |cpu| cpu.program_counter == 0x0206);
+    drop(cpu);
+    Rc::try_unwrap(profiler).ok().unwrap().into_inner()
+}
+
+#[test]
+fn count_addresses_and_opcodes() {
+    let profiler = profile();
+
+    assert_eq!(profiler.total_cycles, 60);
+    assert_eq!(profiler.instructions, 12);
+    assert_eq!(profiler.addresses[&0x0220], Counter { count: 2, cycles: 8 });
+    assert_eq!(profiler.addresses[&0x0200], Counter { count: 1, cycles: 6 });
+    assert_eq!(profiler.opcodes[&0x20], Counter { count: 4, cycles: 24 });
+    assert_eq!(profiler.opcodes[&0x60], Counter { count: 4, cycles: 24 });
+}
+
+#[test]
+fn attribute_cycles_to_subroutines() {
+    let profiler = profile();
+
+    assert_eq!(profiler.subroutines[&None], SubroutineProfile { calls: 0, inclusive: 60, exclusive: 12 });
+    assert_eq!(profiler.subroutines[&Some(0x0210)], SubroutineProfile { calls: 2, inclusive: 48, exclusive: 28 });
+    assert_eq!(profiler.subroutines[&Some(0x0220)], SubroutineProfile { calls: 2, inclusive: 20, exclusive: 20 });
+}
+
+#[test]
+fn symbolized_report() {
+    let profiler = profile();
+    let mut symbols = SymbolTable::new();
+    symbols.insert("outer", 0x0210);
+    symbols.insert("inner", 0x0220);
+
+    let report = profiler.report(Some(&symbols), 2);
+    assert_eq!(report, "\
+60 cycles in 12 instructions
+
+Hot spots
+    Cycles       %     Count  Address
+        12  20.00%         2  $0212 outer+2
+        12  20.00%         2  $0215 outer+5
+
+Subroutines
+ Inclusive       %  Exclusive       %     Calls  Subroutine
+        60 100.00%         12  20.00%         0  (top level)
+        48  80.00%         28  46.67%         2  $0210 outer
+
+Opcodes
+    Cycles       %     Count  Opcode
+        24  40.00%         4  $20 JSR Absolute
+        24  40.00%         4  $60 RTS Implied
+");
+}
+
+#[test]
+fn folded_stacks() {
+    let profiler = profile();
+    let mut symbols = SymbolTable::new();
+    symbols.insert("outer", 0x0210);
+    symbols.insert("inner", 0x0220);
+
+    assert_eq!(profiler.folded_stacks(Some(&symbols)), "\
+(top level) 12
+(top level);outer 28
+(top level);outer;inner 20
+");
+}
+
+#[test]
+fn interrupt_handlers_are_roots() {
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0x00, 0x00, 0x4C, 0x02, 0x02]);
+    ram.load(0x0300, &[0x20, 0x10, 0x03, 0x40]);
+    ram.load(0x0310, &[0x60]);
+    ram.load(0xFFFE, &[0x00, 0x03]);
+
+    let profiler = Rc::new(RefCell::new(Profiler::new()));
+    let mut cpu = CPU::new(&mut ram);
+    cpu.observers.push(Box::new(profiler.clone()));
+    let mut cycles = 1000;
+    cpu.run_until(&mut cycles, |cpu| cpu.program_counter == 0x0202);
+    drop(cpu);
+
+    assert_eq!(profiler.borrow().folded_stacks(None), "\
+$0300 12
+$0300;$0310 6
+(top level) 7
+");
+}
+
+#[test]
+fn interrupts_charge_only_their_handler() {
+    /* $0200: JSR $0400; JMP $0203, $0400: NOP; NOP; RTS, interrupted at $0401 by LDA #$01; RTI */
+    let mut ram = Ram::new();
+    ram.load(0x0200, &[0x20, 0x00, 0x04, 0x4C, 0x03, 0x02]);
+    ram.load(0x0400, &[0xEA, 0xEA, 0x60]);
+    ram.load(0x0300, &[0xA9, 0x01, 0x40]);
+    ram.load(0xFFFE, &[0x00, 0x03]);
+
+    let profiler = Rc::new(RefCell::new(Profiler::new()));
+    let mut cpu = CPU::new(&mut ram);
+    cpu.observers.push(Box::new(profiler.clone()));
+    let mut cycles = 1000;
+    cpu.run_until(&mut cycles, |cpu| cpu.program_counter == 0x0401);
+    cpu.interrupt_request(&mut cycles);
+    cpu.run_until(&mut cycles, |cpu| cpu.program_counter == 0x0203);
+    let total_cycles = cpu.total_cycles;
+    drop(cpu);
+
+    let profiler = profiler.borrow();
+    assert_eq!(total_cycles, 29);
+    assert_eq!(profiler.total_cycles, total_cycles);
+    assert_eq!(profiler.instructions, 6);
+    assert_eq!(profiler.subroutines[&Some(0x0400)], SubroutineProfile { calls: 1, inclusive: 8, exclusive: 8 });
+    assert_eq!(profiler.subroutines[&Some(0x0300)], SubroutineProfile { calls: 1, inclusive: 15, exclusive: 15 });
+    assert_eq!(profiler.subroutines[&None], SubroutineProfile { calls: 0, inclusive: 29, exclusive: 6 });
+}
@@ -0,0 +1,149 @@
+use std::cell::RefCell;
+use std::io::{Cursor, ErrorKind};
+use std::rc::Rc;
+use m6052_emulator::breakpoint::{BreakpointKind, StopReason};
+use m6052_emulator::console::Console;
+use m6052_emulator::cpu::CPU;
+use m6052_emulator::hooks::HookAction;
+use m6052_emulator::ram::Ram;
+use m6052_emulator::runner::{Halt, Runner};
+
+fn program(bytes: &[u8]) -> Ram {
+    let mut ram = Ram::new();
+    ram.load(0x0200, bytes);
+    ram
+}
+
+#[test]
+fn stop_at_jump_to_itself() {
+    /* LDA #$01; JMP $0202 */
+    let mut ram = program(&[0xA9, 0x01, 0x4C, 0x02, 0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut runner = Runner::new();
+    runner.stop_on_trap = true;
+
+    assert_eq!(runner.run(&mut cpu), Halt::Trap(0x0202));
+    assert_eq!(cpu.accumulator, 0x01);
+}
+
+#[test]
+fn stop_at_branch_to_itself() {
+    let mut ram = program(&[0xD0, 0xFE]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut runner = Runner::new();
+    runner.stop_on_trap = true;
+
+    assert_eq!(runner.run(&mut cpu), Halt::Trap(0x0200));
+}
+
+#[test]
+fn branch_to_itself_falling_through() {
+    /* LDA #$01; BEQ *; LDX #$02; BNE * */
+    let mut ram = program(&[0xA9, 0x01, 0xF0, 0xFE, 0xA2, 0x02, 0xD0, 0xFE]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut runner = Runner::new();
+    runner.stop_on_trap = true;
+
+    assert_eq!(runner.run(&mut cpu), Halt::Trap(0x0206));
+    assert_eq!(cpu.x, 0x02);
+}
+
+#[test]
+fn stop_at_address() {
+    let mut ram = program(&[0xA9, 0x01, 0xA2, 0x02, 0xA0, 0x03]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut runner = Runner::new();
+    runner.stop_addresses.push(0x0204);
+
+    assert_eq!(runner.run(&mut cpu), Halt::Address(0x0204));
+    assert_eq!(cpu.x, 0x02);
+    assert_eq!(cpu.y, 0x00);
+}
+
+#[test]
+fn stop_at_break_and_illegal_opcodes() {
+    let mut ram = program(&[0xA9, 0x01, 0x00, 0x00, 0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut runner = Runner::new();
+    runner.stop_on_break = true;
+
+    assert_eq!(runner.run(&mut cpu), Halt::Break(0x0202));
+
+    let mut ram = program(&[0xA9, 0x01, 0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    assert_eq!(Runner::new().run(&mut cpu), Halt::IllegalOpcode(0x0202));
+}
+
+#[test]
+fn limits() {
+    /* LDA #$01 in an endless loop */
+    let mut ram = program(&[0xA9, 0x01, 0x4C, 0x00, 0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut runner = Runner::new();
+    runner.instruction_limit = Some(5);
+
+    assert_eq!(runner.run(&mut cpu), Halt::InstructionLimit);
+    assert_eq!(cpu.program_counter, 0x0202);
+    assert_eq!(cpu.total_cycles, 12);
+
+    let mut runner = Runner::new();
+    runner.cycle_limit = Some(100);
+    assert_eq!(runner.run(&mut cpu), Halt::CycleLimit);
+    assert!(cpu.total_cycles >= 112);
+}
+
+#[test]
+fn stop_at_breakpoint() {
+    let mut ram = program(&[0xA9, 0x01, 0xA2, 0x02, 0xA0, 0x03]);
+    let mut cpu = CPU::new(&mut ram);
+    let id = cpu.breakpoints.add(BreakpointKind::Execute(0x0202));
+
+    assert_eq!(Runner::new().run(&mut cpu), Halt::Breakpoint(StopReason::Breakpoint { id, address: 0x0202 }));
+}
+
+#[test]
+fn console() {
+    /* LDA $F004; STA $F001; LDA $F004; STA $F001; BRK */
+    let ram = program(&[0xAD, 0x04, 0xF0, 0x8D, 0x01, 0xF0, 0xAD, 0x04, 0xF0, 0x8D, 0x01, 0xF0, 0x00]);
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut console = Console::new(ram);
+    console.map_output(0xF001, output.clone());
+    console.map_input(0xF004, Rc::new(RefCell::new(Cursor::new(b"H".to_vec()))));
+    let mut cpu = CPU::new(&mut console);
+    let mut runner = Runner::new();
+    runner.stop_on_break = true;
+
+    assert_eq!(runner.run(&mut cpu), Halt::Break(0x020C));
+    assert_eq!(*output.borrow(), b"H\0");
+}
+
+#[test]
+fn hooked_illegal_opcodes_run() {
+    /* JSR $0300; JMP $0203, with an illegal opcode at $0300 */
+    let mut ram = program(&[0x20, 0x00, 0x03, 0x4C, 0x03, 0x02]);
+    ram.load(0x0300, &[0x02]);
+    let mut cpu = CPU::new(&mut ram);
+    let mut runner = Runner::new();
+    runner.stop_on_trap = true;
+
+    cpu.hooks.add(0x0300, |_: &mut CPU| HookAction::Return);
+    assert_eq!(runner.run(&mut cpu), Halt::Trap(0x0203));
+    cpu.hooks.clear();
+    cpu.program_counter = 0x0300;
+    assert_eq!(runner.run(&mut cpu), Halt::IllegalOpcode(0x0300));
+}
+
+#[test]
+fn halt_once_the_output_closed() {
+    /* LDA #$41; STA $F001; JMP $0200 */
+    let mut console = Console::new(program(&[0xA9, 0x41, 0x8D, 0x01, 0xF0, 0x4C, 0x00, 0x02]));
+    let output = Rc::new(RefCell::new(Cursor::new([0; 2])));
+    console.map_output(0xF001, output.clone());
+    let mut cpu = CPU::new(&mut console);
+    let mut runner = Runner::new();
+    runner.cycle_limit = Some(1000);
+
+    assert_eq!(runner.run(&mut cpu), Halt::OutputClosed);
+    assert_eq!(cpu.memory.output_error().map(|error| error.kind()), Some(ErrorKind::WriteZero));
+    assert_eq!(output.borrow().get_ref(), b"AA");
+}
@@ -0,0 +1,58 @@
+use m6052_emulator::cpu::Memory;
+use m6052_emulator::ram::Ram;
+use m6052_emulator::search::{Filter, MemorySearch, Width};
+
+#[test]
+fn narrow_down_bytes() {
+    let mut ram = Ram::new();
+    ram.load(0x0010, &[0x05]);
+    ram.load(0x0020, &[0x05]);
+    ram.load(0x0030, &[0x05]);
+
+    let mut search = MemorySearch::new(&ram, Width::Byte);
+    assert_eq!(search.candidates().len(), 0x10000);
+    assert!(search.filter(&ram, Filter::Equal(0x105)).is_err());
+    assert!(search.filter(&ram, Filter::IncreasedBy(0x100)).is_err());
+    assert_eq!(search.candidates().len(), 0x10000);
+    assert_eq!(search.filter(&ram, Filter::Equal(0x05)), Ok(3));
+
+    ram.set(0x0010, 0x06);
+    ram.set(0x0020, 0x04);
+    assert_eq!(search.filter(&ram, Filter::Changed), Ok(2));
+    assert_eq!(search.candidates(), [0x0010, 0x0020]);
+
+    ram.set(0x0010, 0x08);
+    ram.set(0x0020, 0x02);
+    let mut decreased = search.clone();
+    assert_eq!(search.filter(&ram, Filter::IncreasedBy(2)), Ok(1));
+    assert_eq!(search.candidates(), [0x0010]);
+    assert_eq!(decreased.filter(&ram, Filter::Decreased), Ok(1));
+    assert_eq!(decreased.candidates(), [0x0020]);
+
+    assert_eq!(search.filter(&ram, Filter::Unchanged), Ok(1));
+    assert_eq!(search.previous(0x0010), 0x08);
+}
+
+#[test]
+fn narrow_down_words() {
+    let mut ram = Ram::new();
+    ram.load(0x0040, &[0x34, 0x12]);
+
+    let mut search = MemorySearch::new(&ram, Width::Word);
+    assert_eq!(search.candidates().len(), 0xFFFF);
+    assert_eq!(search.filter(&ram, Filter::Equal(0x1234)), Ok(1));
+
+    ram.load(0x0040, &[0x00, 0x13]);
+    assert_eq!(search.filter(&ram, Filter::IncreasedBy(0xCC)), Ok(1));
+    assert_eq!(search.value(&ram, 0x0040), 0x1300);
+}
+
+#[test]
+fn differences_wrap_around() {
+    assert!(Filter::IncreasedBy(1).matches(0xFF, 0x00, Width::Byte));
+    assert!(Filter::IncreasedBy(1).matches(0xFFFF, 0x0000, Width::Word));
+    assert!(!Filter::IncreasedBy(1).matches(0xFF, 0x00, Width::Word));
+    assert!(Filter::DecreasedBy(2).matches(0x0001, 0xFFFF, Width::Word));
+    assert!(!Filter::Equal(0x1FF).matches(0, 0xFF, Width::Byte));
+    assert!(!Filter::Increased.matches(0x05, 0x05, Width::Byte));
+}
@@ -0,0 +1,113 @@
+use m6052_emulator::cow_ram::CowRam;
+use m6052_emulator::cpu::{CPU, Memory};
+use m6052_emulator::history::Overwrite;
+use m6052_emulator::ram::Ram;
+use m6052_emulator::state::{MachineState, StateDelta};
+
+/* LDA #$01; STA $1000; LDA #$02; STA $1001; STA $2000; BRK */
+fn program() -> CowRam {
+    let mut ram = CowRam::new();
+    ram.load(0x0200, &[0xA9, 0x01, 0x8D, 0x00, 0x10, 0xA9, 0x02, 0x8D, 0x01, 0x10, 0x8D, 0x00, 0x20, 0x00]);
+    ram
+}
+
+#[test]
+fn track_dirty_pages() {
+    let mut ram = program();
+    assert_eq!(ram.dirty_pages(), [0x02]);
+    assert_eq!(ram.checkpoint(), Some(vec![0x02]));
+    assert!(ram.dirty_pages().is_empty());
+
+    ram.set(0x1000, 0x00);
+    assert!(!ram.is_dirty(0x10));
+    ram.set(0x10FF, 0x01);
+    ram.set(0x2000, 0x01);
+    assert_eq!(ram.checkpoint(), Some(vec![0x10, 0x20]));
+    assert_eq!(Ram::new().checkpoint(), None);
+}
+
+#[test]
+fn diff_snapshots() {
+    let mut ram = program();
+    let snapshot = ram.clone();
+    ram.set(0x0201, 0x05);
+    ram.set(0x1000, 0x07);
+    ram.set(0x2000, 0x01);
+    ram.set(0x2000, 0x00);
+
+    assert_eq!(snapshot.diff(&ram), [
+        Overwrite { address: 0x0201, old: 0x01, new: 0x05 },
+        Overwrite { address: 0x1000, old: 0x00, new: 0x07 },
+    ]);
+    assert!(ram.diff(&ram.clone()).is_empty());
+    assert_eq!(ram.page(0x10)[0], 0x07);
+}
+
+#[test]
+fn apply_deltas_on_top_of_a_state() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    cpu.memory.checkpoint();
+    let base = MachineState::capture(&cpu);
+
+    cpu.step(&mut 100);
+    cpu.step(&mut 100);
+    let first = StateDelta::capture(&mut cpu);
+    assert_eq!(first.pages.keys().copied().collect::<Vec<_>>(), [0x10]);
+    cpu.step(&mut 100);
+    cpu.step(&mut 100);
+    cpu.step(&mut 100);
+    let second = StateDelta::capture(&mut cpu);
+    assert_eq!(second.pages.keys().copied().collect::<Vec<_>>(), [0x10, 0x20]);
+    let end = MachineState::capture(&cpu);
+
+    let mut bytes = Vec::new();
+    second.write_to(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), 9 + 7 + 8 + 2 + 2 * 257);
+    assert_eq!(StateDelta::read_from(&mut bytes.as_slice()).unwrap(), second);
+
+    let mut other = Ram::new();
+    let mut restored = CPU::new(&mut other);
+    base.restore(&mut restored);
+    first.apply(&mut restored);
+    assert_eq!(restored.program_counter, 0x0205);
+    assert_eq!(restored.memory.get(0x1000), 0x01);
+    second.apply(&mut restored);
+    assert_eq!(MachineState::capture(&restored), end);
+
+    /* Memory without dirty tracking ends up in the delta completely */
+    assert_eq!(StateDelta::capture(&mut restored).pages.len(), 256);
+}
+
+#[test]
+fn reject_full_states_as_deltas() {
+    let mut ram = program();
+    let cpu = CPU::new(&mut ram);
+    let mut bytes = Vec::new();
+    MachineState::capture(&cpu).write_to(&mut bytes).unwrap();
+
+    assert!(StateDelta::read_from(&mut bytes.as_slice()).is_err());
+    assert_eq!(MachineState::read_from(&mut bytes.as_slice()).unwrap(), MachineState::capture(&cpu));
+}
+
+#[test]
+fn applied_pages_are_not_captured_again() {
+    let mut ram = program();
+    let mut cpu = CPU::new(&mut ram);
+    cpu.memory.checkpoint();
+    let base = MachineState::capture(&cpu);
+    cpu.step(&mut 100);
+    cpu.step(&mut 100);
+    let delta = StateDelta::capture(&mut cpu);
+
+    let mut other = CowRam::new();
+    let mut restored = CPU::new(&mut other);
+    base.restore(&mut restored);
+    assert!(StateDelta::capture(&mut restored).pages.is_empty());
+    delta.apply(&mut restored);
+    assert!(StateDelta::capture(&mut restored).pages.is_empty());
+
+    restored.step(&mut 100);
+    restored.step(&mut 100);
+    assert_eq!(StateDelta::capture(&mut restored).pages.keys().copied().collect::<Vec<_>>(), [0x10]);
+}
@@ -0,0 +1,58 @@
+use m6052_emulator::symbols::SymbolTable;
+
+#[test]
+fn parse_vice_labels() {
+    let symbols = SymbolTable::parse("al C:0810 .main\nal C:d020 .border\nal 00fb .lives\n").unwrap();
+
+    assert_eq!(symbols.address_of("main"), Some(0x0810));
+    assert_eq!(symbols.address_of("border"), Some(0xD020));
+    assert_eq!(symbols.address_of("lives"), Some(0x00FB));
+}
+
+#[test]
+fn parse_assignments() {
+    let text = "\
+; ACME symbol list
+\t!addr\tborder\t= $d020
+\tmain\t= $0810\t; ?
+loop            = $0815
+count := 3
+title = \"HELLO\"
+ratio = 1.5
+mask = %1010
+vector = 0xFFFE
+";
+    let symbols = SymbolTable::parse(text).unwrap();
+
+    assert_eq!(symbols.address_of("border"), Some(0xD020));
+    assert_eq!(symbols.address_of("main"), Some(0x0810));
+    assert_eq!(symbols.address_of("loop"), Some(0x0815));
+    assert_eq!(symbols.address_of("count"), Some(3));
+    assert_eq!(symbols.address_of("mask"), Some(0x000A));
+    assert_eq!(symbols.address_of("vector"), Some(0xFFFE));
+    assert_eq!(symbols.address_of("title"), None);
+    assert_eq!(symbols.len(), 6);
+}
+
+#[test]
+fn reject_invalid_lines() {
+    assert_eq!(SymbolTable::parse("main\nloop = $10").unwrap_err(),
+               "Line 1: Expected 'al address .name' or 'name = address'");
+    assert!(SymbolTable::parse("al C:xyz .main").is_err());
+    assert!(SymbolTable::parse("two words = $10").is_err());
+}
+
+#[test]
+fn nearest_label_and_export() {
+    let mut symbols = SymbolTable::parse("main = $0810\nloop = $0815").unwrap();
+    symbols.merge(&SymbolTable::parse("al C:0800 .start\nal C:0815 .again").unwrap());
+
+    assert_eq!(symbols.lookup(0x0818), Some(("loop", 3)));
+    assert_eq!(symbols.lookup(0x0810), Some(("main", 0)));
+    assert_eq!(symbols.lookup(0x07FF), None);
+
+    let exported = symbols.to_vice_labels();
+    assert_eq!(exported, "al C:0800 .start\nal C:0810 .main\nal C:0815 .loop\nal C:0815 .again\n");
+    let reloaded = SymbolTable::parse(&exported).unwrap();
+    assert_eq!(reloaded.iter().collect::<Vec<_>>(), symbols.iter().collect::<Vec<_>>());
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use std::borrow::BorrowMut;
+use std::cell::RefCell;
+use std::io::ErrorKind;
+use std::rc::Rc;
+use common::TestMemory;
+use m6052_emulator::cpu::{CPU, Memory};
+use m6052_emulator::trace::{diff, disassemble, format_line, Divergence, Tracer};
+
+#[test]
+fn nestest_first_line() {
+    let mut memory = TestMemory::new();
+    memory.load(0xC000, &[0x4C, 0xF5, 0xC5]);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.program_counter = 0xC000;
+    cpu.stack_pointer = 0xFD;
+    cpu.interrupt = true;
+    cpu.total_cycles = 7;
+
+    let instruction = cpu.next_instruction().unwrap();
+    assert_eq!(format_line(&cpu, &instruction),
+               "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7");
+}
+
+#[test]
+fn disassemble_annotates_memory() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xB5, 0x10]);
+    memory.load(0x0202, &[0xBD, 0x00, 0x03]);
+    memory.load(0x0205, &[0xA1, 0x80]);
+    memory.load(0x0207, &[0xB1, 0x89]);
+    memory.load(0x0209, &[0x6C, 0x00, 0x02]);
+    memory.load(0x0082, &[0x00, 0x03]);
+    memory.load(0x0089, &[0x00, 0x03]);
+    memory.set(0x0012, 0xAA);
+    memory.set(0x0302, 0x89);
+    memory.set(0x0300, 0x5A);
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.x = 0x02;
+    cpu.y = 0x02;
+
+    let mut lines = Vec::new();
+    for address in [0x0200, 0x0202, 0x0205, 0x0207, 0x0209] {
+        cpu.program_counter = address;
+        let instruction = cpu.next_instruction().unwrap();
+        lines.push(disassemble(&cpu, &instruction));
+    }
+
+    assert_eq!(lines, vec![
+        "LDA $10,X @ 12 = AA",
+        "LDA $0300,X @ 0302 = 89",
+        "LDA ($80,X) @ 82 = 0300 = 5A",
+        "LDA ($89),Y = 0300 @ 0302 = 89",
+        "JMP ($0200) = 10B5",
+    ]);
+}
+
+#[test]
+fn tracer_logs_each_instruction() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xA9, 0x42, 0xAA]);
+
+    let tracer = Rc::new(RefCell::new(Tracer::new(Vec::new())));
+
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.observers.push(Box::new(tracer.clone()));
+
+    let mut cycles = 4;
+    cpu.run(&mut cycles);
+    drop(cpu);
+
+    let output = String::from_utf8(tracer.borrow().get_ref().clone()).unwrap();
+    assert_eq!(output,
+               "0200  A9 42     LDA #$42                        A:00 X:00 Y:00 P:20 SP:FF PPU:  0,  0 CYC:0\n\
+                0202  AA        TAX                             A:42 X:00 Y:00 P:20 SP:FF PPU:  0,  6 CYC:2\n");
+}
+
+#[test]
+fn tracer_stops_at_write_errors() {
+    let mut memory = TestMemory::new();
+    memory.load(0x0200, &[0xA9, 0x42, 0xA2, 0x07, 0xA0, 0x03]);
+
+    /* Room for the first line only */
+    let mut buffer = [0; 100];
+    let tracer = Rc::new(RefCell::new(Tracer::new(&mut buffer[..])));
+    let mut cpu = CPU::new(memory.borrow_mut());
+    cpu.observers.push(Box::new(tracer.clone()));
+
+    cpu.run(&mut 6);
+    assert_eq!(cpu.y, 0x03);
+    drop(cpu);
+
+    assert_eq!(tracer.borrow().error().map(|error| error.kind()), Some(ErrorKind::WriteZero));
+}
+
+#[test]
+fn diff_identical_traces() {
+    let produced = "C000  4C F5 C5\nC5F5  A2 00\n";
+    let reference = "C000  4C F5 C5\r\nC5F5  A2 00\r\n";
+    assert_eq!(diff(produced.as_bytes(), reference.as_bytes()).unwrap(), None);
+}
+
+#[test]
+fn diff_reports_first_divergence() {
+    let produced = "C000  4C F5 C5\nC5F5  A2 01\nC5F7  86 00\n";
+    let reference = "C000  4C F5 C5\nC5F5  A2 00\nC5F7  86 00\n";
+    assert_eq!(diff(produced.as_bytes(), reference.as_bytes()).unwrap(), Some(Divergence {
+        line: 2,
+        produced: Some("C5F5  A2 01".to_string()),
+        reference: Some("C5F5  A2 00".to_string()),
+    }));
+}
+
+#[test]
+fn diff_reports_truncated_trace() {
+    let produced = "C000  4C F5 C5\n";
+    let reference = "C000  4C F5 C5\nC5F5  A2 00\n";
+    assert_eq!(diff(produced.as_bytes(), reference.as_bytes()).unwrap(), Some(Divergence {
+        line: 2,
+        produced: None,
+        reference: Some("C5F5  A2 00".to_string()),
+    }));
+}